@@ -0,0 +1,344 @@
+use crate::pinyin_index::char_syllables;
+use icu_collator::{Collator, CollatorOptions};
+use icu_locid::locale;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+/// 名称的排序方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CollationOrder {
+    /// 按拼音排序，读音相同时笔画少的在前；英文字母与汉字按拼写混排
+    #[default]
+    Pinyin,
+    /// 按笔画数排序，笔画相同时按部首；数字和英文排在汉字之前
+    Stroke,
+    /// 按 Unicode 码位排序
+    Unicode,
+}
+
+/// 笔画排序实际使用的规则
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StrokeCollationSupport {
+    /// CLDR 的笔画排序规则
+    Stroke,
+    /// 编译进来的数据缺少笔画规则，退回根排序规则
+    Root,
+    /// 排序规则都不可用，按码位比较
+    CodePoint,
+}
+
+/// CLDR 的笔画排序规则（先比较总笔画数，再比较部首）
+static STROKE_COLLATOR: Lazy<(Option<Collator>, StrokeCollationSupport)> =
+    Lazy::new(stroke_collator);
+
+/// 构建笔画排序规则：编译进来的数据缺少笔画规则时退回根排序规则，都不可用时按码位比较
+fn stroke_collator() -> (Option<Collator>, StrokeCollationSupport) {
+    if let Ok(collator) =
+        Collator::try_new(&locale!("zh-u-co-stroke").into(), CollatorOptions::new())
+    {
+        return (Some(collator), StrokeCollationSupport::Stroke);
+    }
+    match Collator::try_new(&Default::default(), CollatorOptions::new()) {
+        Ok(collator) => (Some(collator), StrokeCollationSupport::Root),
+        Err(_) => (None, StrokeCollationSupport::CodePoint),
+    }
+}
+
+/// 笔画排序实际使用的规则，供前端提示排序结果可能不准确
+pub fn stroke_collation_support() -> StrokeCollationSupport {
+    STROKE_COLLATOR.1
+}
+
+/// 排序时的最小单位
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Unit {
+    /// 标点和其他符号，排在最前
+    Symbol(char),
+    /// 连续的数字，按数值比较（5G 排在 10G 之前）
+    Number(String),
+    /// 英文字母
+    Letter(char),
+    /// 汉字及其读音（没有读音的生僻字为 None）
+    Han(char, Option<&'static str>),
+}
+
+impl Unit {
+    /// 大类的先后：符号 → 数字 → 字母和有读音的汉字 → 没有读音的汉字
+    fn class(&self, order: CollationOrder) -> u8 {
+        match (self, order) {
+            (Unit::Symbol(_), _) => 0,
+            (Unit::Number(_), _) => 1,
+            (Unit::Letter(_), _) => 2,
+            (Unit::Han(_, Some(_)), CollationOrder::Pinyin) => 2,
+            (Unit::Han(..), _) => 3,
+        }
+    }
+
+    /// 第一级比较：拼音模式下字母和汉字都按拼写比较，笔画模式下汉字按笔画比较
+    fn compare_primary(&self, other: &Self, order: CollationOrder) -> Ordering {
+        self.class(order)
+            .cmp(&other.class(order))
+            .then_with(|| match (self, other) {
+                (Unit::Symbol(a), Unit::Symbol(b)) => a.cmp(b),
+                (Unit::Number(a), Unit::Number(b)) => compare_numbers(a, b),
+                // 笔画模式下的汉字和拼音模式下没有读音的汉字按笔画比较
+                (Unit::Han(a, _), Unit::Han(b, _)) if self.class(order) == 3 => {
+                    compare_strokes(*a, *b)
+                }
+                _ => self.spelling().cmp(&other.spelling()),
+            })
+    }
+
+    /// 第二级比较：拼写相同时字母在前，读音相同的汉字笔画少的在前
+    fn compare_secondary(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Unit::Letter(_), Unit::Han(..)) => Ordering::Less,
+            (Unit::Han(..), Unit::Letter(_)) => Ordering::Greater,
+            (Unit::Han(a, _), Unit::Han(b, _)) => compare_strokes(*a, *b),
+            _ => Ordering::Equal,
+        }
+    }
+
+    /// 第三级比较：小写字母在前
+    fn compare_tertiary(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Unit::Letter(a), Unit::Letter(b)) => {
+                b.is_ascii_lowercase().cmp(&a.is_ascii_lowercase())
+            }
+            _ => Ordering::Equal,
+        }
+    }
+
+    fn spelling(&self) -> String {
+        match self {
+            Unit::Letter(ch) => ch.to_ascii_lowercase().to_string(),
+            Unit::Han(_, Some(syllable)) => syllable.to_string(),
+            Unit::Han(ch, None) | Unit::Symbol(ch) => ch.to_string(),
+            Unit::Number(digits) => digits.clone(),
+        }
+    }
+}
+
+/// 预先计算的排序键，用于对同一批名称反复比较
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CollationKey {
+    order: CollationOrder,
+    units: Vec<Unit>,
+    text: String,
+}
+
+impl CollationKey {
+    pub fn new(text: &str, order: CollationOrder) -> Self {
+        let units = match order {
+            CollationOrder::Unicode => Vec::new(),
+            CollationOrder::Pinyin | CollationOrder::Stroke => units_of(text),
+        };
+        Self {
+            order,
+            units,
+            text: text.to_string(),
+        }
+    }
+}
+
+impl Ord for CollationKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        if self.order == CollationOrder::Unicode {
+            return self.text.cmp(&other.text);
+        }
+        // 逐级比较，前一级完全相同时才比较下一级，最后按码位保证顺序确定
+        compare_units(&self.units, &other.units, |a, b| {
+            a.compare_primary(b, self.order)
+        })
+        .then_with(|| compare_units(&self.units, &other.units, Unit::compare_secondary))
+        .then_with(|| compare_units(&self.units, &other.units, Unit::compare_tertiary))
+        .then_with(|| self.text.cmp(&other.text))
+    }
+}
+
+impl PartialOrd for CollationKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// 按指定方式比较两个名称
+pub fn collate(a: &str, b: &str, order: CollationOrder) -> Ordering {
+    if order == CollationOrder::Unicode {
+        return a.cmp(b);
+    }
+    CollationKey::new(a, order).cmp(&CollationKey::new(b, order))
+}
+
+/// 按指定方式比较两个可选的名称，None 排在最前
+pub fn collate_option(a: Option<&str>, b: Option<&str>, order: CollationOrder) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => collate(a, b, order),
+        _ => a.is_some().cmp(&b.is_some()),
+    }
+}
+
+/// 按指定方式排序名称
+pub fn sort_names(names: &mut [String], order: CollationOrder) {
+    match order {
+        CollationOrder::Unicode => names.sort(),
+        CollationOrder::Pinyin | CollationOrder::Stroke => {
+            names.sort_by_cached_key(|name| CollationKey::new(name, order))
+        }
+    }
+}
+
+fn units_of(text: &str) -> Vec<Unit> {
+    let mut units: Vec<Unit> = Vec::new();
+    for (ch, syllable) in char_syllables(text) {
+        if ch.is_ascii_digit() {
+            if let Some(Unit::Number(digits)) = units.last_mut() {
+                digits.push(ch);
+            } else {
+                units.push(Unit::Number(ch.to_string()));
+            }
+        } else if ch.is_ascii_alphabetic() {
+            units.push(Unit::Letter(ch));
+        } else if syllable.is_some() || is_han(ch) {
+            units.push(Unit::Han(ch, syllable));
+        } else if !ch.is_whitespace() {
+            units.push(Unit::Symbol(ch));
+        }
+    }
+    units
+}
+
+fn compare_units(a: &[Unit], b: &[Unit], compare: impl Fn(&Unit, &Unit) -> Ordering) -> Ordering {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| compare(a, b))
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or_else(|| a.len().cmp(&b.len()))
+}
+
+/// 按数值比较两串数字（忽略前导零）
+fn compare_numbers(a: &str, b: &str) -> Ordering {
+    let a = a.trim_start_matches('0');
+    let b = b.trim_start_matches('0');
+    a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+}
+
+/// 按笔画比较两个汉字，笔画和部首都相同时按码位
+fn compare_strokes(a: char, b: char) -> Ordering {
+    if a == b {
+        return Ordering::Equal;
+    }
+    let (mut a_buf, mut b_buf) = ([0u8; 4], [0u8; 4]);
+    STROKE_COLLATOR
+        .0
+        .as_ref()
+        .map_or(Ordering::Equal, |collator| {
+            collator.compare(a.encode_utf8(&mut a_buf), b.encode_utf8(&mut b_buf))
+        })
+        .then_with(|| a.cmp(&b))
+}
+
+/// CJK 统一汉字（含扩展区）
+fn is_han(ch: char) -> bool {
+    matches!(ch as u32,
+        0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xF900..=0xFAFF | 0x20000..=0x3134F)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(names: &[&str], order: CollationOrder) -> Vec<String> {
+        let mut names: Vec<String> = names.iter().map(|name| name.to_string()).collect();
+        sort_names(&mut names, order);
+        names
+    }
+
+    #[test]
+    fn stroke_collator_is_available() {
+        assert_eq!(stroke_collation_support(), StrokeCollationSupport::Stroke);
+        let collator = STROKE_COLLATOR.0.as_ref().expect("stroke collation data");
+        // 一(1 画) < 八(2 画) < 白(5 画)
+        assert_eq!(collator.compare("一", "八"), Ordering::Less);
+        assert_eq!(collator.compare("八", "白"), Ordering::Less);
+    }
+
+    #[test]
+    fn pinyin_order_mixes_letters_and_han_by_spelling() {
+        let names = [
+            "白酒",
+            "半导体",
+            "阿里",
+            "ABC",
+            "abc",
+            "10G",
+            "5G",
+            "一",
+            "八",
+            "巴",
+            "杨",
+            "羊",
+            "洋",
+            "芯片",
+        ];
+        assert_eq!(
+            sorted(&names, CollationOrder::Pinyin),
+            vec![
+                "5G",
+                "10G",
+                "abc",
+                "ABC",
+                "阿里",
+                "八",
+                "巴",
+                "白酒",
+                "半导体",
+                "芯片",
+                "羊",
+                "杨",
+                "洋",
+                "一"
+            ]
+        );
+    }
+
+    #[test]
+    fn same_reading_breaks_ties_by_strokes() {
+        // 羊(6 画) < 杨(7 画) < 洋(9 画)
+        assert_eq!(
+            sorted(&["洋", "杨", "羊"], CollationOrder::Pinyin),
+            vec!["羊", "杨", "洋"]
+        );
+    }
+
+    #[test]
+    fn stroke_order_puts_digits_and_letters_first() {
+        assert_eq!(
+            sorted(&["白", "B", "八", "一", "2"], CollationOrder::Stroke),
+            vec!["2", "B", "一", "八", "白"]
+        );
+    }
+
+    #[test]
+    fn unicode_order_is_code_point_order() {
+        let names = ["白酒", "阿里", "ABC", "abc", "10G", "5G"];
+        let mut expected: Vec<String> = names.iter().map(|name| name.to_string()).collect();
+        expected.sort();
+        assert_eq!(sorted(&names, CollationOrder::Unicode), expected);
+    }
+
+    #[test]
+    fn missing_names_sort_first() {
+        assert_eq!(
+            collate_option(None, Some("八"), CollationOrder::Pinyin),
+            Ordering::Less
+        );
+        assert_eq!(
+            collate_option(Some("八"), Some("八"), CollationOrder::Pinyin),
+            Ordering::Equal
+        );
+    }
+}
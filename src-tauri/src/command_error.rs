@@ -0,0 +1,125 @@
+use crate::tag_processor::QueryParseError;
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use thiserror::Error;
+
+/// 单页最多返回的条目数
+pub const MAX_PER_PAGE: u32 = 10_000;
+
+/// Tauri 命令的返回值
+pub type CommandResult<T> = Result<T, CommandError>;
+
+/// Tauri 命令返回给前端的错误
+/// 序列化为 { code, message, details }，code 为稳定的错误代码，前端据此区分错误类型
+#[derive(Debug, Clone, Error)]
+pub enum CommandError {
+    /// 标签、股票、快照等不存在
+    #[error("{0} not found")]
+    NotFound(String),
+    /// 分页参数不合法
+    #[error("Invalid page: {0}")]
+    InvalidPage(String),
+    /// 持有锁的线程发生 panic，状态可能不完整
+    #[error("Failed to lock {0}: lock poisoned")]
+    LockPoisoned(&'static str),
+    /// 搜索查询无法解析
+    #[error("Invalid query: {0}")]
+    InvalidQuery(QueryParseError),
+    /// 分页游标生成后数据集已发生变化，需要从第一页重新加载
+    #[error("Stale cursor: dataset version changed from {cursor_version} to {current_version}")]
+    StaleCursor {
+        cursor_version: u64,
+        current_version: u64,
+    },
+    /// 其他参数或输入文件不合法
+    #[error("Invalid input: {0}")]
+    InvalidInput(String),
+    /// 文件既不在应用数据目录中，也不是通过文件对话框选择的
+    #[error("Access denied: {0}")]
+    PermissionDenied(String),
+    /// 本地存储未初始化或读写失败
+    #[error("Storage error: {0}")]
+    Storage(String),
+    /// 其他内部错误
+    #[error("{0}")]
+    Internal(String),
+}
+
+impl CommandError {
+    /// 稳定的错误代码
+    pub fn code(&self) -> &'static str {
+        match self {
+            CommandError::NotFound(_) => "not_found",
+            CommandError::InvalidPage(_) => "invalid_page",
+            CommandError::LockPoisoned(_) => "lock_poisoned",
+            CommandError::InvalidQuery(_) => "invalid_query",
+            CommandError::StaleCursor { .. } => "stale_cursor",
+            CommandError::InvalidInput(_) => "invalid_input",
+            CommandError::PermissionDenied(_) => "permission_denied",
+            CommandError::Storage(_) => "storage",
+            CommandError::Internal(_) => "internal",
+        }
+    }
+
+    /// 用于 map_err：锁被污染时返回 LockPoisoned
+    pub fn lock_poisoned<E>(resource: &'static str) -> impl FnOnce(E) -> CommandError {
+        move |_| CommandError::LockPoisoned(resource)
+    }
+}
+
+impl Serialize for CommandError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("CommandError", 3)?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("message", &self.to_string())?;
+        match self {
+            CommandError::InvalidQuery(error) => state.serialize_field("details", error)?,
+            CommandError::StaleCursor {
+                cursor_version,
+                current_version,
+            } => state.serialize_field(
+                "details",
+                &serde_json::json!({
+                    "cursor_version": cursor_version,
+                    "current_version": current_version,
+                }),
+            )?,
+            _ => state.serialize_field("details", &None::<()>)?,
+        }
+        state.end()
+    }
+}
+
+/// 底层模块的字符串错误统一视为内部错误
+impl From<String> for CommandError {
+    fn from(message: String) -> Self {
+        CommandError::Internal(message)
+    }
+}
+
+impl From<QueryParseError> for CommandError {
+    fn from(error: QueryParseError) -> Self {
+        CommandError::InvalidQuery(error)
+    }
+}
+
+/// 校验分页参数：页码从 1 开始，每页数量在 1 ~ MAX_PER_PAGE 之间
+/// 页码超出末页是合法的，返回空页
+pub fn validate_page(page: u32, per_page: u32) -> CommandResult<()> {
+    if page == 0 {
+        return Err(CommandError::InvalidPage(
+            "page numbers start from 1".to_string(),
+        ));
+    }
+    if per_page == 0 || per_page > MAX_PER_PAGE {
+        return Err(CommandError::InvalidPage(format!(
+            "per_page must be between 1 and {}",
+            MAX_PER_PAGE
+        )));
+    }
+    Ok(())
+}
+
+/// 页码对应的起始下标（页码已通过 validate_page 校验）
+pub fn page_start(page: u32, per_page: u32) -> usize {
+    (page.saturating_sub(1) as usize).saturating_mul(per_page as usize)
+}
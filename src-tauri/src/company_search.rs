@@ -0,0 +1,660 @@
+use crate::command_error::page_start;
+use crate::fuzzy_match::{merge_spans, HighlightSpan};
+use crate::stock_data::*;
+use crate::tag_index::{remove_positions, TagIndex};
+use once_cell::sync::OnceCell;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// BM25 参数
+const BM25_K1: f64 = 1.2;
+const BM25_B: f64 = 0.75;
+
+/// 摘要中命中位置前后保留的字符数
+const SNIPPET_CONTEXT: usize = 30;
+
+/// 参与全文检索的字段
+const TEXT_FIELDS: [&str; 2] = ["business_scope", "company_description"];
+
+fn field_text<'a>(stock: &'a StockCompanyInfo, field: &str) -> &'a str {
+    match field {
+        "business_scope" => &stock.business_scope,
+        _ => &stock.company_description,
+    }
+}
+
+/// 公司全文检索参数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompanySearchParams {
+    pub query: String,
+    /// 只返回带有该分类标签的股票
+    #[serde(default)]
+    pub category_name: Option<String>,
+    /// 只返回带有该标签的股票（可以是同义词别名；未指定分类时匹配任意分类下的同名标签）
+    #[serde(default)]
+    pub tag_name: Option<String>,
+    #[serde(default)]
+    pub tag_detail: Option<String>,
+    pub page: u32,
+    pub per_page: u32,
+}
+
+/// 摘要片段，highlights 为片段内的字符下标
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TextSnippet {
+    pub field: String,
+    pub text: String,
+    pub highlights: Vec<HighlightSpan>,
+}
+
+/// 单条检索结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompanySearchHit {
+    pub stock_code: String,
+    pub stock_name: String,
+    pub exchange: String,
+    pub score: f64,
+    pub snippets: Vec<TextSnippet>,
+}
+
+/// 检索结果（带分页）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompanySearchResult {
+    pub hits: Vec<CompanySearchHit>,
+    pub total_hits: u32,
+    pub total_pages: u32,
+    pub current_page: u32,
+}
+
+/// 将文本切分为检索词：连续汉字按二元组并保留每个单字（单字查询也能命中），英文和数字按整词
+pub fn tokenize_text(text: &str) -> Vec<String> {
+    split_terms(text, true)
+}
+
+/// 将查询切分为检索词：连续汉字只取二元组（单字时取单字），英文和数字按整词
+fn tokenize_query(query: &str) -> Vec<String> {
+    split_terms(query, false)
+}
+
+fn split_terms(text: &str, cjk_unigrams: bool) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut cjk_run: Vec<char> = Vec::new();
+    let mut word = String::new();
+
+    let flush_cjk = |run: &mut Vec<char>, tokens: &mut Vec<String>| {
+        if run.len() == 1 || cjk_unigrams {
+            tokens.extend(run.iter().map(char::to_string));
+        }
+        tokens.extend(run.windows(2).map(|pair| pair.iter().collect::<String>()));
+        run.clear();
+    };
+    let flush_word = |word: &mut String, tokens: &mut Vec<String>| {
+        if !word.is_empty() {
+            tokens.push(std::mem::take(word));
+        }
+    };
+
+    for ch in text.chars() {
+        if is_cjk(ch) {
+            flush_word(&mut word, &mut tokens);
+            cjk_run.push(ch);
+        } else if ch.is_alphanumeric() {
+            flush_cjk(&mut cjk_run, &mut tokens);
+            word.extend(ch.to_lowercase());
+        } else {
+            flush_cjk(&mut cjk_run, &mut tokens);
+            flush_word(&mut word, &mut tokens);
+        }
+    }
+    flush_cjk(&mut cjk_run, &mut tokens);
+    flush_word(&mut word, &mut tokens);
+    tokens
+}
+
+fn is_cjk(ch: char) -> bool {
+    matches!(ch, '\u{4e00}'..='\u{9fff}' | '\u{3400}'..='\u{4dbf}' | '\u{f900}'..='\u{faff}')
+}
+
+/// business_scope 和 company_description 的倒排索引（BM25 打分）
+#[derive(Debug, Default)]
+pub struct CompanyTextIndex {
+    /// 检索词 → (股票下标, 词频)，按股票下标升序
+    postings: HashMap<String, Vec<(usize, u32)>>,
+    /// 每只股票的检索词数量
+    doc_lengths: Vec<u32>,
+    /// 所有股票的检索词总数
+    total_length: u64,
+    /// 每只股票 TF-IDF 向量的模长（用于计算文本相似度），索引变化后在下次使用时重新计算
+    doc_norms: OnceCell<Vec<f64>>,
+    /// 英文和数字检索词（升序），用于前缀匹配
+    words: Vec<String>,
+}
+
+/// 股票参与检索的全部检索词
+fn document_tokens(stock: &StockCompanyInfo) -> Vec<String> {
+    TEXT_FIELDS
+        .iter()
+        .flat_map(|field| tokenize_text(field_text(stock, field)))
+        .collect()
+}
+
+/// 股票每个检索词的词频
+fn term_counts(stock: &StockCompanyInfo) -> HashMap<String, u32> {
+    let mut counts: HashMap<String, u32> = HashMap::new();
+    for token in document_tokens(stock) {
+        *counts.entry(token).or_default() += 1;
+    }
+    counts
+}
+
+/// 两条记录参与检索的字段是否相同
+fn same_text(a: &StockCompanyInfo, b: &StockCompanyInfo) -> bool {
+    TEXT_FIELDS
+        .iter()
+        .all(|field| field_text(a, field) == field_text(b, field))
+}
+
+impl CompanyTextIndex {
+    /// 根据股票数据构建索引
+    pub fn build(stock_data: &[StockCompanyInfo]) -> Self {
+        let term_counts: Vec<HashMap<String, u32>> =
+            stock_data.par_iter().map(term_counts).collect();
+
+        let mut index = Self {
+            doc_lengths: Vec::with_capacity(stock_data.len()),
+            ..Self::default()
+        };
+        for (stock_id, counts) in term_counts.into_iter().enumerate() {
+            let length: u32 = counts.values().sum();
+            index.doc_lengths.push(length);
+            index.total_length += length as u64;
+            for (token, count) in counts {
+                index
+                    .postings
+                    .entry(token)
+                    .or_default()
+                    .push((stock_id, count));
+            }
+        }
+
+        index.words = index
+            .postings
+            .keys()
+            .filter(|token| !token.starts_with(is_cjk))
+            .cloned()
+            .collect();
+        index.words.sort_unstable();
+        index
+    }
+
+    /// 追加一只股票（与 stock_data.push 同步调用）
+    pub fn push_stock(&mut self, stock: &StockCompanyInfo) {
+        let stock_id = self.doc_lengths.len();
+        self.doc_lengths.push(0);
+        self.add_document(stock_id, stock);
+    }
+
+    /// 更新一只股票的检索词（在 stock_data[stock_id] 替换之后调用），检索字段未变化时跳过
+    pub fn update_stock(
+        &mut self,
+        stock_id: usize,
+        old: &StockCompanyInfo,
+        new: &StockCompanyInfo,
+    ) {
+        if same_text(old, new) {
+            return;
+        }
+        self.remove_document(stock_id, old);
+        self.add_document(stock_id, new);
+    }
+
+    /// 移除若干只股票（在 stock_data 删除之前调用，stock_ids 为升序下标）
+    /// 其余股票保持原有顺序，下标整体前移
+    pub fn remove_stocks(&mut self, stock_data: &[StockCompanyInfo], stock_ids: &[usize]) {
+        if stock_ids.is_empty() {
+            return;
+        }
+        for &stock_id in stock_ids {
+            self.remove_document(stock_id, &stock_data[stock_id]);
+        }
+
+        // 下标映射是单调的，倒排列表调整后仍保持升序
+        let shift = |id: usize| id - stock_ids.partition_point(|&removed| removed < id);
+        for postings in self.postings.values_mut() {
+            for (stock_id, _) in postings.iter_mut() {
+                *stock_id = shift(*stock_id);
+            }
+        }
+        remove_positions(&mut self.doc_lengths, stock_ids);
+        self.doc_norms.take();
+    }
+
+    fn add_document(&mut self, stock_id: usize, stock: &StockCompanyInfo) {
+        let counts = term_counts(stock);
+        let length: u32 = counts.values().sum();
+        self.doc_lengths[stock_id] = length;
+        self.total_length += length as u64;
+        for (token, count) in counts {
+            let postings = self.postings.entry(token.clone()).or_default();
+            if postings.is_empty() && !token.starts_with(is_cjk) {
+                if let Err(position) = self.words.binary_search(&token) {
+                    self.words.insert(position, token);
+                }
+            }
+            if let Err(position) = postings.binary_search_by_key(&stock_id, |&(id, _)| id) {
+                postings.insert(position, (stock_id, count));
+            }
+        }
+        self.doc_norms.take();
+    }
+
+    fn remove_document(&mut self, stock_id: usize, stock: &StockCompanyInfo) {
+        self.total_length -= self.doc_lengths[stock_id] as u64;
+        self.doc_lengths[stock_id] = 0;
+        for token in term_counts(stock).into_keys() {
+            let Some(postings) = self.postings.get_mut(&token) else {
+                continue;
+            };
+            postings.retain(|&(id, _)| id != stock_id);
+            if postings.is_empty() {
+                self.postings.remove(&token);
+                if let Ok(position) = self.words.binary_search(&token) {
+                    self.words.remove(position);
+                }
+            }
+        }
+        self.doc_norms.take();
+    }
+
+    /// 平均检索词数量
+    fn avg_doc_length(&self) -> f64 {
+        self.total_length as f64 / self.doc_lengths.len().max(1) as f64
+    }
+
+    /// 每只股票 TF-IDF 向量的模长
+    fn doc_norms(&self) -> &[f64] {
+        self.doc_norms.get_or_init(|| {
+            let mut squared_norms = vec![0.0; self.doc_lengths.len()];
+            for postings in self.postings.values() {
+                let idf = self.tf_idf_weight(postings.len());
+                for &(stock_id, tf) in postings {
+                    squared_norms[stock_id] += (tf as f64 * idf).powi(2);
+                }
+            }
+            squared_norms.into_iter().map(f64::sqrt).collect()
+        })
+    }
+
+    /// 查询词对应的倒排列表：汉字精确匹配，英文和数字按前缀匹配（如 "ai" 命中 "aigc"）
+    /// 前缀命中多个检索词时合并为一个列表，词频相加
+    fn query_postings(&self, token: &str) -> Vec<(usize, u32)> {
+        if token.starts_with(is_cjk) {
+            return self.postings.get(token).cloned().unwrap_or_default();
+        }
+
+        let start = self.words.partition_point(|word| word.as_str() < token);
+        let mut merged: HashMap<usize, u32> = HashMap::new();
+        for word in self.words[start..]
+            .iter()
+            .take_while(|word| word.starts_with(token))
+        {
+            for &(stock_id, tf) in &self.postings[word] {
+                *merged.entry(stock_id).or_default() += tf;
+            }
+        }
+        merged.into_iter().collect()
+    }
+
+    /// TF-IDF 中检索词的 idf（平滑后恒为正）
+    fn tf_idf_weight(&self, document_frequency: usize) -> f64 {
+        (1.0 + self.doc_lengths.len() as f64 / document_frequency.max(1) as f64).ln()
+    }
+
+    /// 计算指定股票与其余股票 TF-IDF 向量的余弦相似度，只返回相似度大于 0 的股票
+    pub fn similar_documents(&self, stock: &StockCompanyInfo) -> HashMap<usize, f64> {
+        let mut query_norm = 0.0;
+        let mut dot_products: HashMap<usize, f64> = HashMap::new();
+        for (token, count) in term_counts(stock) {
+            let Some(postings) = self.postings.get(&token) else {
+                continue;
+            };
+            let idf = self.tf_idf_weight(postings.len());
+            let query_weight = count as f64 * idf;
+            query_norm += query_weight * query_weight;
+            for &(stock_id, tf) in postings {
+                *dot_products.entry(stock_id).or_default() += query_weight * tf as f64 * idf;
+            }
+        }
+
+        let query_norm = query_norm.sqrt();
+        let doc_norms = self.doc_norms();
+        dot_products
+            .into_iter()
+            .filter_map(|(stock_id, dot)| {
+                let norm = query_norm * doc_norms.get(stock_id)?;
+                (norm > 0.0).then(|| (stock_id, (dot / norm).min(1.0)))
+            })
+            .collect()
+    }
+
+    /// 检索包含全部查询词的股票，返回 (股票下标, BM25 得分)，未排序
+    pub fn search(&self, query: &str) -> Vec<(usize, f64)> {
+        let mut tokens = tokenize_query(query);
+        tokens.sort();
+        tokens.dedup();
+        if tokens.is_empty() {
+            return Vec::new();
+        }
+
+        let doc_count = self.doc_lengths.len() as f64;
+        let mut scores: HashMap<usize, (usize, f64)> = HashMap::new();
+        for token in &tokens {
+            let postings = self.query_postings(token);
+            if postings.is_empty() {
+                // 任一查询词不存在时没有结果
+                return Vec::new();
+            }
+            let df = postings.len() as f64;
+            let idf = ((doc_count - df + 0.5) / (df + 0.5) + 1.0).ln();
+            for (stock_id, tf) in postings {
+                let tf = tf as f64;
+                let length_norm = 1.0 - BM25_B
+                    + BM25_B * self.doc_lengths[stock_id] as f64 / self.avg_doc_length().max(1.0);
+                let entry = scores.entry(stock_id).or_default();
+                entry.0 += 1;
+                entry.1 += idf * tf * (BM25_K1 + 1.0) / (tf + BM25_K1 * length_norm);
+            }
+        }
+
+        scores
+            .into_iter()
+            .filter(|(_, (matched, _))| *matched == tokens.len())
+            .map(|(stock_id, (_, score))| (stock_id, score))
+            .collect()
+    }
+}
+
+/// 判断股票是否满足分类和标签过滤条件，过滤的标签名称先按同义词词典换成规范名称
+fn passes_tag_filter(index: &TagIndex, stock_id: usize, params: &CompanySearchParams) -> bool {
+    let Some(tags) = index.stock_tags.get(stock_id) else {
+        return false;
+    };
+    let matches_item = |category: &str, item: &TagItem| {
+        params.tag_name.as_deref().is_none_or(|name| {
+            item.name == index.synonyms.canonical(category, name).unwrap_or(name)
+        }) && params
+            .tag_detail
+            .as_ref()
+            .is_none_or(|detail| item.detail.as_ref() == Some(detail))
+    };
+
+    match &params.category_name {
+        Some(category) => tags
+            .get(category)
+            .is_some_and(|items| items.iter().any(|item| matches_item(category, item))),
+        None if params.tag_name.is_some() => tags
+            .iter()
+            .any(|(category, items)| items.iter().any(|item| matches_item(category, item))),
+        None => true,
+    }
+}
+
+/// 全文检索公司信息，结果按 BM25 得分降序，得分相同时按股票代码排序
+pub fn search_company_text(
+    stock_data: &[StockCompanyInfo],
+    tag_index: &TagIndex,
+    text_index: &CompanyTextIndex,
+    params: &CompanySearchParams,
+) -> CompanySearchResult {
+    let mut hits: Vec<(usize, f64)> = text_index
+        .search(&params.query)
+        .into_iter()
+        .filter(|&(stock_id, _)| stock_id < stock_data.len())
+        .filter(|&(stock_id, _)| passes_tag_filter(tag_index, stock_id, params))
+        .collect();
+    hits.sort_by(|a, b| {
+        b.1.total_cmp(&a.1)
+            .then_with(|| stock_data[a.0].stock_code.cmp(&stock_data[b.0].stock_code))
+    });
+
+    let total_hits = hits.len() as u32;
+    let per_page = params.per_page;
+    let total_pages = total_hits.div_ceil(per_page);
+    let start_index = page_start(params.page, per_page);
+
+    // 只为当前页生成摘要
+    let query_tokens = tokenize_query(&params.query);
+    let hits = hits
+        .into_iter()
+        .skip(start_index)
+        .take(per_page as usize)
+        .map(|(stock_id, score)| {
+            let stock = &stock_data[stock_id];
+            CompanySearchHit {
+                stock_code: stock.stock_code.clone(),
+                stock_name: stock.stock_name.clone(),
+                exchange: stock.exchange.clone(),
+                score,
+                snippets: TEXT_FIELDS
+                    .iter()
+                    .filter_map(|field| {
+                        build_snippet(field, field_text(stock, field), &query_tokens)
+                    })
+                    .collect(),
+            }
+        })
+        .collect();
+
+    CompanySearchResult {
+        hits,
+        total_hits,
+        total_pages,
+        current_page: params.page,
+    }
+}
+
+/// 在字段文本中截取第一个命中位置附近的片段，并标出片段内所有命中的检索词
+fn build_snippet(field: &str, text: &str, query_tokens: &[String]) -> Option<TextSnippet> {
+    let chars: Vec<char> = text.chars().collect();
+    let lower: Vec<char> = chars
+        .iter()
+        .map(|ch| ch.to_lowercase().next().unwrap_or(*ch))
+        .collect();
+
+    let mut spans: Vec<HighlightSpan> = Vec::new();
+    for token in query_tokens {
+        let token: Vec<char> = token.chars().collect();
+        if token.is_empty() || token.len() > lower.len() {
+            continue;
+        }
+        for (start, window) in lower.windows(token.len()).enumerate() {
+            if window == token.as_slice() {
+                spans.push(HighlightSpan {
+                    start,
+                    end: start + token.len(),
+                });
+            }
+        }
+    }
+    let spans = merge_spans(spans);
+    let first = spans.first()?;
+
+    let start = first.start.saturating_sub(SNIPPET_CONTEXT);
+    let end = (first.end + SNIPPET_CONTEXT).min(chars.len());
+    let mut snippet: String = chars[start..end].iter().collect();
+    let mut offset = start;
+    if start > 0 {
+        snippet.insert(0, '…');
+        offset = start - 1;
+    }
+    if end < chars.len() {
+        snippet.push('…');
+    }
+
+    Some(TextSnippet {
+        field: field.to_string(),
+        text: snippet,
+        highlights: spans
+            .iter()
+            .filter(|span| span.start >= start && span.end <= end)
+            .map(|span| HighlightSpan {
+                start: span.start - offset,
+                end: span.end - offset,
+            })
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tag_synonyms::TagSynonyms;
+
+    fn stock(code: &str, business_scope: &str) -> StockCompanyInfo {
+        StockCompanyInfo {
+            business_scope: business_scope.to_string(),
+            ..test_stock(code, "")
+        }
+    }
+
+    fn stocks() -> Vec<StockCompanyInfo> {
+        vec![
+            stock("600001", "锂电池材料的研发与销售"),
+            stock("600002", "AIGC 内容生成平台，AI 芯片设计"),
+            stock("600003", "铜箔、锂电铜箔生产，铜材加工"),
+            stock("600004", "软件开发"),
+        ]
+    }
+
+    fn search_codes(stock_data: &[StockCompanyInfo], query: &str) -> Vec<String> {
+        let index = CompanyTextIndex::build(stock_data);
+        let mut hits = index.search(query);
+        hits.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        hits.into_iter()
+            .map(|(stock_id, _)| stock_data[stock_id].stock_code.clone())
+            .collect()
+    }
+
+    #[test]
+    fn tokenizes_cjk_runs_and_words() {
+        assert_eq!(
+            tokenize_text("锂电AI-Chip"),
+            vec!["锂", "电", "锂电", "ai", "chip"]
+        );
+        assert_eq!(tokenize_query("锂电 芯"), vec!["锂电", "芯"]);
+    }
+
+    #[test]
+    fn single_character_queries_match() {
+        let stock_data = stocks();
+        assert_eq!(search_codes(&stock_data, "锂"), vec!["600001", "600003"]);
+        assert_eq!(search_codes(&stock_data, "芯"), vec!["600002"]);
+        // 词频更高的文档得分更高
+        assert_eq!(search_codes(&stock_data, "铜"), vec!["600003"]);
+    }
+
+    #[test]
+    fn words_match_by_prefix() {
+        let stock_data = stocks();
+        assert_eq!(search_codes(&stock_data, "ai"), vec!["600002"]);
+        assert_eq!(search_codes(&stock_data, "AIG"), vec!["600002"]);
+        assert!(search_codes(&stock_data, "aigcx").is_empty());
+    }
+
+    #[test]
+    fn bm25_requires_all_terms_and_prefers_frequent_terms() {
+        let stock_data = vec![
+            stock("600001", "储能电池，光伏电站"),
+            stock("600002", "储能电池，储能电站"),
+            stock("600003", "电池回收"),
+        ];
+        assert_eq!(
+            search_codes(&stock_data, "储能 电池"),
+            vec!["600002", "600001"]
+        );
+        assert!(search_codes(&stock_data, "储能 回收").is_empty());
+        assert!(search_codes(&stock_data, "  ").is_empty());
+    }
+
+    #[test]
+    fn incremental_updates_match_a_rebuild() {
+        let mut stock_data = stocks();
+        let mut index = CompanyTextIndex::build(&stock_data);
+
+        let updated = stock("600003", "AI 服务器铜缆连接");
+        index.update_stock(2, &stock_data[2], &updated);
+        stock_data[2] = updated;
+        let inserted = stock("600005", "锂矿开采，AIGC 营销");
+        index.push_stock(&inserted);
+        stock_data.push(inserted);
+        index.remove_stocks(&stock_data, &[0, 3]);
+        remove_positions(&mut stock_data, &[0, 3]);
+
+        let rebuilt = CompanyTextIndex::build(&stock_data);
+        assert_eq!(index.words, rebuilt.words);
+        assert_eq!(index.doc_lengths, rebuilt.doc_lengths);
+        for query in ["锂", "ai", "铜箔", "铜缆", "软件", "营销"] {
+            let mut hits = index.search(query);
+            let mut expected = rebuilt.search(query);
+            hits.sort_by_key(|&(stock_id, _)| stock_id);
+            expected.sort_by_key(|&(stock_id, _)| stock_id);
+            assert_eq!(hits, expected, "{}", query);
+        }
+        assert_eq!(
+            index.similar_documents(&stock_data[1]),
+            rebuilt.similar_documents(&stock_data[1])
+        );
+    }
+
+    #[test]
+    fn tag_filter_accepts_aliases() {
+        let mut stock_data = stocks();
+        stock_data[0].custom_tags = "概念:NEV".to_string();
+        stock_data[2].custom_tags = "概念:锂电".to_string();
+        let tag_index = TagIndex::build_with_synonyms(&stock_data, TagSynonyms::builtin());
+        let text_index = CompanyTextIndex::build(&stock_data);
+        let codes = |category: Option<&str>, tag: &str| {
+            let params = CompanySearchParams {
+                query: "锂".to_string(),
+                category_name: category.map(str::to_string),
+                tag_name: Some(tag.to_string()),
+                tag_detail: None,
+                page: 1,
+                per_page: 10,
+            };
+            search_company_text(&stock_data, &tag_index, &text_index, &params)
+                .hits
+                .into_iter()
+                .map(|hit| hit.stock_code)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(codes(Some("概念"), "NEV"), vec!["600001"]);
+        assert_eq!(codes(None, "新能源车"), vec!["600001"]);
+        assert_eq!(codes(None, "锂电池"), vec!["600003"]);
+        assert!(codes(Some("行业"), "NEV").is_empty());
+    }
+
+    #[test]
+    fn snippets_highlight_query_terms() {
+        let snippet = build_snippet(
+            "business_scope",
+            "AIGC 内容生成平台",
+            &tokenize_query("ai 平台"),
+        )
+        .unwrap();
+        let highlighted: Vec<String> = snippet
+            .highlights
+            .iter()
+            .map(|span| {
+                snippet
+                    .text
+                    .chars()
+                    .skip(span.start)
+                    .take(span.end - span.start)
+                    .collect()
+            })
+            .collect();
+        assert_eq!(highlighted, vec!["AI", "平台"]);
+    }
+}
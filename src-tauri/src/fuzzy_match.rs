@@ -0,0 +1,299 @@
+use serde::{Deserialize, Serialize};
+
+/// 高亮区间，[start, end) 为字符下标（按 Unicode 字符计数）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HighlightSpan {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// 单个字段的匹配结果，score 取值 0 ~ 1
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldMatch {
+    pub score: f64,
+    pub spans: Vec<HighlightSpan>,
+}
+
+/// 标签的搜索匹配结果：相关度和各字段的高亮区间
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TagSearchMatch {
+    pub score: f64,
+    pub name_spans: Vec<HighlightSpan>,
+    pub detail_spans: Vec<HighlightSpan>,
+    pub category_spans: Vec<HighlightSpan>,
+}
+
+/// 各类匹配的得分
+const SCORE_EQUAL: f64 = 1.0;
+const SCORE_PREFIX: f64 = 0.95;
+const SCORE_CONTAINS: f64 = 0.9;
+const SCORE_PINYIN: f64 = 0.8;
+const SCORE_SUBSEQUENCE: f64 = 0.75;
+const SCORE_TRANSPOSED: f64 = 0.6;
+const SCORE_EDIT: f64 = 0.7;
+
+/// 拼音命中时没有可高亮的原文区间
+pub fn pinyin_match() -> FieldMatch {
+    FieldMatch {
+        score: SCORE_PINYIN,
+        spans: Vec::new(),
+    }
+}
+
+fn lower_chars(text: &str) -> Vec<char> {
+    text.chars()
+        .map(|ch| ch.to_lowercase().next().unwrap_or(ch))
+        .collect()
+}
+
+fn single_span(start: usize, end: usize, score: f64) -> FieldMatch {
+    FieldMatch {
+        score,
+        spans: vec![HighlightSpan { start, end }],
+    }
+}
+
+/// 精确匹配：不区分大小写的包含，query 需为小写
+pub fn exact_match(query: &str, text: &str) -> Option<FieldMatch> {
+    let query = lower_chars(query);
+    let text = lower_chars(text);
+    find_substring(&query, &text)
+}
+
+fn find_substring(query: &[char], text: &[char]) -> Option<FieldMatch> {
+    if query.is_empty() || query.len() > text.len() {
+        return None;
+    }
+    let start = text
+        .windows(query.len())
+        .position(|window| window == query)?;
+    let score = if query.len() == text.len() {
+        SCORE_EQUAL
+    } else if start == 0 {
+        SCORE_PREFIX
+    } else {
+        SCORE_CONTAINS
+    };
+    Some(single_span(start, start + query.len(), score))
+}
+
+/// 容错匹配：依次尝试精确包含、漏字（子序列）、相邻字颠倒和编辑距离
+/// query 需为小写
+pub fn fuzzy_match(query: &str, text: &str) -> Option<FieldMatch> {
+    let query = lower_chars(query);
+    let text = lower_chars(text);
+    if query.is_empty() || text.is_empty() {
+        return None;
+    }
+
+    find_substring(&query, &text)
+        .or_else(|| subsequence_match(&query, &text))
+        .or_else(|| edit_distance_match(&query, &text))
+}
+
+/// 漏字：query 的字符按顺序出现在 text 中，且跨度不超过 query 长度的两倍
+fn subsequence_match(query: &[char], text: &[char]) -> Option<FieldMatch> {
+    if query.len() < 2 {
+        return None;
+    }
+
+    let mut best: Option<Vec<usize>> = None;
+    for start in (0..text.len()).filter(|&i| text[i] == query[0]) {
+        let mut positions = vec![start];
+        let mut next = start + 1;
+        for &ch in &query[1..] {
+            match text[next..].iter().position(|&c| c == ch) {
+                Some(offset) => {
+                    positions.push(next + offset);
+                    next += offset + 1;
+                }
+                None => break,
+            }
+        }
+        if positions.len() < query.len() {
+            // 之后的起点只会更靠后，同样无法匹配完整
+            break;
+        }
+        let width = |positions: &[usize]| positions[positions.len() - 1] - positions[0] + 1;
+        if best
+            .as_ref()
+            .is_none_or(|best| width(&positions) < width(best))
+        {
+            best = Some(positions);
+        }
+    }
+
+    let positions = best?;
+    let width = positions[positions.len() - 1] - positions[0] + 1;
+    if width > query.len() * 2 {
+        return None;
+    }
+
+    Some(FieldMatch {
+        score: SCORE_SUBSEQUENCE * query.len() as f64 / width as f64,
+        spans: merge_spans(
+            positions
+                .into_iter()
+                .map(|position| HighlightSpan {
+                    start: position,
+                    end: position + 1,
+                })
+                .collect(),
+        ),
+    })
+}
+
+/// 允许的编辑次数：两个字只允许颠倒，3~5 个字允许 1 处，更长允许 2 处
+fn max_edits(query_len: usize) -> usize {
+    match query_len {
+        0..=2 => 0,
+        3..=5 => 1,
+        _ => 2,
+    }
+}
+
+/// 在 text 中寻找与 query 编辑距离（含相邻字颠倒）最小的片段
+fn edit_distance_match(query: &[char], text: &[char]) -> Option<FieldMatch> {
+    if query.len() == 2 {
+        let reversed = [query[1], query[0]];
+        let start = text.windows(2).position(|window| window == reversed)?;
+        return Some(single_span(start, start + 2, SCORE_TRANSPOSED));
+    }
+
+    let max_edits = max_edits(query.len());
+    if max_edits == 0 {
+        return None;
+    }
+
+    // dist[i][j]：query[..i] 与 text 中以 j 结尾的某个片段的最小编辑距离
+    // start[i][j]：该片段的起点
+    let (m, n) = (query.len(), text.len());
+    let mut dist = vec![vec![0usize; n + 1]; m + 1];
+    let mut start = vec![vec![0usize; n + 1]; m + 1];
+    start[0] = (0..=n).collect();
+    for i in 1..=m {
+        dist[i][0] = i;
+        for j in 1..=n {
+            let cost = usize::from(query[i - 1] != text[j - 1]);
+            // 距离相同时优先起点更靠前（覆盖更多字符）的片段
+            let mut best = (dist[i - 1][j - 1] + cost, start[i - 1][j - 1]);
+            best = best.min((dist[i - 1][j] + 1, start[i - 1][j]));
+            best = best.min((dist[i][j - 1] + 1, start[i][j - 1]));
+            let transposed =
+                i > 1 && j > 1 && query[i - 1] == text[j - 2] && query[i - 2] == text[j - 1];
+            if transposed {
+                best = best.min((dist[i - 2][j - 2] + 1, start[i - 2][j - 2]));
+            }
+            dist[i][j] = best.0;
+            start[i][j] = best.1;
+        }
+    }
+
+    let (end, distance) = (1..=n)
+        .map(|j| (j, dist[m][j]))
+        .min_by_key(|&(end, distance)| (distance, start[m][end], std::cmp::Reverse(end)))?;
+    if distance > max_edits || start[m][end] >= end {
+        return None;
+    }
+
+    Some(single_span(
+        start[m][end],
+        end,
+        SCORE_EDIT * (1.0 - distance as f64 / m as f64),
+    ))
+}
+
+/// 正则匹配，得分与包含匹配相同
+pub fn regex_match(regex: &regex::Regex, text: &str) -> Option<FieldMatch> {
+    regex.is_match(text).then(|| FieldMatch {
+        score: SCORE_CONTAINS,
+        spans: regex_spans(regex, text),
+    })
+}
+
+/// 在 text 中查找正则匹配的区间（转换为字符下标）
+fn regex_spans(regex: &regex::Regex, text: &str) -> Vec<HighlightSpan> {
+    let char_index = |byte: usize| text[..byte].chars().count();
+    regex
+        .find_iter(text)
+        .filter(|found| !found.is_empty())
+        .map(|found| HighlightSpan {
+            start: char_index(found.start()),
+            end: char_index(found.end()),
+        })
+        .collect()
+}
+
+/// 排序并合并重叠或相邻的区间
+pub fn merge_spans(mut spans: Vec<HighlightSpan>) -> Vec<HighlightSpan> {
+    spans.sort_by_key(|span| (span.start, span.end));
+    let mut merged: Vec<HighlightSpan> = Vec::with_capacity(spans.len());
+    for span in spans {
+        match merged.last_mut() {
+            Some(last) if span.start <= last.end => last.end = last.end.max(span.end),
+            _ => merged.push(span),
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(start: usize, end: usize) -> HighlightSpan {
+        HighlightSpan { start, end }
+    }
+
+    #[test]
+    fn exact_matches_rank_equal_prefix_contains() {
+        let equal = exact_match("机器人", "机器人").unwrap();
+        let prefix = exact_match("机器", "机器人").unwrap();
+        let contains = exact_match("器人", "机器人").unwrap();
+        assert!(equal.score > prefix.score && prefix.score > contains.score);
+        assert_eq!(contains.spans, vec![span(1, 3)]);
+        assert_eq!(exact_match("ai", "AIGC").unwrap().spans, vec![span(0, 2)]);
+        assert!(exact_match("机人", "机器人").is_none());
+    }
+
+    #[test]
+    fn fuzzy_tolerates_missing_and_transposed_characters() {
+        let missing = fuzzy_match("机人", "工业机器人").unwrap();
+        assert_eq!(missing.spans, vec![span(2, 3), span(4, 5)]);
+        assert!(missing.score < SCORE_CONTAINS);
+
+        let transposed = fuzzy_match("器机", "机器人").unwrap();
+        assert_eq!(transposed.spans, vec![span(0, 2)]);
+        assert_eq!(transposed.score, SCORE_TRANSPOSED);
+
+        // 3~5 个字允许一处编辑
+        let edited = fuzzy_match("半导休", "功率半导体").unwrap();
+        assert_eq!(edited.spans, vec![span(2, 5)]);
+        assert!(edited.score < SCORE_SUBSEQUENCE);
+        assert!(fuzzy_match("半休休", "功率半导体器件").is_none());
+    }
+
+    #[test]
+    fn fuzzy_rejects_distant_matches() {
+        // 子序列跨度超过查询长度的两倍
+        assert!(fuzzy_match("机人", "机械设备与工业人").is_none());
+        assert!(fuzzy_match("", "机器人").is_none());
+        assert!(fuzzy_match("x", "").is_none());
+    }
+
+    #[test]
+    fn regex_spans_use_character_offsets() {
+        let regex = regex::RegexBuilder::new("芯.")
+            .case_insensitive(true)
+            .build()
+            .unwrap();
+        let found = regex_match(&regex, "AI芯片与芯粒").unwrap();
+        assert_eq!(found.spans, vec![span(2, 4), span(5, 7)]);
+    }
+
+    #[test]
+    fn merges_overlapping_and_adjacent_spans() {
+        let merged = merge_spans(vec![span(5, 6), span(0, 2), span(1, 3), span(3, 4)]);
+        assert_eq!(merged, vec![span(0, 4), span(5, 6)]);
+    }
+}
@@ -1,8 +1,30 @@
 // 模块声明
+mod collation;
+mod command_error;
+mod company_search;
+mod fuzzy_match;
+mod page_cursor;
+mod pinyin_index;
 mod stock_data;
+mod stock_diff;
+mod stock_merge;
+mod stock_similarity;
+mod stock_store;
+mod tag_blacklist;
+mod tag_cooccurrence;
+mod tag_diagnostics;
+mod tag_duplicates;
+mod tag_editor;
+mod tag_index;
+mod tag_journal;
 mod tag_processor;
+mod tag_synonyms;
+mod tag_taxonomy;
+mod tag_validation;
 mod tauri_commands;
+mod text_normalize;
 
+use tauri::Manager;
 use tauri_commands::*;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -11,18 +33,61 @@ pub fn run() {
     let mut builder = tauri::Builder::default()
         .plugin(tauri_plugin_persisted_scope::init())
         .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_dialog::init())
         .manage(AppState::new())
         .invoke_handler(tauri::generate_handler![
             set_stock_data,
+            upsert_stocks,
+            remove_stocks,
+            list_snapshots,
+            create_snapshot,
+            restore_snapshot,
+            delete_snapshot,
+            diff_stock_data,
+            choose_data_file,
+            check_search_query,
             get_categories,
             get_tags_by_category,
             get_stocks_by_tag,
             calculate_statistics,
             parse_tags,
+            parse_tags_ordered,
+            serialize_tags,
+            diagnose_tags,
+            get_tag_diagnostics,
+            get_duplicate_tags,
+            get_tag_normalization,
+            set_tag_normalization,
+            preview_tag_normalization,
+            apply_tag_normalization,
+            preview_tag_edit,
+            apply_tag_edit,
+            undo,
+            redo,
+            list_history,
             validate_tag,
+            validate_tag_detail,
+            get_tag_validation_rules,
+            set_tag_validation_rules,
             get_tag_details,
+            get_category_tree,
+            get_tag_associations,
+            get_tag_cooccurrence,
             search_and_filter,
-            get_data_statistics
+            search_companies,
+            find_similar_stocks,
+            get_data_statistics,
+            get_stroke_collation_support,
+            load_tag_blacklist,
+            reload_tag_blacklist,
+            test_tag_blacklist,
+            load_tag_taxonomy,
+            reload_tag_taxonomy,
+            get_tag_synonyms,
+            load_tag_synonyms,
+            reload_tag_synonyms,
+            add_tag_alias,
+            suggest_tag_aliases
         ]);
 
     #[cfg(debug_assertions)] // only enable instrumentation in development builds
@@ -39,8 +104,14 @@ pub fn run() {
             .plugin(tauri_plugin_process::init());
     }
 
-    #[cfg(target_os = "macos")]
-    let builder = builder.setup(|_app| {
+    let builder = builder.setup(|app| {
+        // 加载应用数据目录中持久化的数据，失败时继续以内存模式运行
+        let data_dir = app.path().app_data_dir()?;
+        if let Err(e) = app.state::<AppState>().init_storage(data_dir) {
+            eprintln!("Failed to initialize local storage: {}", e);
+        }
+
+        #[cfg(target_os = "macos")]
         #[allow(deprecated)]
         {
             use cocoa::appkit::{NSApp, NSApplication, NSApplicationActivationPolicy};
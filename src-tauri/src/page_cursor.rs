@@ -0,0 +1,174 @@
+use crate::command_error::*;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// 游标编码后的长度：版本(16) + 起始位置(16) + 查询指纹(16)，均为十六进制
+const CURSOR_LEN: usize = 48;
+
+/// 分页游标 - 记录生成时的数据集版本、下一页的起始位置和查询指纹
+/// 编码为不透明的字符串，前端原样回传即可
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageCursor {
+    pub version: u64,
+    pub offset: u64,
+    pub fingerprint: u64,
+}
+
+impl PageCursor {
+    pub fn encode(&self) -> String {
+        format!(
+            "{:016x}{:016x}{:016x}",
+            self.version, self.offset, self.fingerprint
+        )
+    }
+
+    pub fn decode(cursor: &str) -> CommandResult<Self> {
+        let invalid = || CommandError::InvalidInput(format!("Invalid cursor: {}", cursor));
+        if cursor.len() != CURSOR_LEN || !cursor.is_ascii() {
+            return Err(invalid());
+        }
+        let field = |i: usize| u64::from_str_radix(&cursor[i * 16..(i + 1) * 16], 16);
+        let (Ok(version), Ok(offset), Ok(fingerprint)) = (field(0), field(1), field(2)) else {
+            return Err(invalid());
+        };
+        Ok(Self {
+            version,
+            offset,
+            fingerprint,
+        })
+    }
+}
+
+/// 查询条件的指纹，游标只能用于生成它的同一组查询条件（分类、搜索词、排序等）
+pub fn query_fingerprint(query: &impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    query.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// 本页的起始位置：传入游标时校验数据集版本和查询指纹，否则按页码计算
+/// 数据集在两次请求之间发生变化时返回 StaleCursor，前端应从第一页重新加载
+pub fn page_offset(
+    cursor: Option<&str>,
+    version: u64,
+    fingerprint: u64,
+    page: u32,
+    per_page: u32,
+) -> CommandResult<usize> {
+    let Some(cursor) = cursor else {
+        validate_page(page, per_page)?;
+        return Ok(page_start(page, per_page));
+    };
+    validate_page(1, per_page)?;
+    let cursor = PageCursor::decode(cursor)?;
+    if cursor.fingerprint != fingerprint {
+        return Err(CommandError::InvalidInput(
+            "Cursor belongs to a different query".to_string(),
+        ));
+    }
+    if cursor.version != version {
+        return Err(CommandError::StaleCursor {
+            cursor_version: cursor.version,
+            current_version: version,
+        });
+    }
+    Ok(usize::try_from(cursor.offset).unwrap_or(usize::MAX))
+}
+
+/// 起始位置对应的页码（从 1 开始）
+pub fn page_number(offset: usize, per_page: u32) -> u32 {
+    u32::try_from(offset / per_page.max(1) as usize + 1).unwrap_or(u32::MAX)
+}
+
+/// 下一页的游标，已到末页时为 None
+pub fn next_cursor(
+    version: u64,
+    fingerprint: u64,
+    offset: usize,
+    per_page: u32,
+    total: usize,
+) -> Option<String> {
+    let next = offset.saturating_add(per_page as usize);
+    (next < total).then(|| {
+        PageCursor {
+            version,
+            offset: next as u64,
+            fingerprint,
+        }
+        .encode()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursor_round_trips() {
+        let cursor = PageCursor {
+            version: 7,
+            offset: 40,
+            fingerprint: u64::MAX,
+        };
+        let encoded = cursor.encode();
+        assert_eq!(encoded.len(), CURSOR_LEN);
+        assert_eq!(PageCursor::decode(&encoded).unwrap(), cursor);
+    }
+
+    #[test]
+    fn malformed_cursor_is_invalid_input() {
+        for cursor in ["", "abc", &"g".repeat(CURSOR_LEN), &"中".repeat(16)] {
+            assert!(
+                matches!(
+                    PageCursor::decode(cursor),
+                    Err(CommandError::InvalidInput(_))
+                ),
+                "{:?}",
+                cursor
+            );
+        }
+    }
+
+    #[test]
+    fn offset_from_page_or_cursor() {
+        assert_eq!(page_offset(None, 1, 9, 3, 20).unwrap(), 40);
+        assert!(matches!(
+            page_offset(None, 1, 9, 0, 20),
+            Err(CommandError::InvalidPage(_))
+        ));
+
+        let cursor = next_cursor(1, 9, 40, 20, 100).unwrap();
+        // 游标优先于页码
+        assert_eq!(page_offset(Some(&cursor), 1, 9, 1, 20).unwrap(), 60);
+        assert_eq!(page_number(60, 20), 4);
+    }
+
+    #[test]
+    fn last_page_has_no_next_cursor() {
+        assert!(next_cursor(1, 9, 80, 20, 100).is_none());
+        assert!(next_cursor(1, 9, 60, 20, 100).is_some());
+    }
+
+    #[test]
+    fn cursor_from_older_version_is_stale() {
+        let cursor = next_cursor(1, 9, 0, 20, 100).unwrap();
+        match page_offset(Some(&cursor), 2, 9, 1, 20) {
+            Err(CommandError::StaleCursor {
+                cursor_version,
+                current_version,
+            }) => assert_eq!((cursor_version, current_version), (1, 2)),
+            other => panic!("expected stale cursor, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn cursor_from_other_query_is_rejected() {
+        let fingerprint = query_fingerprint(&("概念", false));
+        let cursor = next_cursor(1, fingerprint, 0, 20, 100).unwrap();
+        let other = query_fingerprint(&("行业", false));
+        assert!(matches!(
+            page_offset(Some(&cursor), 1, other, 1, 20),
+            Err(CommandError::InvalidInput(_))
+        ));
+    }
+}
@@ -0,0 +1,217 @@
+use once_cell::sync::Lazy;
+use pinyin::ToPinyin;
+use std::collections::HashMap;
+
+/// 金融领域多音字词组的读音，优先于单字读音
+/// 与前端 pinyin-utils.ts 中 polytonicCharMap 的取向一致，并补充了常见词组
+const PHRASE_OVERRIDES: &[(&str, &[&str])] = &[
+    ("银行", &["yin", "hang"]),
+    ("行业", &["hang", "ye"]),
+    ("行情", &["hang", "qing"]),
+    ("同行", &["tong", "hang"]),
+    ("行长", &["hang", "zhang"]),
+    ("发行", &["fa", "xing"]),
+    ("行权", &["xing", "quan"]),
+    ("执行", &["zhi", "xing"]),
+    ("运行", &["yun", "xing"]),
+    ("出行", &["chu", "xing"]),
+    ("旅行", &["lv", "xing"]),
+    ("重庆", &["chong", "qing"]),
+    ("重组", &["chong", "zu"]),
+    ("重大", &["zhong", "da"]),
+    ("重工", &["zhong", "gong"]),
+    ("重卡", &["zhong", "ka"]),
+    ("重型", &["zhong", "xing"]),
+    ("重要", &["zhong", "yao"]),
+    ("重仓", &["zhong", "cang"]),
+    ("重点", &["zhong", "dian"]),
+    ("增长", &["zeng", "zhang"]),
+    ("成长", &["cheng", "zhang"]),
+    ("董事长", &["dong", "shi", "zhang"]),
+    ("厦门", &["xia", "men"]),
+    ("西藏", &["xi", "zang"]),
+    ("会计", &["kuai", "ji"]),
+    ("音乐", &["yin", "yue"]),
+    ("调研", &["diao", "yan"]),
+    ("单抗", &["dan", "kang"]),
+    ("朝阳", &["chao", "yang"]),
+];
+
+/// 单字的默认读音（不在词组表中时使用）
+const CHAR_OVERRIDES: &[(char, &str)] = &[
+    ('行', "hang"),
+    ('中', "zhong"),
+    ('发', "fa"),
+    ('长', "chang"),
+    ('重', "chong"),
+    ('藏', "zang"),
+    ('厦', "sha"),
+    ('乐', "le"),
+    ('调', "tiao"),
+    ('券', "quan"),
+];
+
+static PHRASES: Lazy<HashMap<Vec<char>, &'static [&'static str]>> = Lazy::new(|| {
+    PHRASE_OVERRIDES
+        .iter()
+        .map(|(phrase, syllables)| (phrase.chars().collect(), *syllables))
+        .collect()
+});
+
+static CHARS: Lazy<HashMap<char, &'static str>> =
+    Lazy::new(|| CHAR_OVERRIDES.iter().copied().collect());
+
+static MAX_PHRASE_LEN: Lazy<usize> = Lazy::new(|| {
+    PHRASE_OVERRIDES
+        .iter()
+        .map(|(phrase, _)| phrase.chars().count())
+        .max()
+        .unwrap_or(1)
+});
+
+/// 逐字的读音：优先匹配最长的多音字词组，其次使用单字的默认读音
+/// 没有拼音的字符（英文字母、数字、符号等）读音为 None
+pub fn char_syllables(text: &str) -> Vec<(char, Option<&'static str>)> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut syllables = Vec::with_capacity(chars.len());
+    let mut i = 0;
+
+    while i < chars.len() {
+        let phrase = (2..=(*MAX_PHRASE_LEN).min(chars.len() - i))
+            .rev()
+            .find_map(|len| {
+                PHRASES
+                    .get(&chars[i..i + len])
+                    .map(|phrase_syllables| (len, phrase_syllables))
+            });
+        if let Some((len, phrase_syllables)) = phrase {
+            for (offset, syllable) in phrase_syllables.iter().enumerate() {
+                syllables.push((chars[i + offset], Some(*syllable)));
+            }
+            i += len;
+            continue;
+        }
+
+        let ch = chars[i];
+        let syllable = CHARS
+            .get(&ch)
+            .copied()
+            .or_else(|| ch.to_pinyin().map(|pinyin| pinyin.plain()));
+        syllables.push((ch, syllable));
+        i += 1;
+    }
+
+    syllables
+}
+
+/// 文本的拼音形式（均为小写）
+/// 英文字母和数字原样保留（每个字符单独成节），其余符号忽略
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PinyinForms {
+    /// 简拼，例如 中国平安 → zgpa
+    pub initials: String,
+    /// 全拼的音节，例如 中国平安 → [zhong, guo, ping, an]
+    pub syllables: Vec<String>,
+}
+
+impl PinyinForms {
+    /// 计算文本的简拼和全拼
+    pub fn of(text: &str) -> Self {
+        let mut forms = Self::default();
+        for (ch, syllable) in char_syllables(text) {
+            if let Some(syllable) = syllable {
+                forms.push_syllable(syllable);
+            } else if ch.is_ascii_alphanumeric() {
+                forms.push_syllable(&ch.to_ascii_lowercase().to_string());
+            }
+        }
+        forms
+    }
+
+    fn push_syllable(&mut self, syllable: &str) {
+        if let Some(first) = syllable.chars().next() {
+            self.initials.push(first);
+        }
+        self.syllables.push(syllable.to_string());
+    }
+
+    /// 判断拼音查询（小写字母和数字）是否命中简拼或全拼
+    /// 全拼只在音节边界处匹配：查询须是若干连续音节拼接后的前缀，
+    /// 避免 ai 命中 bai、hai 这类跨音节的子串
+    pub fn matches(&self, query: &str) -> bool {
+        self.initials.contains(query)
+            || (0..self.syllables.len()).any(|start| self.matches_from(start, query))
+    }
+
+    fn matches_from(&self, start: usize, query: &str) -> bool {
+        let mut rest = query;
+        for syllable in &self.syllables[start..] {
+            if rest.len() <= syllable.len() {
+                return syllable.starts_with(rest);
+            }
+            match rest.strip_prefix(syllable.as_str()) {
+                Some(remaining) => rest = remaining,
+                None => return false,
+            }
+        }
+        false
+    }
+}
+
+/// 查询词是否可以按拼音匹配：只包含 ASCII 字母和数字，且至少有一个字母
+pub fn is_pinyin_query(query: &str) -> bool {
+    query.chars().all(|ch| ch.is_ascii_alphanumeric())
+        && query.chars().any(|ch| ch.is_ascii_alphabetic())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_initials_and_full_pinyin() {
+        let forms = PinyinForms::of("中国平安");
+        assert_eq!(forms.initials, "zgpa");
+        assert_eq!(forms.syllables, ["zhong", "guo", "ping", "an"]);
+        assert!(forms.matches("zgpa"));
+        assert!(forms.matches("gp"));
+        assert!(forms.matches("zhongguopingan"));
+        assert!(forms.matches("pingan"));
+        assert!(forms.matches("guopin"));
+
+        let forms = PinyinForms::of("新能源");
+        assert!(forms.matches("xny"));
+        assert!(forms.matches("nengyuan"));
+    }
+
+    #[test]
+    fn full_pinyin_matches_only_at_syllable_boundaries() {
+        assert!(!PinyinForms::of("白酒").matches("ai"));
+        assert!(!PinyinForms::of("白酒").matches("aijiu"));
+        assert!(!PinyinForms::of("中国平安").matches("ongguo"));
+        assert!(PinyinForms::of("白酒").matches("baij"));
+    }
+
+    #[test]
+    fn ascii_characters_are_kept_as_syllables() {
+        let forms = PinyinForms::of("AI芯片");
+        assert_eq!(forms.initials, "aixp");
+        assert!(forms.matches("ai"));
+        assert!(forms.matches("aixin"));
+    }
+
+    #[test]
+    fn phrase_overrides_take_precedence() {
+        assert!(PinyinForms::of("招商银行").matches("zsyh"));
+        assert!(PinyinForms::of("重庆").matches("chongqing"));
+        assert!(!PinyinForms::of("招商银行").matches("yinxing"));
+    }
+
+    #[test]
+    fn pinyin_queries_need_a_letter() {
+        assert!(is_pinyin_query("zgpa"));
+        assert!(is_pinyin_query("a1"));
+        assert!(!is_pinyin_query("600519"));
+        assert!(!is_pinyin_query("中国"));
+    }
+}
@@ -1,7 +1,11 @@
+use crate::collation::CollationOrder;
+use crate::fuzzy_match::TagSearchMatch;
+use crate::tag_validation::TagValidationResult;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 /// 股票公司基本信息 - 与前端类型保持一致
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct StockCompanyInfo {
     /// 股票代码
     pub stock_code: String,
@@ -29,24 +33,275 @@ pub struct StockCompanyInfo {
     pub sectors_concepts: Vec<String>,
 }
 
+/// StockCompanyInfo 的字段，用于按需返回部分字段
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StockField {
+    StockCode,
+    StockName,
+    CompanyName,
+    Exchange,
+    BusinessScope,
+    CustomTags,
+    OfficialWebsite,
+    CompanyDescription,
+    UnderwritingMethod,
+    CreatedAt,
+    UpdatedAt,
+    SectorsConcepts,
+}
+
+/// 按字段投影后的股票信息 - 未请求的字段为 None，序列化时省略
+/// 返回全部字段时与 StockCompanyInfo 的序列化结果一致
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProjectedStock {
+    /// 股票代码（始终返回）
+    pub stock_code: String,
+    /// 股票名称
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stock_name: Option<String>,
+    /// 公司名称
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub company_name: Option<String>,
+    /// 交易所
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exchange: Option<String>,
+    /// 业务范围
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub business_scope: Option<String>,
+    /// 自定义标签
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custom_tags: Option<String>,
+    /// 官方网站
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub official_website: Option<String>,
+    /// 公司描述
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub company_description: Option<String>,
+    /// 承销方式
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub underwriting_method: Option<String>,
+    /// 创建时间
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+    /// 更新时间
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<String>,
+    /// 板块概念
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sectors_concepts: Option<Vec<String>>,
+}
+
+impl StockCompanyInfo {
+    /// 只返回指定字段（股票代码始终返回）
+    pub fn project(&self, fields: &[StockField]) -> ProjectedStock {
+        let mut projected = ProjectedStock {
+            stock_code: self.stock_code.clone(),
+            ..ProjectedStock::default()
+        };
+        for field in fields {
+            match field {
+                StockField::StockCode => {}
+                StockField::StockName => projected.stock_name = Some(self.stock_name.clone()),
+                StockField::CompanyName => projected.company_name = Some(self.company_name.clone()),
+                StockField::Exchange => projected.exchange = Some(self.exchange.clone()),
+                StockField::BusinessScope => {
+                    projected.business_scope = Some(self.business_scope.clone())
+                }
+                StockField::CustomTags => projected.custom_tags = Some(self.custom_tags.clone()),
+                StockField::OfficialWebsite => {
+                    projected.official_website = Some(self.official_website.clone())
+                }
+                StockField::CompanyDescription => {
+                    projected.company_description = Some(self.company_description.clone())
+                }
+                StockField::UnderwritingMethod => {
+                    projected.underwriting_method = Some(self.underwriting_method.clone())
+                }
+                StockField::CreatedAt => projected.created_at = Some(self.created_at.clone()),
+                StockField::UpdatedAt => projected.updated_at = Some(self.updated_at.clone()),
+                StockField::SectorsConcepts => {
+                    projected.sectors_concepts = Some(self.sectors_concepts.clone())
+                }
+            }
+        }
+        projected
+    }
+}
+
+/// 测试用的股票记录，只填写股票代码和自定义标签
+#[cfg(test)]
+pub fn test_stock(code: &str, tags: &str) -> StockCompanyInfo {
+    StockCompanyInfo {
+        stock_code: code.to_string(),
+        custom_tags: tags.to_string(),
+        ..Default::default()
+    }
+}
+
+impl From<&StockCompanyInfo> for ProjectedStock {
+    fn from(stock: &StockCompanyInfo) -> Self {
+        Self {
+            stock_code: stock.stock_code.clone(),
+            stock_name: Some(stock.stock_name.clone()),
+            company_name: Some(stock.company_name.clone()),
+            exchange: Some(stock.exchange.clone()),
+            business_scope: Some(stock.business_scope.clone()),
+            custom_tags: Some(stock.custom_tags.clone()),
+            official_website: Some(stock.official_website.clone()),
+            company_description: Some(stock.company_description.clone()),
+            underwriting_method: Some(stock.underwriting_method.clone()),
+            created_at: Some(stock.created_at.clone()),
+            updated_at: Some(stock.updated_at.clone()),
+            sectors_concepts: Some(stock.sectors_concepts.clone()),
+        }
+    }
+}
+
 /// 股票信息数组
 #[allow(dead_code)]
 pub type StockInfoArray = Vec<StockCompanyInfo>;
 
 /// 解析后的标签项
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct TagItem {
     pub name: String,
     pub detail: Option<String>,
 }
 
+/// 保持原有顺序的标签解析结果
+/// 分类按首次出现的顺序排列，同名分类合并，分类内标签按出现顺序排列且不重复
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParsedTags {
+    pub categories: Vec<ParsedTagCategory>,
+}
+
+/// 解析结果中的单个分类
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParsedTagCategory {
+    pub name: String,
+    pub items: Vec<TagItem>,
+}
+
+impl ParsedTags {
+    /// 获取分类下的标签（可修改）
+    pub fn get_mut(&mut self, category_name: &str) -> Option<&mut Vec<TagItem>> {
+        self.categories
+            .iter_mut()
+            .find(|category| category.name == category_name)
+            .map(|category| &mut category.items)
+    }
+
+    /// 追加标签：名称和补充说明去掉首尾空白，空补充说明视为没有补充说明
+    /// 分类或名称为空、或同一分类下已有相同标签时忽略，返回是否追加
+    pub fn push(&mut self, category_name: &str, item: TagItem) -> bool {
+        let category_name = category_name.trim();
+        let item = TagItem {
+            name: item.name.trim().to_string(),
+            detail: item
+                .detail
+                .as_deref()
+                .map(str::trim)
+                .filter(|detail| !detail.is_empty())
+                .map(str::to_string),
+        };
+        if category_name.is_empty() || item.name.is_empty() {
+            return false;
+        }
+
+        match self.get_mut(category_name) {
+            Some(items) if items.contains(&item) => false,
+            Some(items) => {
+                items.push(item);
+                true
+            }
+            None => {
+                self.categories.push(ParsedTagCategory {
+                    name: category_name.to_string(),
+                    items: vec![item],
+                });
+                true
+            }
+        }
+    }
+
+    /// 移除没有标签的分类
+    pub fn remove_empty_categories(&mut self) {
+        self.categories
+            .retain(|category| !category.items.is_empty());
+    }
+
+    /// 规范形式：同名分类合并、重复标签和空标签去除，顺序保持不变
+    pub fn canonical(&self) -> ParsedTags {
+        let mut canonical = ParsedTags::default();
+        for category in &self.categories {
+            for item in &category.items {
+                canonical.push(&category.name, item.clone());
+            }
+        }
+        canonical
+    }
+
+    /// 转换为 分类 → 标签 的映射
+    pub fn into_map(self) -> HashMap<String, Vec<TagItem>> {
+        self.categories
+            .into_iter()
+            .map(|category| (category.name, category.items))
+            .collect()
+    }
+}
+
 /// 标签分类下的标签详情
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TagDetails {
+    /// 稳定的标签 ID，可用于 get_stocks_by_tag 等按引用查询的命令
+    #[serde(default)]
+    pub tag_id: String,
     pub name: String,
     pub detail: Option<String>,
     pub count: u32,
-    pub stocks: Vec<StockCompanyInfo>,
+    /// 标签下的股票（传入 stock_fields 时只包含这些字段，摘要模式下为空）
+    pub stocks: Vec<ProjectedStock>,
+    /// 命中的黑名单分组（未命中为 None）
+    #[serde(default)]
+    pub blacklist_group: Option<String>,
+    /// 标签验证结果（状态、原因代码和提示信息）
+    #[serde(default)]
+    pub validation: Option<TagValidationResult>,
+    /// 搜索时的相关度和高亮区间（无搜索查询时为 None）
+    #[serde(default)]
+    pub search_match: Option<TagSearchMatch>,
+    /// 按同义词合并到该标签的原始名称（未发生合并时为空）
+    #[serde(default)]
+    pub variants: Vec<TagVariant>,
+    /// 摘要模式下的示例股票（此时 stocks 为空）
+    #[serde(default)]
+    pub sample_stocks: Vec<StockSample>,
+}
+
+/// 示例股票：只包含代码和名称
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StockSample {
+    pub stock_code: String,
+    pub stock_name: String,
+}
+
+/// 标签列表中股票数据的返回方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TagProjection {
+    /// 附带标签下的全部股票
+    #[default]
+    Full,
+    /// 只返回股票数量、标签 ID 和少量示例股票
+    Summary,
+}
+
+/// 合并前的原始标签名称及其股票数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagVariant {
+    pub name: String,
+    pub count: u32,
 }
 
 /// 标签分类
@@ -54,15 +309,98 @@ pub struct TagDetails {
 pub struct TagCategory {
     pub name: String,
     pub tags: Vec<TagDetails>,
+    /// 按标签层级组织的标签树（未加载层级时所有标签都是根节点）
+    #[serde(default)]
+    pub tree: Vec<TagTreeNode>,
+}
+
+/// 标签树节点（按标签名称聚合，忽略补充说明）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagTreeNode {
+    pub name: String,
+    /// 子树中的股票数（同一只股票只计一次）
+    pub count: u32,
+    /// 直接带有该标签的股票数
+    pub direct_count: u32,
+    pub children: Vec<TagTreeNode>,
 }
 
-/// 选中的标签信息
+/// 选中的标签信息 - 只包含标签引用和股票数量，股票通过 get_stocks_by_tag 分页获取
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SelectedTag {
     pub category_name: String,
     pub tag_name: String,
     pub tag_detail: Option<String>,
-    pub stocks: Vec<StockCompanyInfo>,
+    /// 稳定的标签 ID，可直接传给 get_stocks_by_tag
+    #[serde(default)]
+    pub tag_id: String,
+    /// 标签下的股票数量
+    #[serde(default)]
+    pub count: u32,
+}
+
+/// 股票列表的排序字段（相同时按股票代码排序）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StockSortKey {
+    #[default]
+    Code,
+    Name,
+    Exchange,
+    UpdatedAt,
+}
+
+/// 按标签查询股票列表的参数 - 通过 分类/标签/补充说明 或标签 ID 引用标签，无需回传股票数据
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagStocksQuery {
+    #[serde(default)]
+    pub category_name: Option<String>,
+    #[serde(default)]
+    pub tag_name: Option<String>,
+    #[serde(default)]
+    pub tag_detail: Option<String>,
+    /// 标签 ID（优先于 分类/标签/补充说明）
+    #[serde(default)]
+    pub tag_id: Option<String>,
+    /// 是否包含标签层级中子标签的股票（同一只股票只返回一次），默认包含；
+    /// 为 false 时只返回直接带有该标签的股票（与标签列表中的 count 一致）
+    #[serde(default = "default_include_children")]
+    pub include_children: bool,
+    pub page: u32,
+    pub per_page: u32,
+    #[serde(default)]
+    pub sort_by: StockSortKey,
+    #[serde(default)]
+    pub descending: bool,
+    /// 只返回股票的这些字段，为空时返回全部字段
+    #[serde(default)]
+    pub stock_fields: Option<Vec<StockField>>,
+    /// 上一页返回的 next_cursor，传入时忽略 page
+    #[serde(default)]
+    pub cursor: Option<String>,
+    /// 按名称排序时的排序方式，默认按拼音
+    #[serde(default)]
+    pub collation: CollationOrder,
+}
+
+fn default_include_children() -> bool {
+    true
+}
+
+/// 标签列表的排序方式
+/// 容错搜索时先按相关度排序，相同时再按排序方式排序
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TagSortKey {
+    /// 验证状态（错误优先），然后按使用次数降序、名称
+    #[default]
+    Validation,
+    /// 使用次数降序
+    Count,
+    /// 名称升序（按 collation 指定的排序方式）
+    Name,
+    /// 最近更新的股票时间降序
+    UpdatedAt,
 }
 
 /// 标签统计信息
@@ -77,6 +415,30 @@ pub struct TagStatistics {
     pub valid_tags_count: u32,
 }
 
+/// 标签黑名单处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BlacklistMode {
+    /// 不检查黑名单
+    Off,
+    /// 保留标签，但标记命中的黑名单分组
+    #[default]
+    Flag,
+    /// 从结果中隐藏命中的标签
+    Hide,
+}
+
+/// 文本搜索词的匹配方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchMode {
+    /// 精确包含匹配，结果按验证状态排序
+    #[default]
+    Exact,
+    /// 容错匹配：允许错字、相邻字颠倒和漏字，纯字母数字的搜索词同时匹配拼音，结果按相关度排序
+    Fuzzy,
+}
+
 /// 搜索和分页参数
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SearchParams {
@@ -86,6 +448,33 @@ pub struct SearchParams {
     pub stocks_page: u32,
     pub tags_per_page: u32,
     pub stocks_per_page: u32,
+    /// 黑名单标签的处理方式，默认仅标记
+    #[serde(default)]
+    pub blacklist_mode: BlacklistMode,
+    /// 搜索词的匹配方式，默认精确匹配
+    #[serde(default)]
+    pub match_mode: MatchMode,
+    /// 标签列表的返回方式，默认附带完整股票数据
+    #[serde(default)]
+    pub projection: TagProjection,
+    /// 摘要模式下每个标签附带的示例股票数量，默认 3
+    #[serde(default)]
+    pub sample_size: Option<u32>,
+    /// 只返回股票的这些字段，为空时返回全部字段
+    #[serde(default)]
+    pub stock_fields: Option<Vec<StockField>>,
+    /// 标签列表的排序方式，默认按验证状态
+    #[serde(default)]
+    pub tags_sort_by: TagSortKey,
+    /// 反转排序方向
+    #[serde(default)]
+    pub tags_reverse: bool,
+    /// 上一页返回的 next_cursor，传入时忽略 tags_page
+    #[serde(default)]
+    pub cursor: Option<String>,
+    /// 分类和标签名称的排序方式，默认按拼音
+    #[serde(default)]
+    pub collation: CollationOrder,
 }
 
 /// 分类列表结果
@@ -106,13 +495,29 @@ pub struct TagListResult {
     pub error_tags_count: u32,
     pub warning_tags_count: u32,
     pub valid_tags_count: u32,
+    /// 命中黑名单的标签数量（隐藏模式下为被隐藏的数量）
+    #[serde(default)]
+    pub blacklisted_tags_count: u32,
+    /// 下一页的游标，已到末页时为 None
+    #[serde(default)]
+    pub next_cursor: Option<String>,
+    /// 生成结果时的数据集版本
+    #[serde(default)]
+    pub dataset_version: u64,
 }
 
 /// 股票列表结果（带分页）
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StockListResult {
-    pub stocks: Vec<StockCompanyInfo>,
+    /// 当前页的股票（传入 stock_fields 时只包含这些字段）
+    pub stocks: Vec<ProjectedStock>,
     pub total_stocks: u32,
     pub total_pages: u32,
     pub current_page: u32,
+    /// 下一页的游标，已到末页时为 None
+    #[serde(default)]
+    pub next_cursor: Option<String>,
+    /// 生成结果时的数据集版本
+    #[serde(default)]
+    pub dataset_version: u64,
 }
@@ -0,0 +1,410 @@
+use crate::command_error::page_start;
+use crate::stock_data::*;
+use crate::tag_processor::parse_custom_tags;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::time::SystemTime;
+
+/// 参与比较的数据集
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DiffSource {
+    /// 当前内存中的数据集
+    Current,
+    /// 本地保存的快照
+    Snapshot { id: u64 },
+    /// 外部数据文件（本地存储格式或 JSON 数组）
+    File { path: String },
+}
+
+/// 单只股票的变化类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StockDiffKind {
+    /// 新上市（仅存在于目标数据集）
+    Added,
+    /// 退市（仅存在于基准数据集）
+    Removed,
+    Modified,
+}
+
+/// 文本字段的变化
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub before: String,
+    pub after: String,
+}
+
+/// 某个分类下新增和移除的标签
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CategoryTagDiff {
+    pub category_name: String,
+    pub added: Vec<TagItem>,
+    pub removed: Vec<TagItem>,
+}
+
+/// 单只股票的差异
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StockDiff {
+    pub stock_code: String,
+    pub stock_name: String,
+    pub kind: StockDiffKind,
+    /// 文本字段的变化（仅 Modified）
+    pub field_changes: Vec<FieldChange>,
+    /// sectors_concepts 中新增和移除的概念
+    pub concepts_added: Vec<String>,
+    pub concepts_removed: Vec<String>,
+    /// custom_tags 按分类的标签变化
+    pub tag_changes: Vec<CategoryTagDiff>,
+}
+
+/// 数据集比较报告（带分页）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatasetDiffReport {
+    pub changes: Vec<StockDiff>,
+    pub total_changes: u32,
+    pub total_pages: u32,
+    pub current_page: u32,
+    pub added_count: u32,
+    pub removed_count: u32,
+    pub modified_count: u32,
+    pub unchanged_count: u32,
+}
+
+/// 比较两个数据集（按 stock_code 对应），结果按股票代码排序
+/// created_at / updated_at 不参与比较
+pub fn diff_datasets(base: &[StockCompanyInfo], target: &[StockCompanyInfo]) -> Vec<StockDiff> {
+    let base_by_code: HashMap<&str, &StockCompanyInfo> = base
+        .iter()
+        .map(|stock| (stock.stock_code.as_str(), stock))
+        .collect();
+    let target_codes: HashSet<&str> = target
+        .iter()
+        .map(|stock| stock.stock_code.as_str())
+        .collect();
+
+    let mut diffs: Vec<StockDiff> = target
+        .par_iter()
+        .filter_map(|stock| match base_by_code.get(stock.stock_code.as_str()) {
+            Some(old) => diff_stock(old, stock),
+            None => Some(whole_stock_diff(stock, StockDiffKind::Added)),
+        })
+        .collect();
+
+    diffs.extend(
+        base.iter()
+            .filter(|stock| !target_codes.contains(stock.stock_code.as_str()))
+            .map(|stock| whole_stock_diff(stock, StockDiffKind::Removed)),
+    );
+
+    diffs.sort_by(|a, b| a.stock_code.cmp(&b.stock_code));
+    diffs
+}
+
+/// 新增或移除的股票：所有概念和标签都计入新增或移除
+fn whole_stock_diff(stock: &StockCompanyInfo, kind: StockDiffKind) -> StockDiff {
+    let empty = StockCompanyInfo {
+        sectors_concepts: Vec::new(),
+        custom_tags: String::new(),
+        ..stock.clone()
+    };
+    let (old, new) = match kind {
+        StockDiffKind::Added => (&empty, stock),
+        _ => (stock, &empty),
+    };
+    let (concepts_added, concepts_removed) = diff_concepts(old, new);
+
+    StockDiff {
+        stock_code: stock.stock_code.clone(),
+        stock_name: stock.stock_name.clone(),
+        kind,
+        field_changes: Vec::new(),
+        concepts_added,
+        concepts_removed,
+        tag_changes: diff_tags(&old.custom_tags, &new.custom_tags),
+    }
+}
+
+/// 比较同一只股票的两条记录，没有变化时返回 None
+fn diff_stock(old: &StockCompanyInfo, new: &StockCompanyInfo) -> Option<StockDiff> {
+    let fields: [(&str, &String, &String); 8] = [
+        ("stock_name", &old.stock_name, &new.stock_name),
+        ("company_name", &old.company_name, &new.company_name),
+        ("exchange", &old.exchange, &new.exchange),
+        ("business_scope", &old.business_scope, &new.business_scope),
+        ("custom_tags", &old.custom_tags, &new.custom_tags),
+        (
+            "official_website",
+            &old.official_website,
+            &new.official_website,
+        ),
+        (
+            "company_description",
+            &old.company_description,
+            &new.company_description,
+        ),
+        (
+            "underwriting_method",
+            &old.underwriting_method,
+            &new.underwriting_method,
+        ),
+    ];
+
+    let field_changes: Vec<FieldChange> = fields
+        .into_iter()
+        .filter(|(_, before, after)| before != after)
+        .map(|(field, before, after)| FieldChange {
+            field: field.to_string(),
+            before: before.clone(),
+            after: after.clone(),
+        })
+        .collect();
+    let (concepts_added, concepts_removed) = diff_concepts(old, new);
+
+    if field_changes.is_empty() && concepts_added.is_empty() && concepts_removed.is_empty() {
+        return None;
+    }
+
+    Some(StockDiff {
+        stock_code: new.stock_code.clone(),
+        stock_name: new.stock_name.clone(),
+        kind: StockDiffKind::Modified,
+        field_changes,
+        concepts_added,
+        concepts_removed,
+        tag_changes: diff_tags(&old.custom_tags, &new.custom_tags),
+    })
+}
+
+fn diff_concepts(old: &StockCompanyInfo, new: &StockCompanyInfo) -> (Vec<String>, Vec<String>) {
+    let before: BTreeSet<&String> = old.sectors_concepts.iter().collect();
+    let after: BTreeSet<&String> = new.sectors_concepts.iter().collect();
+    (
+        after.difference(&before).map(|c| c.to_string()).collect(),
+        before.difference(&after).map(|c| c.to_string()).collect(),
+    )
+}
+
+/// 按分类比较标签集合（忽略顺序和重复），分类按名称排序
+pub fn diff_tags(before: &str, after: &str) -> Vec<CategoryTagDiff> {
+    if before == after {
+        return Vec::new();
+    }
+
+    let old_tags = parse_custom_tags(before);
+    let new_tags = parse_custom_tags(after);
+    let categories: BTreeSet<&String> = old_tags.keys().chain(new_tags.keys()).collect();
+
+    categories
+        .into_iter()
+        .filter_map(|category| {
+            let old_set = category_tag_set(&old_tags, category);
+            let new_set = category_tag_set(&new_tags, category);
+            let added = sorted_difference(&new_set, &old_set);
+            let removed = sorted_difference(&old_set, &new_set);
+            (!added.is_empty() || !removed.is_empty()).then(|| CategoryTagDiff {
+                category_name: category.clone(),
+                added,
+                removed,
+            })
+        })
+        .collect()
+}
+
+fn category_tag_set<'a>(
+    tags: &'a HashMap<String, Vec<TagItem>>,
+    category: &str,
+) -> HashSet<&'a TagItem> {
+    tags.get(category)
+        .map(|items| items.iter().collect())
+        .unwrap_or_default()
+}
+
+fn sorted_difference(a: &HashSet<&TagItem>, b: &HashSet<&TagItem>) -> Vec<TagItem> {
+    let mut items: Vec<TagItem> = a.difference(b).map(|item| (*item).clone()).collect();
+    items.sort_by(|x, y| (&x.name, &x.detail).cmp(&(&y.name, &y.detail)));
+    items
+}
+
+/// 两个数据集的完整比较结果，翻页时复用
+#[derive(Debug, Clone)]
+pub struct DatasetDiff {
+    diffs: Vec<StockDiff>,
+    /// 目标数据集的股票数量
+    target_len: u32,
+}
+
+impl DatasetDiff {
+    pub fn compute(base: &[StockCompanyInfo], target: &[StockCompanyInfo]) -> Self {
+        Self {
+            diffs: diff_datasets(base, target),
+            target_len: target.len() as u32,
+        }
+    }
+
+    /// 按变化类型筛选后分页生成报告（kinds 为 None 时包含所有类型）
+    pub fn report(
+        &self,
+        kinds: Option<&[StockDiffKind]>,
+        page: u32,
+        per_page: u32,
+    ) -> DatasetDiffReport {
+        let count =
+            |kind: StockDiffKind| self.diffs.iter().filter(|diff| diff.kind == kind).count() as u32;
+        let added_count = count(StockDiffKind::Added);
+        let removed_count = count(StockDiffKind::Removed);
+        let modified_count = count(StockDiffKind::Modified);
+        let unchanged_count = self.target_len.saturating_sub(added_count + modified_count);
+
+        let diffs: Vec<&StockDiff> = self
+            .diffs
+            .iter()
+            .filter(|diff| kinds.is_none_or(|kinds| kinds.contains(&diff.kind)))
+            .collect();
+
+        let total_changes = diffs.len() as u32;
+        let total_pages = total_changes.div_ceil(per_page);
+        let start_index = page_start(page, per_page);
+
+        DatasetDiffReport {
+            changes: diffs
+                .into_iter()
+                .skip(start_index)
+                .take(per_page as usize)
+                .cloned()
+                .collect(),
+            total_changes,
+            total_pages,
+            current_page: page,
+            added_count,
+            removed_count,
+            modified_count,
+            unchanged_count,
+        }
+    }
+}
+
+/// 比较结果缓存的键：两个数据来源、外部文件的修改时间以及当前数据集版本
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffCacheKey {
+    pub base: DiffSource,
+    pub target: DiffSource,
+    pub file_modified: Vec<Option<SystemTime>>,
+    pub version: u64,
+}
+
+impl DiffCacheKey {
+    pub fn new(base: &DiffSource, target: &DiffSource, version: u64) -> Self {
+        let file_modified = [base, target]
+            .into_iter()
+            .filter_map(|source| match source {
+                DiffSource::File { path } => Some(
+                    std::fs::metadata(path)
+                        .and_then(|metadata| metadata.modified())
+                        .ok(),
+                ),
+                _ => None,
+            })
+            .collect();
+        Self {
+            base: base.clone(),
+            target: target.clone(),
+            file_modified,
+            version,
+        }
+    }
+}
+
+/// 最近一次的比较结果
+#[derive(Debug, Clone)]
+pub struct DiffCache {
+    pub key: DiffCacheKey,
+    pub diff: DatasetDiff,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn datasets() -> (Vec<StockCompanyInfo>, Vec<StockCompanyInfo>) {
+        let mut base = vec![
+            test_stock("600001", "行业:银行;概念:AI"),
+            test_stock("600002", "行业:银行"),
+            test_stock("600003", ""),
+        ];
+        base[0].sectors_concepts = vec!["金融".to_string()];
+        let mut target = vec![
+            test_stock("600001", "概念:AI;概念:芯片{存储};行业:保险"),
+            test_stock("600002", "行业:银行"),
+            test_stock("600004", "概念:机器人"),
+        ];
+        target[0].sectors_concepts = vec!["保险".to_string()];
+        target[1].updated_at = "2024-05-01".to_string();
+        (base, target)
+    }
+
+    #[test]
+    fn diff_reports_added_removed_and_modified() {
+        let (base, target) = datasets();
+        let diffs = diff_datasets(&base, &target);
+        let kinds: Vec<(&str, StockDiffKind)> = diffs
+            .iter()
+            .map(|diff| (diff.stock_code.as_str(), diff.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ("600001", StockDiffKind::Modified),
+                ("600003", StockDiffKind::Removed),
+                ("600004", StockDiffKind::Added),
+            ]
+        );
+
+        let modified = &diffs[0];
+        assert_eq!(modified.concepts_added, vec!["保险"]);
+        assert_eq!(modified.concepts_removed, vec!["金融"]);
+        let categories: Vec<&str> = modified
+            .tag_changes
+            .iter()
+            .map(|change| change.category_name.as_str())
+            .collect();
+        assert_eq!(categories.len(), 2, "{:?}", modified.tag_changes);
+        assert!(categories.contains(&"概念") && categories.contains(&"行业"));
+    }
+
+    #[test]
+    fn report_filters_and_pages() {
+        let (base, target) = datasets();
+        let diff = DatasetDiff::compute(&base, &target);
+
+        let report = diff.report(None, 1, 2);
+        assert_eq!(
+            (
+                report.added_count,
+                report.removed_count,
+                report.modified_count,
+                report.unchanged_count
+            ),
+            (1, 1, 1, 1)
+        );
+        assert_eq!((report.total_changes, report.total_pages), (3, 2));
+        assert_eq!(report.changes.len(), 2);
+        assert_eq!(diff.report(None, 2, 2).changes.len(), 1);
+
+        let added = diff.report(Some(&[StockDiffKind::Added]), 1, 10);
+        assert_eq!(added.total_changes, 1);
+        assert_eq!(added.changes[0].stock_code, "600004");
+        assert_eq!(added.added_count, 1);
+    }
+
+    #[test]
+    fn cache_key_tracks_sources_and_version() {
+        let snapshot = DiffSource::Snapshot { id: 1 };
+        let key = DiffCacheKey::new(&snapshot, &DiffSource::Current, 7);
+        assert_eq!(key, DiffCacheKey::new(&snapshot, &DiffSource::Current, 7));
+        assert_ne!(key, DiffCacheKey::new(&snapshot, &DiffSource::Current, 8));
+        assert_ne!(key, DiffCacheKey::new(&DiffSource::Current, &snapshot, 7));
+    }
+}
@@ -0,0 +1,434 @@
+use crate::command_error::*;
+use crate::company_search::CompanyTextIndex;
+use crate::stock_data::*;
+use crate::tag_editor::StockTagChange;
+use crate::tag_index::{remove_positions, TagIndex};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// upsert_stocks / remove_stocks 的执行结果（均为股票代码）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StockMergeSummary {
+    pub inserted: Vec<String>,
+    pub updated: Vec<String>,
+    /// 已有记录更新或相同，未做修改
+    pub unchanged: Vec<String>,
+    pub removed: Vec<String>,
+    /// 待删除但数据集中不存在的股票代码
+    pub not_found: Vec<String>,
+}
+
+/// 增量合并的计划：先据此写入撤销日志，再通过 apply 修改数据
+#[derive(Debug, Default)]
+pub struct StockUpsert {
+    pub summary: StockMergeSummary,
+    /// 已有股票 custom_tags 的变化（用于撤销日志）
+    pub changes: Vec<StockTagChange>,
+    /// 新增股票的完整记录（用于撤销日志）
+    pub inserted: Vec<StockCompanyInfo>,
+    /// 需要替换的已有股票（位置和新记录）
+    updated: Vec<(usize, StockCompanyInfo)>,
+}
+
+impl StockUpsert {
+    /// 是否不会修改任何数据
+    pub fn is_empty(&self) -> bool {
+        self.inserted.is_empty() && self.updated.is_empty()
+    }
+
+    /// 按计划修改股票数据，标签索引和已构建的全文索引同步增量更新
+    pub fn apply(
+        self,
+        stock_data: &mut Vec<StockCompanyInfo>,
+        index: &mut TagIndex,
+        mut company_index: Option<&mut CompanyTextIndex>,
+    ) -> StockMergeSummary {
+        for (stock_id, stock) in self.updated {
+            index.update_stock(stock_id, &stock_data[stock_id], &stock);
+            if let Some(company_index) = company_index.as_deref_mut() {
+                company_index.update_stock(stock_id, &stock_data[stock_id], &stock);
+            }
+            stock_data[stock_id] = stock;
+        }
+        for stock in self.inserted {
+            index.push_stock(&stock);
+            if let Some(company_index) = company_index.as_deref_mut() {
+                company_index.push_stock(&stock);
+            }
+            stock_data.push(stock);
+        }
+        self.summary
+    }
+}
+
+/// 按 stock_code 计算合并计划（不修改数据），updated_at 较新的一方生效（时间相同时保留现有记录）
+/// updated_at 按 ISO-8601 时间比较，传入记录的时间无法解析时返回错误；
+/// 现有记录的时间无法解析时视为更早。同一批次中重复的股票代码按同样的规则取较新的记录
+pub fn plan_stock_upsert(
+    stock_data: &[StockCompanyInfo],
+    index: &TagIndex,
+    incoming: Vec<StockCompanyInfo>,
+) -> CommandResult<StockUpsert> {
+    if incoming
+        .iter()
+        .any(|stock| stock.stock_code.trim().is_empty())
+    {
+        return Err(CommandError::InvalidInput(
+            "Stock code must not be empty".to_string(),
+        ));
+    }
+    let invalid_codes: Vec<&str> = incoming
+        .iter()
+        .filter(|stock| parse_timestamp(&stock.updated_at).is_none())
+        .map(|stock| stock.stock_code.as_str())
+        .collect();
+    if !invalid_codes.is_empty() {
+        return Err(CommandError::InvalidInput(format!(
+            "updated_at must be an ISO-8601 time: {}",
+            invalid_codes.join(", ")
+        )));
+    }
+
+    let mut upsert = StockUpsert::default();
+    // 本批次中已计划新增或替换的股票在计划中的位置
+    let mut inserted_positions: HashMap<String, usize> = HashMap::new();
+    let mut updated_positions: HashMap<usize, usize> = HashMap::new();
+
+    for stock in incoming {
+        if let Some(&position) = inserted_positions.get(&stock.stock_code) {
+            if is_newer(&stock, &upsert.inserted[position]) {
+                upsert.inserted[position] = stock;
+            } else {
+                upsert.summary.unchanged.push(stock.stock_code);
+            }
+            continue;
+        }
+
+        let Some(stock_id) = index.position(&stock.stock_code) else {
+            inserted_positions.insert(stock.stock_code.clone(), upsert.inserted.len());
+            upsert.summary.inserted.push(stock.stock_code.clone());
+            upsert.inserted.push(stock);
+            continue;
+        };
+
+        let position = updated_positions.get(&stock_id).copied();
+        let existing = match position {
+            Some(position) => &upsert.updated[position].1,
+            None => &stock_data[stock_id],
+        };
+        if !is_newer(&stock, existing) {
+            upsert.summary.unchanged.push(stock.stock_code);
+            continue;
+        }
+        match position {
+            Some(position) => upsert.updated[position].1 = stock,
+            None => {
+                updated_positions.insert(stock_id, upsert.updated.len());
+                upsert.summary.updated.push(stock.stock_code.clone());
+                upsert.updated.push((stock_id, stock));
+            }
+        }
+    }
+
+    upsert.changes = upsert
+        .updated
+        .iter()
+        .filter(|(stock_id, stock)| stock_data[*stock_id].custom_tags != stock.custom_tags)
+        .map(|(stock_id, stock)| StockTagChange {
+            stock_code: stock.stock_code.clone(),
+            stock_name: stock.stock_name.clone(),
+            before: stock_data[*stock_id].custom_tags.clone(),
+            after: stock.custom_tags.clone(),
+        })
+        .collect();
+    Ok(upsert)
+}
+
+/// 传入记录的 updated_at 晚于现有记录且内容不同
+fn is_newer(stock: &StockCompanyInfo, existing: &StockCompanyInfo) -> bool {
+    parse_timestamp(&stock.updated_at) > parse_timestamp(&existing.updated_at) && stock != existing
+}
+
+/// 删除股票的计划：先据此写入撤销日志，再通过 apply 修改数据
+#[derive(Debug, Default)]
+pub struct StockRemoval {
+    pub summary: StockMergeSummary,
+    /// 被删除股票的完整记录（用于撤销日志）
+    pub removed: Vec<StockCompanyInfo>,
+    /// 被删除股票的位置（升序）
+    stock_ids: Vec<usize>,
+}
+
+impl StockRemoval {
+    /// 是否不会修改任何数据
+    pub fn is_empty(&self) -> bool {
+        self.stock_ids.is_empty()
+    }
+
+    /// 按计划删除股票，其余股票保持原有顺序，标签索引和已构建的全文索引同步增量更新
+    pub fn apply(
+        self,
+        stock_data: &mut Vec<StockCompanyInfo>,
+        index: &mut TagIndex,
+        company_index: Option<&mut CompanyTextIndex>,
+    ) -> StockMergeSummary {
+        index.remove_stocks(stock_data, &self.stock_ids);
+        if let Some(company_index) = company_index {
+            company_index.remove_stocks(stock_data, &self.stock_ids);
+        }
+        remove_positions(stock_data, &self.stock_ids);
+        self.summary
+    }
+}
+
+/// 按 stock_code 计算删除计划（不修改数据）
+pub fn plan_stock_removal(
+    stock_data: &[StockCompanyInfo],
+    index: &TagIndex,
+    stock_codes: &[String],
+) -> StockRemoval {
+    let mut removal = StockRemoval::default();
+    let mut seen: HashSet<&str> = HashSet::new();
+
+    for code in stock_codes {
+        if !seen.insert(code) {
+            continue;
+        }
+        match index.position(code) {
+            Some(stock_id) => {
+                removal.stock_ids.push(stock_id);
+                removal.summary.removed.push(code.clone());
+            }
+            None => removal.summary.not_found.push(code.clone()),
+        }
+    }
+
+    removal.stock_ids.sort_unstable();
+    removal.removed = removal
+        .stock_ids
+        .iter()
+        .map(|&stock_id| stock_data[stock_id].clone())
+        .collect();
+    removal
+}
+
+/// 解析 ISO-8601 时间，返回 UTC 毫秒时间戳；未带时区时按 UTC 处理
+/// 支持 "2024-05-01"、"2024-05-01T08:30"、"2024-05-01 08:30:00.123+08:00" 等形式
+pub fn parse_timestamp(text: &str) -> Option<i64> {
+    let text = text.trim();
+    let (date, rest) = (text.get(..10)?, text.get(10..)?);
+    let mut fields = date.split('-');
+    let year = parse_digits(fields.next()?, 4)?;
+    let month = parse_digits(fields.next()?, 2)?;
+    let day = parse_digits(fields.next()?, 2)?;
+    if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+        return None;
+    }
+    let date_millis = days_from_civil(year, month, day) * 86_400_000;
+    if rest.is_empty() {
+        return Some(date_millis);
+    }
+
+    let (time, offset_minutes) = split_time_zone(rest.strip_prefix(['T', 't', ' '])?)?;
+    let (time, fraction) = match time.split_once('.') {
+        Some((time, fraction)) => (time, Some(fraction)),
+        None => (time, None),
+    };
+    let mut fields = time.split(':');
+    let hour = parse_digits(fields.next()?, 2)?;
+    let minute = parse_digits(fields.next()?, 2)?;
+    let second = fields
+        .next()
+        .map_or(Some(0), |second| parse_digits(second, 2))?;
+    if fields.next().is_some() || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+    let millis = match fraction {
+        None => 0,
+        Some(fraction) => {
+            if fraction.is_empty() || fraction.len() > 9 {
+                return None;
+            }
+            let digits = parse_digits(fraction, fraction.len())?;
+            digits * 1000 / 10_i64.pow(fraction.len() as u32)
+        }
+    };
+
+    Some(date_millis + ((hour * 60 + minute - offset_minutes) * 60 + second) * 1000 + millis)
+}
+
+/// 拆分时区后缀（Z、±HH:MM、±HHMM 或 ±HH），返回时间部分和相对 UTC 的分钟偏移
+fn split_time_zone(time: &str) -> Option<(&str, i64)> {
+    if let Some(time) = time.strip_suffix(['Z', 'z']) {
+        return Some((time, 0));
+    }
+    let Some(position) = time.rfind(['+', '-']) else {
+        return Some((time, 0));
+    };
+
+    let (time, zone) = time.split_at(position);
+    let sign = if zone.starts_with('-') { -1 } else { 1 };
+    let zone = &zone[1..];
+    let (hours, minutes) = match zone.split_once(':') {
+        Some(parts) => parts,
+        None if zone.len() == 4 => zone.split_at(2),
+        None => (zone, "00"),
+    };
+    let hours = parse_digits(hours, 2)?;
+    let minutes = parse_digits(minutes, 2)?;
+    if hours > 23 || minutes > 59 {
+        return None;
+    }
+    Some((time, sign * (hours * 60 + minutes)))
+}
+
+/// 解析固定位数的十进制数字
+fn parse_digits(text: &str, len: usize) -> Option<i64> {
+    if text.len() != len || !text.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    text.parse().ok()
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// 公历日期距 1970-01-01 的天数
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stock(code: &str, tags: &str, updated_at: &str) -> StockCompanyInfo {
+        StockCompanyInfo {
+            updated_at: updated_at.to_string(),
+            ..test_stock(code, tags)
+        }
+    }
+
+    #[test]
+    fn parses_iso_8601_times() {
+        assert_eq!(parse_timestamp("1970-01-01"), Some(0));
+        assert_eq!(parse_timestamp("1970-01-02T00:00:01.5Z"), Some(86_401_500));
+        assert_eq!(
+            parse_timestamp("2024-05-01T08:00:00+08:00"),
+            parse_timestamp("2024-05-01 00:00Z")
+        );
+        assert_eq!(
+            parse_timestamp("2024-05-01T00:00:00-0130"),
+            parse_timestamp("2024-05-01T01:30:00")
+        );
+        assert_eq!(
+            parse_timestamp("2024-02-29"),
+            parse_timestamp("2024-03-01").map(|millis| millis - 86_400_000)
+        );
+
+        for invalid in [
+            "",
+            "2024",
+            "2024-13-01",
+            "2023-02-29",
+            "2024-05-01T25:00",
+            "昨天",
+        ] {
+            assert_eq!(parse_timestamp(invalid), None, "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn upsert_compares_parsed_times() {
+        let mut stock_data = vec![stock("600001", "概念:AI", "2024-05-01T02:00:00Z")];
+        let mut index = TagIndex::build_with_synonyms(&stock_data, Default::default());
+
+        // 按字符串比较更新，按时间比较更早
+        let older = stock("600001", "概念:芯片", "2024-05-01T09:00:00+08:00");
+        let newer = stock("600002", "概念:芯片", "2024-05-01");
+        let upsert = plan_stock_upsert(&stock_data, &index, vec![older, newer]).unwrap();
+        assert_eq!(upsert.summary.unchanged, vec!["600001"]);
+        assert_eq!(upsert.summary.inserted, vec!["600002"]);
+        assert_eq!(upsert.inserted[0].stock_code, "600002");
+        assert!(upsert.changes.is_empty());
+        upsert.apply(&mut stock_data, &mut index, None);
+        assert_eq!(stock_data.len(), 2);
+
+        let invalid = stock("600001", "概念:芯片", "2024/05/02");
+        assert!(matches!(
+            plan_stock_upsert(&stock_data, &index, vec![invalid]),
+            Err(CommandError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn upsert_plan_does_not_modify_data() {
+        let stock_data = vec![stock("600001", "概念:AI", "2024-01-01")];
+        let index = TagIndex::build_with_synonyms(&stock_data, Default::default());
+
+        let unchanged = plan_stock_upsert(
+            &stock_data,
+            &index,
+            vec![stock("600001", "概念:AI", "2024-01-01")],
+        )
+        .unwrap();
+        assert!(unchanged.is_empty());
+
+        // 同一批次中的重复代码取较新的记录
+        let upsert = plan_stock_upsert(
+            &stock_data,
+            &index,
+            vec![
+                stock("600001", "概念:芯片", "2024-03-01"),
+                stock("600001", "概念:机器人", "2024-02-01"),
+                stock("600002", "行业:银行", "2024-01-01"),
+                stock("600002", "行业:保险", "2024-02-01"),
+            ],
+        )
+        .unwrap();
+        assert_eq!(stock_data[0].custom_tags, "概念:AI");
+        assert_eq!(upsert.summary.updated, vec!["600001"]);
+        assert_eq!(upsert.summary.inserted, vec!["600002"]);
+        assert_eq!(upsert.summary.unchanged, vec!["600001"]);
+        assert_eq!(upsert.changes.len(), 1);
+        assert_eq!(upsert.changes[0].after, "概念:芯片");
+        assert_eq!(upsert.inserted[0].custom_tags, "行业:保险");
+    }
+
+    #[test]
+    fn removal_returns_removed_records() {
+        let mut stock_data = vec![
+            stock("600001", "概念:AI", "2024-01-01"),
+            stock("600002", "", "2024-01-01"),
+            stock("600003", "行业:银行", "2024-01-01"),
+        ];
+        let mut index = TagIndex::build_with_synonyms(&stock_data, Default::default());
+        let codes = ["600003", "600001", "600009"].map(str::to_string);
+
+        let removal = plan_stock_removal(&stock_data, &index, &codes);
+        let removed_codes: Vec<&str> = removal
+            .removed
+            .iter()
+            .map(|s| s.stock_code.as_str())
+            .collect();
+        assert_eq!(removed_codes, vec!["600001", "600003"]);
+        assert_eq!(stock_data.len(), 3);
+
+        let summary = removal.apply(&mut stock_data, &mut index, None);
+        assert_eq!(summary.not_found, vec!["600009"]);
+        assert_eq!(stock_data.len(), 1);
+        assert_eq!(index.position("600002"), Some(0));
+        assert!(plan_stock_removal(&stock_data, &index, &codes).is_empty());
+    }
+}
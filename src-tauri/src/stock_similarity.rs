@@ -0,0 +1,342 @@
+use crate::command_error::*;
+use crate::company_search::CompanyTextIndex;
+use crate::stock_data::*;
+use crate::tag_index::TagIndex;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// 相似度各部分的权重
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct SimilarityWeights {
+    pub tags: f64,
+    pub concepts: f64,
+    pub text: f64,
+}
+
+impl Default for SimilarityWeights {
+    fn default() -> Self {
+        Self {
+            tags: 0.5,
+            concepts: 0.3,
+            text: 0.2,
+        }
+    }
+}
+
+/// 相似股票查询选项
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SimilarityOptions {
+    #[serde(default)]
+    pub weights: SimilarityWeights,
+    /// 是否计算经营范围和公司简介的 TF-IDF 文本相似度
+    #[serde(default)]
+    pub use_text: bool,
+    /// 只统计这些分类的标签（为空时统计所有分类）
+    #[serde(default)]
+    pub categories: Vec<String>,
+}
+
+/// 相似度明细，便于解释排序结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimilarityBreakdown {
+    /// 自定义标签的加权 Jaccard 相似度
+    pub tag_score: f64,
+    /// 板块概念的加权 Jaccard 相似度
+    pub concept_score: f64,
+    /// 文本余弦相似度（未启用时为 None）
+    pub text_score: Option<f64>,
+    /// 共同的标签（"分类:标签"，按权重降序）
+    pub shared_tags: Vec<String>,
+    /// 共同的板块概念（按权重降序）
+    pub shared_concepts: Vec<String>,
+}
+
+/// 单只相似股票
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimilarStock {
+    pub stock_code: String,
+    pub stock_name: String,
+    pub exchange: String,
+    /// 加权后的综合得分，取值 0 ~ 1
+    pub score: f64,
+    pub breakdown: SimilarityBreakdown,
+}
+
+/// 每只股票的标签集合，标签按 "分类:名称" 计（忽略补充说明，避免说明不同的同名标签无法匹配）
+fn stock_tag_sets(index: &TagIndex, categories: &[String]) -> Vec<HashSet<String>> {
+    index
+        .stock_tags
+        .iter()
+        .map(|tags| {
+            tags.iter()
+                .filter(|(category, _)| categories.is_empty() || categories.contains(category))
+                .flat_map(|(category, items)| {
+                    items
+                        .iter()
+                        .map(move |item| format!("{}:{}", category, item.name))
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn stock_concept_sets(stock_data: &[StockCompanyInfo]) -> Vec<HashSet<String>> {
+    stock_data
+        .iter()
+        .map(|stock| {
+            stock
+                .sectors_concepts
+                .iter()
+                .map(|concept| concept.trim())
+                .filter(|concept| !concept.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .collect()
+}
+
+/// 集合元素的 idf 权重：出现的股票越少权重越高
+fn idf_weights(sets: &[HashSet<String>]) -> HashMap<&str, f64> {
+    let mut document_frequency: HashMap<&str, usize> = HashMap::new();
+    for set in sets {
+        for item in set {
+            *document_frequency.entry(item.as_str()).or_default() += 1;
+        }
+    }
+    let total = sets.len() as f64;
+    document_frequency
+        .into_iter()
+        .map(|(item, df)| (item, (1.0 + total / df as f64).ln()))
+        .collect()
+}
+
+/// 加权 Jaccard：共同元素的权重和 / 并集元素的权重和，同时返回共同元素
+fn weighted_jaccard<'a>(
+    target: &'a HashSet<String>,
+    other: &HashSet<String>,
+    weights: &HashMap<&str, f64>,
+) -> (f64, Vec<&'a String>) {
+    let weight = |item: &String| weights.get(item.as_str()).copied().unwrap_or(0.0);
+    let mut shared: Vec<&String> = target.iter().filter(|item| other.contains(*item)).collect();
+    if shared.is_empty() {
+        return (0.0, shared);
+    }
+
+    let intersection: f64 = shared.iter().map(|item| weight(item)).sum();
+    let union: f64 = target.iter().map(weight).sum::<f64>() + other.iter().map(weight).sum::<f64>()
+        - intersection;
+    shared.sort_by(|a, b| weight(b).total_cmp(&weight(a)).then_with(|| a.cmp(b)));
+    let score = if union > 0.0 {
+        intersection / union
+    } else {
+        0.0
+    };
+    (score, shared)
+}
+
+/// 查找与指定股票最相似的 k 只股票
+pub fn find_similar(
+    stock_data: &[StockCompanyInfo],
+    index: &TagIndex,
+    text_index: Option<&CompanyTextIndex>,
+    stock_code: &str,
+    k: u32,
+    options: &SimilarityOptions,
+) -> CommandResult<Vec<SimilarStock>> {
+    let target_id = index
+        .position(stock_code)
+        .ok_or_else(|| CommandError::NotFound(format!("Stock {}", stock_code)))?;
+
+    let tag_sets = stock_tag_sets(index, &options.categories);
+    let concept_sets = stock_concept_sets(stock_data);
+    let tag_weights = idf_weights(&tag_sets);
+    let concept_weights = idf_weights(&concept_sets);
+    let text_scores = match (options.use_text, text_index) {
+        (true, Some(text_index)) => Some(text_index.similar_documents(&stock_data[target_id])),
+        _ => None,
+    };
+
+    let weights = options.weights;
+    let total_weight = weights.tags.max(0.0)
+        + weights.concepts.max(0.0)
+        + if text_scores.is_some() {
+            weights.text.max(0.0)
+        } else {
+            0.0
+        };
+    if total_weight <= 0.0 {
+        return Err(CommandError::InvalidInput(
+            "Similarity weights must not all be zero".to_string(),
+        ));
+    }
+
+    let mut peers: Vec<SimilarStock> = (0..stock_data.len())
+        .into_par_iter()
+        .filter(|&stock_id| stock_id != target_id)
+        .filter_map(|stock_id| {
+            let (tag_score, shared_tags) =
+                weighted_jaccard(&tag_sets[target_id], &tag_sets[stock_id], &tag_weights);
+            let (concept_score, shared_concepts) = weighted_jaccard(
+                &concept_sets[target_id],
+                &concept_sets[stock_id],
+                &concept_weights,
+            );
+            let text_score = text_scores
+                .as_ref()
+                .map(|scores| scores.get(&stock_id).copied().unwrap_or(0.0));
+
+            let score = (weights.tags.max(0.0) * tag_score
+                + weights.concepts.max(0.0) * concept_score
+                + weights.text.max(0.0) * text_score.unwrap_or(0.0))
+                / total_weight;
+            if score <= 0.0 {
+                return None;
+            }
+
+            let stock = &stock_data[stock_id];
+            Some(SimilarStock {
+                stock_code: stock.stock_code.clone(),
+                stock_name: stock.stock_name.clone(),
+                exchange: stock.exchange.clone(),
+                score,
+                breakdown: SimilarityBreakdown {
+                    tag_score,
+                    concept_score,
+                    text_score,
+                    shared_tags: shared_tags.into_iter().cloned().collect(),
+                    shared_concepts: shared_concepts.into_iter().cloned().collect(),
+                },
+            })
+        })
+        .collect();
+
+    peers.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.stock_code.cmp(&b.stock_code))
+    });
+    peers.truncate(k as usize);
+    Ok(peers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stock(code: &str, tags: &str, concepts: &[&str]) -> StockCompanyInfo {
+        StockCompanyInfo {
+            sectors_concepts: concepts.iter().map(|concept| concept.to_string()).collect(),
+            ..test_stock(code, tags)
+        }
+    }
+
+    fn fixture() -> (Vec<StockCompanyInfo>, TagIndex) {
+        let stock_data = vec![
+            stock("600001", "概念:机器人;概念:芯片", &["半导体", "AI"]),
+            stock("600002", "概念:机器人;概念:芯片", &["半导体"]),
+            stock("600003", "概念:机器人", &[]),
+            stock("600004", "行业:银行", &["银行"]),
+        ];
+        let index = TagIndex::build_with_synonyms(&stock_data, Default::default());
+        (stock_data, index)
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn ranks_by_weighted_jaccard() {
+        let (stock_data, index) = fixture();
+        let similar =
+            find_similar(&stock_data, &index, None, "600001", 10, &Default::default()).unwrap();
+        let codes: Vec<&str> = similar.iter().map(|s| s.stock_code.as_str()).collect();
+        assert_eq!(codes, vec!["600002", "600003"]);
+
+        // idf = ln(1 + N / df)，N = 4
+        let idf = |df: f64| (1.0 + 4.0 / df).ln();
+        let peer = &similar[0].breakdown;
+        assert_close(peer.tag_score, 1.0);
+        assert_close(peer.concept_score, idf(2.0) / (idf(2.0) + idf(1.0)));
+        assert_eq!(peer.text_score, None);
+        assert_eq!(peer.shared_tags, vec!["概念:芯片", "概念:机器人"]);
+        assert_eq!(peer.shared_concepts, vec!["半导体"]);
+        assert_close(
+            similar[0].score,
+            (0.5 * peer.tag_score + 0.3 * peer.concept_score) / 0.8,
+        );
+
+        let peer = &similar[1].breakdown;
+        assert_close(peer.tag_score, idf(3.0) / (idf(3.0) + idf(2.0)));
+        assert_close(peer.concept_score, 0.0);
+    }
+
+    #[test]
+    fn respects_options() {
+        let (stock_data, index) = fixture();
+        let options = SimilarityOptions {
+            categories: vec!["行业".to_string()],
+            ..Default::default()
+        };
+        // 只统计行业标签时没有共同标签，只剩板块概念
+        let similar = find_similar(&stock_data, &index, None, "600001", 10, &options).unwrap();
+        assert_eq!(similar.len(), 1);
+        assert_close(similar[0].breakdown.tag_score, 0.0);
+
+        let similar =
+            find_similar(&stock_data, &index, None, "600001", 1, &Default::default()).unwrap();
+        assert_eq!(similar.len(), 1);
+
+        let options = SimilarityOptions {
+            weights: SimilarityWeights {
+                tags: 0.0,
+                concepts: 0.0,
+                text: 1.0,
+            },
+            ..Default::default()
+        };
+        assert!(matches!(
+            find_similar(&stock_data, &index, None, "600001", 10, &options),
+            Err(CommandError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            find_similar(&stock_data, &index, None, "699999", 10, &Default::default()),
+            Err(CommandError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn text_similarity_is_optional() {
+        let (mut stock_data, _) = fixture();
+        stock_data[0].business_scope = "工业机器人研发".to_string();
+        stock_data[3].business_scope = "工业机器人租赁".to_string();
+        let index = TagIndex::build_with_synonyms(&stock_data, Default::default());
+        let text_index = CompanyTextIndex::build(&stock_data);
+        let options = SimilarityOptions {
+            use_text: true,
+            ..Default::default()
+        };
+        let similar = find_similar(
+            &stock_data,
+            &index,
+            Some(&text_index),
+            "600001",
+            10,
+            &options,
+        )
+        .unwrap();
+        let bank = similar
+            .iter()
+            .find(|s| s.stock_code == "600004")
+            .expect("text match");
+        assert!(bank.breakdown.text_score.unwrap() > 0.0);
+        assert_close(bank.breakdown.tag_score, 0.0);
+        assert!(similar.iter().all(|s| s.breakdown.text_score.is_some()));
+    }
+}
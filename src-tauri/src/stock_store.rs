@@ -0,0 +1,347 @@
+use crate::command_error::*;
+use crate::stock_data::*;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 默认保留的快照数量
+pub const DEFAULT_MAX_SNAPSHOTS: usize = 10;
+
+/// 文件头魔数与格式版本
+const STORE_MAGIC: &[u8; 4] = b"WMSD";
+const STORE_FORMAT_VERSION: u32 = 1;
+/// 魔数 + 版本 + 保存时间 + 股票数量 + 内容哈希
+const HEADER_LEN: usize = 4 + 4 + 8 + 4 + 8;
+
+const STORE_DIR_NAME: &str = "stock-data";
+const SNAPSHOT_DIR_NAME: &str = "snapshots";
+const CURRENT_FILE_NAME: &str = "current.bin";
+const DATA_FILE_EXTENSION: &str = "bin";
+
+/// 快照信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotInfo {
+    /// 快照 ID（创建时的毫秒时间戳）
+    pub id: u64,
+    pub created_at: u64,
+    pub stock_count: u32,
+    pub size_bytes: u64,
+}
+
+/// 数据文件头
+struct StoreHeader {
+    saved_at: u64,
+    stock_count: u32,
+    /// 数据内容的哈希
+    content_hash: u64,
+}
+
+/// 股票数据的本地存储 - 当前数据集与若干带时间戳的快照
+#[derive(Debug, Clone)]
+pub struct StockStore {
+    dir: PathBuf,
+    max_snapshots: usize,
+}
+
+impl StockStore {
+    /// 在应用数据目录下打开（必要时创建）存储目录
+    pub fn open(data_dir: &Path, max_snapshots: usize) -> Result<Self, String> {
+        let dir = data_dir.join(STORE_DIR_NAME);
+        std::fs::create_dir_all(dir.join(SNAPSHOT_DIR_NAME))
+            .map_err(|e| format!("Failed to create stock data directory: {}", e))?;
+        Ok(Self {
+            dir,
+            max_snapshots: max_snapshots.max(1),
+        })
+    }
+
+    fn current_path(&self) -> PathBuf {
+        self.dir.join(CURRENT_FILE_NAME)
+    }
+
+    fn snapshot_path(&self, id: u64) -> PathBuf {
+        self.dir
+            .join(SNAPSHOT_DIR_NAME)
+            .join(format!("{}.{}", id, DATA_FILE_EXTENSION))
+    }
+
+    /// 读取上次保存的数据集，文件不存在时返回 None
+    pub fn load_current(&self) -> Result<Option<Vec<StockCompanyInfo>>, String> {
+        let path = self.current_path();
+        if !path.exists() {
+            return Ok(None);
+        }
+        read_dataset(&path).map(Some)
+    }
+
+    /// 保存当前数据集
+    pub fn save_current(&self, stock_data: &[StockCompanyInfo]) -> Result<(), String> {
+        write_dataset(&self.current_path(), stock_data, now_millis())
+    }
+
+    /// 创建快照并淘汰超出数量的旧快照
+    /// 内容与最近一次快照相同时不再写入，直接返回该快照
+    pub fn create_snapshot(&self, stock_data: &[StockCompanyInfo]) -> Result<SnapshotInfo, String> {
+        let encoded = encode_dataset(stock_data)?;
+        let content_hash = content_hash(&encoded);
+        if let Some(latest) = self.list_snapshots()?.into_iter().next() {
+            if self.snapshot_header(latest.id)?.content_hash == content_hash {
+                return Ok(latest);
+            }
+        }
+
+        let mut id = now_millis();
+        while self.snapshot_path(id).exists() {
+            id += 1;
+        }
+
+        write_encoded(
+            &self.snapshot_path(id),
+            &encoded,
+            stock_data.len() as u32,
+            id,
+        )?;
+        self.prune_snapshots()?;
+        self.snapshot_info(id)
+    }
+
+    /// 所有快照，最新的在前
+    pub fn list_snapshots(&self) -> Result<Vec<SnapshotInfo>, String> {
+        let entries = std::fs::read_dir(self.dir.join(SNAPSHOT_DIR_NAME))
+            .map_err(|e| format!("Failed to list snapshots: {}", e))?;
+
+        let mut snapshots: Vec<SnapshotInfo> = entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let path = entry.path();
+                if path.extension()? != DATA_FILE_EXTENSION {
+                    return None;
+                }
+                path.file_stem()?.to_str()?.parse::<u64>().ok()
+            })
+            .filter_map(|id| self.snapshot_info(id).ok())
+            .collect();
+
+        snapshots.sort_by_key(|snapshot| std::cmp::Reverse(snapshot.id));
+        Ok(snapshots)
+    }
+
+    /// 读取快照中的数据集
+    pub fn load_snapshot(&self, id: u64) -> CommandResult<Vec<StockCompanyInfo>> {
+        let path = self.snapshot_path(id);
+        if !path.exists() {
+            return Err(CommandError::NotFound(format!("Snapshot {}", id)));
+        }
+        read_dataset(&path).map_err(CommandError::Storage)
+    }
+
+    /// 删除快照
+    pub fn delete_snapshot(&self, id: u64) -> CommandResult<()> {
+        let path = self.snapshot_path(id);
+        if !path.exists() {
+            return Err(CommandError::NotFound(format!("Snapshot {}", id)));
+        }
+        std::fs::remove_file(path)
+            .map_err(|e| CommandError::Storage(format!("Failed to delete snapshot: {}", e)))
+    }
+
+    fn snapshot_header(&self, id: u64) -> Result<StoreHeader, String> {
+        let mut reader = BufReader::new(
+            File::open(self.snapshot_path(id))
+                .map_err(|e| format!("Failed to open snapshot {}: {}", id, e))?,
+        );
+        read_header(&mut reader)
+    }
+
+    fn snapshot_info(&self, id: u64) -> Result<SnapshotInfo, String> {
+        let size_bytes = std::fs::metadata(self.snapshot_path(id))
+            .map_err(|e| format!("Failed to read snapshot {}: {}", id, e))?
+            .len();
+        let header = self.snapshot_header(id)?;
+
+        Ok(SnapshotInfo {
+            id,
+            created_at: header.saved_at,
+            stock_count: header.stock_count,
+            size_bytes,
+        })
+    }
+
+    fn prune_snapshots(&self) -> Result<(), String> {
+        for snapshot in self.list_snapshots()?.into_iter().skip(self.max_snapshots) {
+            self.delete_snapshot(snapshot.id)
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+}
+
+/// 读取并校验文件头
+fn read_header(reader: &mut impl Read) -> Result<StoreHeader, String> {
+    let mut header = [0u8; HEADER_LEN];
+    reader
+        .read_exact(&mut header)
+        .map_err(|e| format!("Failed to read stock data header: {}", e))?;
+
+    if &header[0..4] != STORE_MAGIC {
+        return Err("Not a stock data file".to_string());
+    }
+    let version = u32::from_le_bytes(header[4..8].try_into().unwrap());
+    if version != STORE_FORMAT_VERSION {
+        return Err(format!("Unsupported stock data format version {}", version));
+    }
+
+    Ok(StoreHeader {
+        saved_at: u64::from_le_bytes(header[8..16].try_into().unwrap()),
+        stock_count: u32::from_le_bytes(header[16..20].try_into().unwrap()),
+        content_hash: u64::from_le_bytes(header[20..28].try_into().unwrap()),
+    })
+}
+
+fn read_dataset(path: &Path) -> Result<Vec<StockCompanyInfo>, String> {
+    let mut reader =
+        BufReader::new(File::open(path).map_err(|e| format!("Failed to open stock data: {}", e))?);
+    read_header(&mut reader)?;
+    bincode::deserialize_from(reader).map_err(|e| format!("Failed to decode stock data: {}", e))
+}
+
+/// 读取外部数据文件：本地存储格式（带文件头）或 JSON 数组
+pub fn load_dataset_file(path: &Path) -> Result<Vec<StockCompanyInfo>, String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("Failed to read data file: {}", e))?;
+    if bytes.starts_with(STORE_MAGIC) {
+        let mut reader = bytes.as_slice();
+        read_header(&mut reader)?;
+        return bincode::deserialize(reader)
+            .map_err(|e| format!("Failed to decode stock data: {}", e));
+    }
+    serde_json::from_slice(&bytes).map_err(|e| format!("Failed to parse data file: {}", e))
+}
+
+fn encode_dataset(stock_data: &[StockCompanyInfo]) -> Result<Vec<u8>, String> {
+    bincode::serialize(stock_data).map_err(|e| format!("Failed to encode stock data: {}", e))
+}
+
+/// 编码后数据的 FNV-1a 哈希，用于判断快照内容是否变化
+fn content_hash(encoded: &[u8]) -> u64 {
+    encoded.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn write_dataset(
+    path: &Path,
+    stock_data: &[StockCompanyInfo],
+    saved_at: u64,
+) -> Result<(), String> {
+    let encoded = encode_dataset(stock_data)?;
+    write_encoded(path, &encoded, stock_data.len() as u32, saved_at)
+}
+
+/// 先写入临时文件再重命名，避免写入中断导致文件损坏
+fn write_encoded(
+    path: &Path,
+    encoded: &[u8],
+    stock_count: u32,
+    saved_at: u64,
+) -> Result<(), String> {
+    let temp_path = path.with_extension("tmp");
+    {
+        let mut writer = BufWriter::new(
+            File::create(&temp_path).map_err(|e| format!("Failed to create stock data: {}", e))?,
+        );
+        writer
+            .write_all(STORE_MAGIC)
+            .and_then(|_| writer.write_all(&STORE_FORMAT_VERSION.to_le_bytes()))
+            .and_then(|_| writer.write_all(&saved_at.to_le_bytes()))
+            .and_then(|_| writer.write_all(&stock_count.to_le_bytes()))
+            .and_then(|_| writer.write_all(&content_hash(encoded).to_le_bytes()))
+            .and_then(|_| writer.write_all(encoded))
+            .and_then(|_| writer.flush())
+            .map_err(|e| format!("Failed to write stock data: {}", e))?;
+    }
+    std::fs::rename(&temp_path, path).map_err(|e| format!("Failed to save stock data: {}", e))
+}
+
+/// 先写入同目录下的临时文件再替换目标文件，避免写入中断时损坏原文件
+pub fn write_file_atomic(path: &Path, contents: impl AsRef<[u8]>) -> std::io::Result<()> {
+    let temp_path = path.with_extension("tmp");
+    std::fs::write(&temp_path, contents)?;
+    std::fs::rename(&temp_path, path)
+}
+
+/// 当前毫秒时间戳
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("stock-store-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn saves_and_loads_current() {
+        let dir = temp_dir("current");
+        let store = StockStore::open(&dir, DEFAULT_MAX_SNAPSHOTS).unwrap();
+        assert!(store.load_current().unwrap().is_none());
+
+        let data = vec![test_stock("600001", "概念:AI"), test_stock("600002", "")];
+        store.save_current(&data).unwrap();
+        assert_eq!(store.load_current().unwrap(), Some(data.clone()));
+        assert_eq!(load_dataset_file(&store.current_path()).unwrap(), data);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_unknown_headers() {
+        assert!(read_header(&mut &b"JSON0000000000000000000000000"[..]).is_err());
+
+        let mut bytes = STORE_MAGIC.to_vec();
+        bytes.extend_from_slice(&(STORE_FORMAT_VERSION + 1).to_le_bytes());
+        bytes.resize(HEADER_LEN, 0);
+        let error = read_header(&mut bytes.as_slice()).err().unwrap();
+        assert!(error.contains("version"), "{}", error);
+    }
+
+    #[test]
+    fn snapshots_skip_unchanged_content_and_prune() {
+        let dir = temp_dir("snapshots");
+        let store = StockStore::open(&dir, 2).unwrap();
+        let first = store
+            .create_snapshot(&[test_stock("600001", "概念:AI")])
+            .unwrap();
+        let again = store
+            .create_snapshot(&[test_stock("600001", "概念:AI")])
+            .unwrap();
+        assert_eq!(first.id, again.id);
+        assert_eq!(store.list_snapshots().unwrap().len(), 1);
+
+        store
+            .create_snapshot(&[test_stock("600001", "概念:芯片")])
+            .unwrap();
+        let latest = store.create_snapshot(&[test_stock("600002", "")]).unwrap();
+        let ids: Vec<u64> = store
+            .list_snapshots()
+            .unwrap()
+            .iter()
+            .map(|s| s.id)
+            .collect();
+        assert_eq!(ids.len(), 2);
+        assert_eq!(ids[0], latest.id);
+        assert!(matches!(
+            store.load_snapshot(first.id),
+            Err(CommandError::NotFound(_))
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
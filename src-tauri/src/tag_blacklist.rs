@@ -0,0 +1,148 @@
+use regex::RegexSet;
+use serde::{Deserialize, Serialize};
+
+/// 内置的黑名单规则，与仓库根目录的 tag-blacklist-regex.json 保持一致
+const BUILTIN_BLACKLIST: &str = include_str!("../../tag-blacklist-regex.json");
+
+/// 黑名单文件格式
+#[derive(Debug, Clone, Deserialize)]
+struct BlacklistFile {
+    #[serde(default)]
+    description: Option<String>,
+    patterns: Vec<BlacklistGroupDef>,
+}
+
+/// 黑名单分组定义（如 减持相关、报告披露）
+#[derive(Debug, Clone, Deserialize)]
+struct BlacklistGroupDef {
+    category: String,
+    patterns: Vec<String>,
+}
+
+/// 黑名单检测结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlacklistMatch {
+    pub tag: String,
+    pub matched: bool,
+    /// 命中的黑名单分组（按文件中的顺序）
+    pub groups: Vec<String>,
+    /// 命中的具体正则
+    pub patterns: Vec<String>,
+}
+
+/// 黑名单概要信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlacklistInfo {
+    /// 规则来源文件，内置规则为 None
+    pub source: Option<String>,
+    pub description: Option<String>,
+    pub groups: Vec<String>,
+    pub pattern_count: u32,
+}
+
+/// 标签黑名单引擎 - 所有正则在加载时一次性编译为 RegexSet
+#[derive(Debug, Clone)]
+pub struct TagBlacklist {
+    source: Option<String>,
+    description: Option<String>,
+    groups: Vec<String>,
+    patterns: Vec<String>,
+    /// 正则下标 → 分组下标
+    pattern_groups: Vec<usize>,
+    set: RegexSet,
+}
+
+impl TagBlacklist {
+    /// 加载内置规则
+    pub fn builtin() -> Self {
+        Self::from_json(BUILTIN_BLACKLIST, None).expect("built-in tag blacklist must be valid")
+    }
+
+    /// 从黑名单 JSON 编译规则
+    pub fn from_json(json: &str, source: Option<String>) -> Result<Self, String> {
+        let file: BlacklistFile = serde_json::from_str(json)
+            .map_err(|e| format!("Failed to parse tag blacklist: {}", e))?;
+
+        let mut groups = Vec::with_capacity(file.patterns.len());
+        let mut patterns = Vec::new();
+        let mut pattern_groups = Vec::new();
+
+        for (group_index, group) in file.patterns.into_iter().enumerate() {
+            groups.push(group.category);
+            for pattern in group.patterns {
+                patterns.push(pattern);
+                pattern_groups.push(group_index);
+            }
+        }
+
+        let set = RegexSet::new(&patterns)
+            .map_err(|e| format!("Invalid tag blacklist pattern: {}", e))?;
+
+        Ok(Self {
+            source,
+            description: file.description,
+            groups,
+            patterns,
+            pattern_groups,
+            set,
+        })
+    }
+
+    /// 从文件加载规则
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read tag blacklist {}: {}", path, e))?;
+        Self::from_json(&json, Some(path.to_string()))
+    }
+
+    /// 按原来源重新加载（文件规则重新读取，内置规则保持不变）
+    pub fn reload(&self) -> Result<Self, String> {
+        match &self.source {
+            Some(path) => Self::from_file(path),
+            None => Ok(Self::builtin()),
+        }
+    }
+
+    /// 返回标签命中的第一个黑名单分组
+    pub fn match_group(&self, tag_name: &str) -> Option<&str> {
+        self.set
+            .matches(tag_name)
+            .iter()
+            .map(|pattern_index| self.pattern_groups[pattern_index])
+            .min()
+            .map(|group_index| self.groups[group_index].as_str())
+    }
+
+    /// 检测标签并返回所有命中的分组和正则
+    pub fn test(&self, tag_name: &str) -> BlacklistMatch {
+        let matched: Vec<usize> = self.set.matches(tag_name).into_iter().collect();
+
+        let mut groups: Vec<String> = Vec::new();
+        for &pattern_index in &matched {
+            let group = &self.groups[self.pattern_groups[pattern_index]];
+            if !groups.contains(group) {
+                groups.push(group.clone());
+            }
+        }
+
+        BlacklistMatch {
+            tag: tag_name.to_string(),
+            matched: !matched.is_empty(),
+            groups,
+            patterns: matched
+                .iter()
+                .map(|&pattern_index| self.patterns[pattern_index].clone())
+                .collect(),
+        }
+    }
+
+    /// 黑名单概要信息
+    pub fn info(&self) -> BlacklistInfo {
+        BlacklistInfo {
+            source: self.source.clone(),
+            description: self.description.clone(),
+            groups: self.groups.clone(),
+            pattern_count: self.patterns.len() as u32,
+        }
+    }
+}
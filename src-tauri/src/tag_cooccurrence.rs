@@ -0,0 +1,498 @@
+use crate::command_error::*;
+use crate::stock_data::*;
+use crate::tag_index::{tag_key, IndexedTag, TagIndex};
+use crate::tag_processor::tag_id;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// 关联标签的统计范围
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AssociationScope {
+    /// 所有分类
+    #[default]
+    All,
+    /// 只统计与所选标签同一分类的标签
+    SameCategory,
+    /// 只统计其他分类的标签
+    CrossCategory,
+}
+
+/// 关联标签的排序方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AssociationSortKey {
+    /// 共现股票数
+    Count,
+    /// 提升度
+    #[default]
+    Lift,
+    /// 置信度（所选标签 → 关联标签）
+    Confidence,
+    /// Jaccard 相似度
+    Jaccard,
+}
+
+/// 标签关联查询参数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagAssociationParams {
+    pub category_name: String,
+    pub tag_name: String,
+    pub tag_detail: Option<String>,
+    #[serde(default)]
+    pub scope: AssociationScope,
+    #[serde(default)]
+    pub sort_by: AssociationSortKey,
+    /// 最少共现股票数
+    #[serde(default)]
+    pub min_support: u32,
+    /// 关联标签自身最少的股票数（过滤只出现一两次的标签，避免提升度虚高）
+    #[serde(default)]
+    pub min_tag_count: u32,
+    /// 返回前 N 个关联标签
+    pub top_n: u32,
+}
+
+/// 共现统计的股票
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssociatedStock {
+    pub stock_code: String,
+    pub stock_name: String,
+}
+
+/// 两个标签之间的共现指标
+/// 股票总数 N 取带有自定义标签的股票数量
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct CooccurrenceMetrics {
+    /// 同时带有两个标签的股票数
+    pub count: u32,
+    /// count / N
+    pub support: f64,
+    /// count / 所选标签（或矩阵中第一个标签）的股票数
+    pub confidence: f64,
+    /// count / 关联标签（或矩阵中第二个标签）的股票数
+    pub reverse_confidence: f64,
+    /// count × N / (两个标签股票数的乘积)，大于 1 表示正相关
+    pub lift: f64,
+    /// count / 两个标签股票集合的并集大小
+    pub jaccard: f64,
+}
+
+impl CooccurrenceMetrics {
+    fn compute(count: usize, count_a: usize, count_b: usize, total: usize) -> Self {
+        let ratio = |numerator: f64, denominator: f64| {
+            if denominator > 0.0 {
+                numerator / denominator
+            } else {
+                0.0
+            }
+        };
+        let (count_f, a, b, n) = (count as f64, count_a as f64, count_b as f64, total as f64);
+        Self {
+            count: count as u32,
+            support: ratio(count_f, n),
+            confidence: ratio(count_f, a),
+            reverse_confidence: ratio(count_f, b),
+            lift: ratio(count_f * n, a * b),
+            jaccard: ratio(count_f, a + b - count_f),
+        }
+    }
+}
+
+/// 单个关联标签
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagAssociation {
+    pub category_name: String,
+    pub tag_name: String,
+    pub tag_detail: Option<String>,
+    /// 关联标签自身的股票数
+    pub tag_count: u32,
+    pub metrics: CooccurrenceMetrics,
+    /// 同时带有两个标签的股票
+    pub stocks: Vec<AssociatedStock>,
+}
+
+/// 标签关联查询结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagAssociationResult {
+    pub category_name: String,
+    pub tag_name: String,
+    pub tag_detail: Option<String>,
+    /// 所选标签的股票数
+    pub tag_count: u32,
+    /// 统计使用的股票总数 N
+    pub total_stocks: u32,
+    /// 满足阈值的关联标签总数（截取前 top_n 之前）
+    pub total_associations: u32,
+    pub associations: Vec<TagAssociation>,
+}
+
+/// 共现矩阵参数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CooccurrenceMatrixParams {
+    /// 参与统计的分类（为空时统计所有分类）
+    #[serde(default)]
+    pub categories: Vec<String>,
+    /// 标签自身最少的股票数
+    #[serde(default)]
+    pub min_tag_count: u32,
+    /// 最少共现股票数
+    #[serde(default)]
+    pub min_support: u32,
+    /// 按股票数取前 max_tags 个标签参与统计
+    pub max_tags: u32,
+}
+
+/// 矩阵中的标签
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MatrixTag {
+    pub category_name: String,
+    pub tag_name: String,
+    pub tag_detail: Option<String>,
+    pub count: u32,
+}
+
+/// 矩阵中的一对标签（first < second，为 tags 中的下标）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MatrixPair {
+    pub first: u32,
+    pub second: u32,
+    pub metrics: CooccurrenceMetrics,
+}
+
+/// 稀疏共现矩阵：只包含满足最少共现股票数的标签对
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CooccurrenceMatrix {
+    pub tags: Vec<MatrixTag>,
+    pub pairs: Vec<MatrixPair>,
+    pub total_stocks: u32,
+}
+
+/// 标签的去重股票下标（同一股票在同一分类下重复打标时只计一次）
+fn distinct_stock_ids(tag: &IndexedTag) -> Vec<usize> {
+    let mut stock_ids = tag.stock_ids.clone();
+    stock_ids.dedup();
+    stock_ids
+}
+
+/// 查询与所选标签共同出现的标签，按指定指标排序后返回前 top_n 个
+pub fn find_tag_associations(
+    stock_data: &[StockCompanyInfo],
+    index: &TagIndex,
+    params: &TagAssociationParams,
+) -> CommandResult<TagAssociationResult> {
+    let selected = index
+        .tag(
+            &params.category_name,
+            &params.tag_name,
+            params.tag_detail.as_deref(),
+        )
+        .ok_or_else(|| {
+            CommandError::NotFound(format!(
+                "Tag {}",
+                tag_id(
+                    &params.category_name,
+                    &params.tag_name,
+                    params.tag_detail.as_deref()
+                )
+            ))
+        })?;
+    let selected_key = tag_key(&params.tag_name, params.tag_detail.as_deref());
+    let selected_ids = distinct_stock_ids(selected);
+    let total = index.stocks_with_tags as usize;
+
+    // (分类, 标签键) → 共现的股票下标
+    let mut cooccurring: HashMap<(&str, String), Vec<usize>> = HashMap::new();
+    for &stock_id in &selected_ids {
+        let Some(tags) = index.stock_tags.get(stock_id) else {
+            continue;
+        };
+        let mut seen: HashSet<(&str, String)> = HashSet::new();
+        for (category, items) in tags {
+            let in_scope = match params.scope {
+                AssociationScope::All => true,
+                AssociationScope::SameCategory => *category == params.category_name,
+                AssociationScope::CrossCategory => *category != params.category_name,
+            };
+            if !in_scope {
+                continue;
+            }
+            for item in items {
+                let key = tag_key(&item.name, item.detail.as_deref());
+                if *category == params.category_name && key == selected_key {
+                    continue;
+                }
+                if seen.insert((category.as_str(), key.clone())) {
+                    cooccurring
+                        .entry((category.as_str(), key))
+                        .or_default()
+                        .push(stock_id);
+                }
+            }
+        }
+    }
+
+    let mut associations: Vec<TagAssociation> = cooccurring
+        .into_iter()
+        .filter(|(_, stock_ids)| stock_ids.len() >= params.min_support as usize)
+        .filter_map(|((category, key), stock_ids)| {
+            let tag = index.category(category)?.get(&key)?;
+            let tag_count = distinct_stock_ids(tag).len();
+            if tag_count < params.min_tag_count as usize {
+                return None;
+            }
+            Some(TagAssociation {
+                category_name: category.to_string(),
+                tag_name: tag.name.clone(),
+                tag_detail: tag.detail.clone(),
+                tag_count: tag_count as u32,
+                metrics: CooccurrenceMetrics::compute(
+                    stock_ids.len(),
+                    selected_ids.len(),
+                    tag_count,
+                    total,
+                ),
+                stocks: stock_ids
+                    .iter()
+                    .filter_map(|&id| stock_data.get(id))
+                    .map(|stock| AssociatedStock {
+                        stock_code: stock.stock_code.clone(),
+                        stock_name: stock.stock_name.clone(),
+                    })
+                    .collect(),
+            })
+        })
+        .collect();
+
+    let sort_value = |metrics: &CooccurrenceMetrics| match params.sort_by {
+        AssociationSortKey::Count => metrics.count as f64,
+        AssociationSortKey::Lift => metrics.lift,
+        AssociationSortKey::Confidence => metrics.confidence,
+        AssociationSortKey::Jaccard => metrics.jaccard,
+    };
+    associations.sort_by(|a, b| {
+        sort_value(&b.metrics)
+            .total_cmp(&sort_value(&a.metrics))
+            .then_with(|| b.metrics.count.cmp(&a.metrics.count))
+            .then_with(|| a.category_name.cmp(&b.category_name))
+            .then_with(|| a.tag_name.cmp(&b.tag_name))
+            .then_with(|| a.tag_detail.cmp(&b.tag_detail))
+    });
+
+    let total_associations = associations.len() as u32;
+    associations.truncate(params.top_n as usize);
+
+    Ok(TagAssociationResult {
+        category_name: params.category_name.clone(),
+        tag_name: params.tag_name.clone(),
+        tag_detail: params.tag_detail.clone(),
+        tag_count: selected_ids.len() as u32,
+        total_stocks: total as u32,
+        total_associations,
+        associations,
+    })
+}
+
+/// 计算指定分类内（或跨分类）标签两两之间的共现矩阵
+pub fn build_cooccurrence_matrix(
+    index: &TagIndex,
+    params: &CooccurrenceMatrixParams,
+) -> CooccurrenceMatrix {
+    let total = index.stocks_with_tags as usize;
+
+    // 选出参与统计的标签：按股票数降序，取前 max_tags 个
+    let mut candidates: Vec<(&str, &str, &IndexedTag, Vec<usize>)> = index
+        .categories
+        .iter()
+        .filter(|(category, _)| {
+            params.categories.is_empty() || params.categories.contains(category)
+        })
+        .flat_map(|(category, tags)| {
+            tags.iter()
+                .map(move |(key, tag)| (category.as_str(), key.as_str(), tag))
+        })
+        .map(|(category, key, tag)| (category, key, tag, distinct_stock_ids(tag)))
+        .filter(|(_, _, _, stock_ids)| stock_ids.len() >= params.min_tag_count.max(1) as usize)
+        .collect();
+    candidates.sort_by(|a, b| {
+        b.3.len()
+            .cmp(&a.3.len())
+            .then_with(|| a.0.cmp(b.0))
+            .then_with(|| a.1.cmp(b.1))
+    });
+    candidates.truncate(params.max_tags as usize);
+
+    // 按股票汇总其携带的候选标签，再两两累加
+    let mut stock_tags: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (tag_id, (_, _, _, stock_ids)) in candidates.iter().enumerate() {
+        for &stock_id in stock_ids {
+            stock_tags.entry(stock_id).or_default().push(tag_id);
+        }
+    }
+    let mut pair_counts: HashMap<(usize, usize), usize> = HashMap::new();
+    for tag_ids in stock_tags.values() {
+        for (i, &first) in tag_ids.iter().enumerate() {
+            for &second in &tag_ids[i + 1..] {
+                *pair_counts.entry((first, second)).or_default() += 1;
+            }
+        }
+    }
+
+    let mut pairs: Vec<MatrixPair> = pair_counts
+        .into_iter()
+        .filter(|&(_, count)| count >= params.min_support.max(1) as usize)
+        .map(|((first, second), count)| MatrixPair {
+            first: first as u32,
+            second: second as u32,
+            metrics: CooccurrenceMetrics::compute(
+                count,
+                candidates[first].3.len(),
+                candidates[second].3.len(),
+                total,
+            ),
+        })
+        .collect();
+    pairs.sort_by_key(|pair| (pair.first, pair.second));
+
+    CooccurrenceMatrix {
+        tags: candidates
+            .into_iter()
+            .map(|(category, _, tag, stock_ids)| MatrixTag {
+                category_name: category.to_string(),
+                tag_name: tag.name.clone(),
+                tag_detail: tag.detail.clone(),
+                count: stock_ids.len() as u32,
+            })
+            .collect(),
+        pairs,
+        total_stocks: total as u32,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// N = 5（最后一只股票没有标签，不计入）
+    /// 机器人 {1,2,3}，芯片 {1,2,4}，银行 {3,5}
+    fn fixture() -> (Vec<StockCompanyInfo>, TagIndex) {
+        let stock_data = vec![
+            test_stock("600001", "概念:机器人;行业:芯片"),
+            test_stock("600002", "概念:机器人;行业:芯片"),
+            test_stock("600003", "概念:机器人;行业:银行"),
+            test_stock("600004", "行业:芯片"),
+            test_stock("600005", "行业:银行"),
+            test_stock("600006", ""),
+        ];
+        let index = TagIndex::build_with_synonyms(&stock_data, Default::default());
+        (stock_data, index)
+    }
+
+    fn params(sort_by: AssociationSortKey) -> TagAssociationParams {
+        TagAssociationParams {
+            category_name: "概念".to_string(),
+            tag_name: "机器人".to_string(),
+            tag_detail: None,
+            scope: AssociationScope::All,
+            sort_by,
+            min_support: 0,
+            min_tag_count: 0,
+            top_n: 10,
+        }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn computes_lift_confidence_and_jaccard() {
+        let (stock_data, index) = fixture();
+        let result =
+            find_tag_associations(&stock_data, &index, &params(AssociationSortKey::Lift)).unwrap();
+        assert_eq!((result.tag_count, result.total_stocks), (3, 5));
+        let names: Vec<&str> = result
+            .associations
+            .iter()
+            .map(|association| association.tag_name.as_str())
+            .collect();
+        assert_eq!(names, vec!["芯片", "银行"]);
+
+        let chip = result.associations[0].metrics;
+        assert_eq!(chip.count, 2);
+        assert_close(chip.support, 2.0 / 5.0);
+        assert_close(chip.confidence, 2.0 / 3.0);
+        assert_close(chip.reverse_confidence, 2.0 / 3.0);
+        assert_close(chip.lift, 2.0 * 5.0 / (3.0 * 3.0));
+        assert_close(chip.jaccard, 2.0 / (3.0 + 3.0 - 2.0));
+
+        let bank = result.associations[1].metrics;
+        assert_eq!(bank.count, 1);
+        assert_close(bank.confidence, 1.0 / 3.0);
+        assert_close(bank.reverse_confidence, 1.0 / 2.0);
+        assert_close(bank.lift, 5.0 / (3.0 * 2.0));
+        assert_close(bank.jaccard, 1.0 / (3.0 + 2.0 - 1.0));
+    }
+
+    #[test]
+    fn applies_thresholds_and_scope() {
+        let (stock_data, index) = fixture();
+        let mut params = params(AssociationSortKey::Count);
+        params.min_support = 2;
+        let result = find_tag_associations(&stock_data, &index, &params).unwrap();
+        assert_eq!(result.total_associations, 1);
+        assert_eq!(result.associations[0].stocks.len(), 2);
+
+        params.min_support = 0;
+        params.scope = AssociationScope::SameCategory;
+        let result = find_tag_associations(&stock_data, &index, &params).unwrap();
+        assert!(result.associations.is_empty());
+
+        params.tag_name = "量子".to_string();
+        assert!(matches!(
+            find_tag_associations(&stock_data, &index, &params),
+            Err(CommandError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn matrix_uses_the_same_metrics() {
+        let (_, index) = fixture();
+        let matrix = build_cooccurrence_matrix(
+            &index,
+            &CooccurrenceMatrixParams {
+                categories: Vec::new(),
+                min_tag_count: 0,
+                min_support: 0,
+                max_tags: 2,
+            },
+        );
+        let names: Vec<&str> = matrix
+            .tags
+            .iter()
+            .map(|tag| tag.tag_name.as_str())
+            .collect();
+        assert_eq!(names, vec!["机器人", "芯片"]);
+        assert_eq!(matrix.pairs.len(), 1);
+        assert_close(matrix.pairs[0].metrics.lift, 10.0 / 9.0);
+        assert_close(matrix.pairs[0].metrics.jaccard, 0.5);
+
+        // 芯片和银行没有共同的股票
+        let matrix = build_cooccurrence_matrix(
+            &index,
+            &CooccurrenceMatrixParams {
+                categories: vec!["行业".to_string()],
+                min_tag_count: 0,
+                min_support: 0,
+                max_tags: 10,
+            },
+        );
+        assert_eq!(matrix.tags.len(), 2);
+        assert!(matrix.pairs.is_empty());
+    }
+}
@@ -0,0 +1,351 @@
+use crate::command_error::page_start;
+use crate::stock_data::*;
+use crate::tag_processor::{unescape_tag_text, unescaped_chars};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+
+/// custom_tags 中的问题类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TagProblemKind {
+    /// 缺少 ":" 分隔符
+    MissingSeparator,
+    /// 分类为空
+    EmptyCategory,
+    /// 标签内容或补充说明为空
+    EmptyValue,
+    /// 大括号嵌套
+    NestedBrace,
+    /// 大括号未闭合
+    UnclosedBrace,
+    /// 多余的 "}"
+    UnmatchedBrace,
+    /// "}" 之后还有内容
+    TextAfterBrace,
+    /// 同一分类下重复的标签
+    DuplicateTag,
+    /// 使用了全角 "：" 或 "；"
+    FullWidthSeparator,
+}
+
+impl TagProblemKind {
+    pub fn message(&self) -> &'static str {
+        match self {
+            TagProblemKind::MissingSeparator => "缺少分类分隔符 \":\"",
+            TagProblemKind::EmptyCategory => "分类名称为空",
+            TagProblemKind::EmptyValue => "标签内容为空",
+            TagProblemKind::NestedBrace => "补充说明中存在嵌套的大括号",
+            TagProblemKind::UnclosedBrace => "大括号未闭合",
+            TagProblemKind::UnmatchedBrace => "存在多余的右大括号",
+            TagProblemKind::TextAfterBrace => "补充说明之后还有多余内容",
+            TagProblemKind::DuplicateTag => "同一分类下标签重复",
+            TagProblemKind::FullWidthSeparator => "使用了全角分隔符",
+        }
+    }
+}
+
+/// 单个问题
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagProblem {
+    pub stock_code: String,
+    pub kind: TagProblemKind,
+    /// 问题在 custom_tags 中的字节区间 [start, end)
+    pub start: usize,
+    pub end: usize,
+    /// 出问题的原始文本
+    pub text: String,
+    pub message: String,
+}
+
+/// 全量数据的问题报告（带分页）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagDiagnosticsReport {
+    pub problems: Vec<TagProblem>,
+    pub total_problems: u32,
+    pub total_pages: u32,
+    pub current_page: u32,
+    /// 存在问题的股票数量
+    pub affected_stocks: u32,
+    /// 各类问题的数量
+    pub counts_by_kind: BTreeMap<TagProblemKind, u32>,
+}
+
+struct ProblemCollector<'a> {
+    stock_code: &'a str,
+    tags: &'a str,
+    problems: Vec<TagProblem>,
+}
+
+impl ProblemCollector<'_> {
+    fn push(&mut self, kind: TagProblemKind, start: usize, end: usize) {
+        self.problems.push(TagProblem {
+            stock_code: self.stock_code.to_string(),
+            kind,
+            start,
+            end,
+            text: self.tags[start..end].to_string(),
+            message: kind.message().to_string(),
+        });
+    }
+}
+
+/// 诊断模式解析 custom_tags，返回 parse_custom_tags 会静默丢弃或错误拆分的部分
+/// 与 parse_custom_tags 使用相同的转义规则，转义的 \; \: \{ \} 按字面字符处理
+pub fn diagnose_custom_tags(stock_code: &str, tags: &str) -> Vec<TagProblem> {
+    let mut collector = ProblemCollector {
+        stock_code,
+        tags,
+        problems: Vec::new(),
+    };
+
+    // 全角分隔符会导致整段无法拆分
+    for (offset, ch) in tags.char_indices() {
+        if ch == '：' || ch == '；' {
+            collector.push(
+                TagProblemKind::FullWidthSeparator,
+                offset,
+                offset + ch.len_utf8(),
+            );
+        }
+    }
+
+    let mut seen: HashSet<(String, String, Option<String>)> = HashSet::new();
+    let mut section_start = 0;
+    let separators = unescaped_chars(tags)
+        .filter(|&(_, ch)| ch == ';')
+        .map(|(i, _)| i)
+        .chain(std::iter::once(tags.len()));
+
+    for section_end in separators {
+        let raw_start = section_start;
+        let raw_section = &tags[raw_start..section_end];
+        section_start = section_end + 1;
+
+        let leading = raw_section.len() - raw_section.trim_start().len();
+        let section = raw_section.trim();
+        if section.is_empty() {
+            continue;
+        }
+        let start = raw_start + leading;
+        let end = start + section.len();
+
+        let colon_index = match unescaped_chars(section).find(|&(_, ch)| ch == ':') {
+            Some((index, _)) => index,
+            None => {
+                // 全角冒号已单独报告
+                if !section.contains('：') {
+                    collector.push(TagProblemKind::MissingSeparator, start, end);
+                }
+                continue;
+            }
+        };
+
+        let category = section[..colon_index].trim();
+        if category.is_empty() {
+            collector.push(TagProblemKind::EmptyCategory, start, end);
+            continue;
+        }
+
+        let raw_value = &section[colon_index + 1..];
+        let value_start =
+            start + colon_index + 1 + (raw_value.len() - raw_value.trim_start().len());
+        let value = raw_value.trim();
+        if value.is_empty() {
+            collector.push(TagProblemKind::EmptyValue, start, end);
+            continue;
+        }
+
+        let (name, detail) = diagnose_braces(&mut collector, value, value_start, start, end);
+
+        if name.is_empty() || detail == Some("") {
+            collector.push(TagProblemKind::EmptyValue, start, end);
+            continue;
+        }
+
+        let key = (
+            unescape_tag_text(category),
+            unescape_tag_text(name),
+            detail.map(unescape_tag_text),
+        );
+        if !seen.insert(key) {
+            collector.push(TagProblemKind::DuplicateTag, start, end);
+        }
+    }
+
+    collector.problems
+}
+
+/// 检查大括号结构，返回拆出的标签名称和补充说明
+fn diagnose_braces<'a>(
+    collector: &mut ProblemCollector,
+    value: &'a str,
+    value_start: usize,
+    section_start: usize,
+    section_end: usize,
+) -> (&'a str, Option<&'a str>) {
+    let mut depth = 0usize;
+    let mut open_index: Option<usize> = None;
+    let mut close_index: Option<usize> = None;
+
+    for (offset, ch) in unescaped_chars(value) {
+        match ch {
+            '{' => {
+                if depth > 0 {
+                    collector.push(
+                        TagProblemKind::NestedBrace,
+                        value_start + offset,
+                        value_start + offset + 1,
+                    );
+                } else if open_index.is_none() {
+                    open_index = Some(offset);
+                }
+                depth += 1;
+            }
+            '}' => {
+                if depth == 0 {
+                    collector.push(
+                        TagProblemKind::UnmatchedBrace,
+                        value_start + offset,
+                        value_start + offset + 1,
+                    );
+                } else {
+                    depth -= 1;
+                    if depth == 0 && close_index.is_none() {
+                        close_index = Some(offset);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if depth > 0 {
+        collector.push(TagProblemKind::UnclosedBrace, section_start, section_end);
+    }
+
+    match (open_index, close_index) {
+        (Some(open), Some(close)) => {
+            if close + 1 < value.len() {
+                collector.push(
+                    TagProblemKind::TextAfterBrace,
+                    value_start + close + 1,
+                    value_start + value.len(),
+                );
+            }
+            (value[..open].trim(), Some(value[open + 1..close].trim()))
+        }
+        _ => (value, None),
+    }
+}
+
+/// 生成全量数据的问题报告
+pub fn build_diagnostics_report(
+    stock_data: &[StockCompanyInfo],
+    kinds: Option<&[TagProblemKind]>,
+    page: u32,
+    per_page: u32,
+) -> TagDiagnosticsReport {
+    let mut problems: Vec<TagProblem> = stock_data
+        .par_iter()
+        .filter(|stock| !stock.custom_tags.is_empty())
+        .flat_map(|stock| diagnose_custom_tags(&stock.stock_code, &stock.custom_tags))
+        .collect();
+
+    if let Some(kinds) = kinds {
+        problems.retain(|problem| kinds.contains(&problem.kind));
+    }
+
+    let mut counts_by_kind: BTreeMap<TagProblemKind, u32> = BTreeMap::new();
+    let mut affected: HashSet<&str> = HashSet::new();
+    for problem in &problems {
+        *counts_by_kind.entry(problem.kind).or_default() += 1;
+        affected.insert(&problem.stock_code);
+    }
+    let affected_stocks = affected.len() as u32;
+
+    let total_problems = problems.len() as u32;
+    let total_pages = total_problems.div_ceil(per_page);
+    let start_index = page_start(page, per_page);
+
+    TagDiagnosticsReport {
+        problems: problems
+            .into_iter()
+            .skip(start_index)
+            .take(per_page as usize)
+            .collect(),
+        total_problems,
+        total_pages,
+        current_page: page,
+        affected_stocks,
+        counts_by_kind,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tag_processor::{parse_custom_tags_ordered, serialize_custom_tags};
+
+    fn kinds(tags: &str) -> Vec<TagProblemKind> {
+        diagnose_custom_tags("000001", tags)
+            .into_iter()
+            .map(|problem| problem.kind)
+            .collect()
+    }
+
+    #[test]
+    fn well_formed_tags_have_no_problems() {
+        assert!(kinds("行业:半导体{晶圆};概念:AI; 地区:上海 ;").is_empty());
+    }
+
+    #[test]
+    fn reports_malformed_sections() {
+        assert_eq!(kinds("半导体"), vec![TagProblemKind::MissingSeparator]);
+        assert_eq!(kinds(":半导体"), vec![TagProblemKind::EmptyCategory]);
+        assert_eq!(kinds("行业: "), vec![TagProblemKind::EmptyValue]);
+        assert_eq!(
+            kinds("行业:半导体{晶圆"),
+            vec![TagProblemKind::UnclosedBrace]
+        );
+        assert_eq!(kinds("行业:半导体}"), vec![TagProblemKind::UnmatchedBrace]);
+        assert_eq!(
+            kinds("行业:半导体{晶圆}x"),
+            vec![TagProblemKind::TextAfterBrace]
+        );
+        assert_eq!(
+            kinds("行业:半导体;行业:半导体"),
+            vec![TagProblemKind::DuplicateTag]
+        );
+        assert!(kinds("行业：半导体").contains(&TagProblemKind::FullWidthSeparator));
+    }
+
+    #[test]
+    fn problem_spans_point_into_the_original_text() {
+        let tags = "行业:半导体; 坏标签 ;概念:AI";
+        let problems = diagnose_custom_tags("000001", tags);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(&tags[problems[0].start..problems[0].end], "坏标签");
+        assert_eq!(problems[0].text, "坏标签");
+    }
+
+    #[test]
+    fn escaped_special_chars_are_not_problems() {
+        assert!(kinds(r"行业\;A:半导体\{x\};概念:a\:b{c\}d}").is_empty());
+    }
+
+    #[test]
+    fn serialized_tags_have_no_problems() {
+        let parsed = parse_custom_tags_ordered(
+            r"a\;b:x\{y\}{p\}q};分\:类:名:称{说\;明};概念:AI;概念:AI{\\}",
+        );
+        assert_eq!(parsed.categories.len(), 3);
+        let serialized = serialize_custom_tags(&parsed);
+        assert!(
+            kinds(&serialized).is_empty(),
+            "{:?} → {:?}",
+            serialized,
+            diagnose_custom_tags("000001", &serialized)
+        );
+    }
+}
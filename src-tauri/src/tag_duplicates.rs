@@ -0,0 +1,529 @@
+use crate::command_error::page_start;
+use crate::stock_data::*;
+use crate::tag_editor::{TagEdit, TagRef};
+use crate::tag_index::TagIndex;
+use crate::text_normalize::{to_half_width, to_simplified};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+/// 去掉后仍有意义的标签后缀
+const TAG_SUFFIXES: [&str; 3] = ["概念", "板块", "行业"];
+
+/// 疑似重复的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateReason {
+    /// 空白字符不同
+    Whitespace,
+    /// 全角/半角不同
+    FullWidth,
+    /// 英文大小写不同
+    LetterCase,
+    /// 繁体/简体不同
+    TraditionalChinese,
+    /// 多了 概念/板块/行业 后缀
+    Suffix,
+    /// 编辑距离很小
+    EditDistance,
+}
+
+impl DuplicateReason {
+    /// 仅因该原因判为重复时的置信度
+    fn confidence(&self) -> f64 {
+        match self {
+            DuplicateReason::Whitespace | DuplicateReason::FullWidth => 0.98,
+            DuplicateReason::LetterCase => 0.95,
+            DuplicateReason::TraditionalChinese => 0.9,
+            DuplicateReason::Suffix => 0.8,
+            DuplicateReason::EditDistance => 0.7,
+        }
+    }
+}
+
+/// 重复检测参数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateTagParams {
+    /// 只检测该分类（为空时检测所有分类）
+    #[serde(default)]
+    pub category_name: Option<String>,
+    /// 最低置信度，默认 0.5
+    #[serde(default)]
+    pub min_confidence: Option<f64>,
+    pub page: u32,
+    pub per_page: u32,
+}
+
+/// 组内的单个标签（按名称聚合，包含所有补充说明）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateTagMember {
+    pub tag_name: String,
+    pub count: u32,
+    /// 与建议名称相比的差异
+    pub reasons: Vec<DuplicateReason>,
+}
+
+/// 一组疑似重复的标签
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateTagGroup {
+    pub category_name: String,
+    /// 建议保留的名称（股票数最多的标签）
+    pub canonical_name: String,
+    pub members: Vec<DuplicateTagMember>,
+    pub reasons: Vec<DuplicateReason>,
+    /// 置信度，取值 0 ~ 1
+    pub confidence: f64,
+    /// 组内涉及的股票数（同一只股票只计一次）
+    pub affected_stocks: u32,
+    /// 将其余标签重命名为建议名称的编辑操作，可直接传给 preview_tag_edit / apply_tag_edit
+    /// 按同义词合并的标签为每个原始名称各生成一个操作
+    pub edits: Vec<TagEdit>,
+}
+
+/// 重复标签报告（带分页）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateTagReport {
+    pub groups: Vec<DuplicateTagGroup>,
+    pub total_groups: u32,
+    pub total_pages: u32,
+    pub current_page: u32,
+    /// 所有分组涉及的标签数
+    pub duplicate_tags: u32,
+}
+
+/// 分类内按名称聚合的标签
+struct NamedTag {
+    name: String,
+    stock_codes: HashSet<String>,
+    /// 股票中实际写的原始名称（按同义词合并到 name 之前）
+    variants: BTreeSet<String>,
+    /// 逐步规范化后的名称，以及每一步是否改变了名称
+    key: String,
+    reasons: BTreeSet<DuplicateReason>,
+}
+
+/// 执行一步规范化，名称有变化时记录原因
+fn normalize_step(
+    text: String,
+    reason: DuplicateReason,
+    reasons: &mut BTreeSet<DuplicateReason>,
+    step: impl Fn(&str) -> String,
+) -> String {
+    let next = step(&text);
+    if next != text {
+        reasons.insert(reason);
+    }
+    next
+}
+
+/// 依次去空白、转半角、转小写、转简体、去后缀，记录每一步带来的变化
+fn normalize_name(name: &str) -> (String, BTreeSet<DuplicateReason>) {
+    let mut reasons = BTreeSet::new();
+    let key = normalize_step(
+        name.to_string(),
+        DuplicateReason::Whitespace,
+        &mut reasons,
+        |text| text.chars().filter(|ch| !ch.is_whitespace()).collect(),
+    );
+    let key = normalize_step(key, DuplicateReason::FullWidth, &mut reasons, |text| {
+        text.chars().map(to_half_width).collect()
+    });
+    let key = normalize_step(key, DuplicateReason::LetterCase, &mut reasons, |text| {
+        text.to_lowercase()
+    });
+    let key = normalize_step(
+        key,
+        DuplicateReason::TraditionalChinese,
+        &mut reasons,
+        |text| text.chars().map(to_simplified).collect(),
+    );
+    let key = normalize_step(key, DuplicateReason::Suffix, &mut reasons, |text| {
+        TAG_SUFFIXES
+            .iter()
+            .find_map(|suffix| {
+                text.strip_suffix(suffix)
+                    .filter(|rest| rest.chars().count() >= 2)
+            })
+            .unwrap_or(text)
+            .to_string()
+    });
+    (key, reasons)
+}
+
+/// 规范化后相同的两个名称之间的差异：只取一方经历过的步骤，都经历过时取全部
+fn pair_reasons(a: &NamedTag, b: &NamedTag) -> Vec<DuplicateReason> {
+    let reasons: Vec<DuplicateReason> = a
+        .reasons
+        .symmetric_difference(&b.reasons)
+        .copied()
+        .collect();
+    if reasons.is_empty() {
+        a.reasons.union(&b.reasons).copied().collect()
+    } else {
+        reasons
+    }
+}
+
+/// 两个名称之间的编辑距离（Levenshtein，按字符计）
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, &ca) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != cb);
+            current[j + 1] = (previous[j] + cost)
+                .min(previous[j + 1] + 1)
+                .min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+/// 规范化名称之间允许的编辑距离：3~7 个字允许 1 处，更长允许 2 处
+fn max_distance(len: usize) -> usize {
+    match len {
+        0..=2 => 0,
+        3..=7 => 1,
+        _ => 2,
+    }
+}
+
+/// 只有数字不同的名称（如 2023年报 与 2024年报）不视为重复
+fn differs_only_in_digits(a: &str, b: &str) -> bool {
+    let strip = |text: &str| -> String { text.chars().filter(|ch| !ch.is_ascii_digit()).collect() };
+    a != b && strip(a) == strip(b)
+}
+
+/// 简单的并查集，合并时保留较低的置信度
+struct Groups {
+    parent: Vec<usize>,
+    confidence: Vec<f64>,
+}
+
+impl Groups {
+    fn new(len: usize) -> Self {
+        Self {
+            parent: (0..len).collect(),
+            confidence: vec![1.0; len],
+        }
+    }
+
+    fn find(&mut self, i: usize) -> usize {
+        let mut root = i;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        let mut node = i;
+        while self.parent[node] != root {
+            let next = self.parent[node];
+            self.parent[node] = root;
+            node = next;
+        }
+        root
+    }
+
+    fn union(&mut self, a: usize, b: usize, confidence: f64) {
+        let (root_a, root_b) = (self.find(a), self.find(b));
+        let merged = self.confidence[root_a]
+            .min(self.confidence[root_b])
+            .min(confidence);
+        if root_a != root_b {
+            self.parent[root_b] = root_a;
+        }
+        self.confidence[root_a] = merged;
+    }
+}
+
+/// 检测一个分类内的疑似重复标签
+fn detect_category_duplicates(
+    stock_data: &[StockCompanyInfo],
+    index: &TagIndex,
+    category_name: &str,
+) -> Vec<DuplicateTagGroup> {
+    // 在索引的聚合结果上按名称再聚合一次（忽略补充说明），同时记录原始名称
+    let mut by_name: BTreeMap<&str, (HashSet<String>, BTreeSet<String>)> = BTreeMap::new();
+    for tag in index
+        .category(category_name)
+        .into_iter()
+        .flat_map(|tags| tags.values())
+    {
+        let (stock_codes, variants) = by_name.entry(&tag.name).or_default();
+        stock_codes.extend(
+            tag.stock_ids
+                .iter()
+                .filter_map(|&id| stock_data.get(id))
+                .map(|stock| stock.stock_code.clone()),
+        );
+        variants.extend(tag.variants.keys().cloned());
+    }
+    let tags: Vec<NamedTag> = by_name
+        .into_iter()
+        .map(|(name, (stock_codes, variants))| {
+            let (key, reasons) = normalize_name(name);
+            NamedTag {
+                name: name.to_string(),
+                stock_codes,
+                variants,
+                key,
+                reasons,
+            }
+        })
+        .collect();
+
+    let mut groups = Groups::new(tags.len());
+
+    // 规范化后相同的名称
+    let mut by_key: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (i, tag) in tags.iter().enumerate() {
+        by_key.entry(tag.key.as_str()).or_default().push(i);
+    }
+    for members in by_key.values() {
+        for &other in &members[1..] {
+            let confidence = pair_reasons(&tags[members[0]], &tags[other])
+                .iter()
+                .map(DuplicateReason::confidence)
+                .fold(1.0, f64::min);
+            groups.union(members[0], other, confidence);
+        }
+    }
+
+    // 规范化后编辑距离很小的名称（每个规范化名称只取一个代表）
+    let mut keys: Vec<(&str, Vec<char>, usize)> = by_key
+        .iter()
+        .map(|(key, members)| (*key, key.chars().collect(), members[0]))
+        .collect();
+    keys.sort_by_key(|(_, chars, _)| chars.len());
+    for i in 0..keys.len() {
+        for j in i + 1..keys.len() {
+            let (key_a, chars_a, tag_a) = &keys[i];
+            let (key_b, chars_b, tag_b) = &keys[j];
+            let allowed = max_distance(chars_a.len().min(chars_b.len()));
+            if chars_b.len() - chars_a.len() > allowed {
+                break;
+            }
+            if allowed == 0 || differs_only_in_digits(key_a, key_b) {
+                continue;
+            }
+            let distance = edit_distance(chars_a, chars_b);
+            if distance <= allowed {
+                let confidence = DuplicateReason::EditDistance.confidence()
+                    * (1.0 - distance as f64 / chars_b.len() as f64);
+                groups.union(*tag_a, *tag_b, confidence);
+            }
+        }
+    }
+
+    let mut members_by_root: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for i in 0..tags.len() {
+        let root = groups.find(i);
+        members_by_root.entry(root).or_default().push(i);
+    }
+
+    members_by_root
+        .into_iter()
+        .filter(|(_, members)| members.len() > 1)
+        .map(|(root, mut members)| {
+            // 股票数最多的名称作为规范名称，数量相同时优先选择无需规范化的写法
+            members.sort_by(|&a, &b| {
+                tags[b]
+                    .stock_codes
+                    .len()
+                    .cmp(&tags[a].stock_codes.len())
+                    .then_with(|| tags[a].reasons.len().cmp(&tags[b].reasons.len()))
+                    .then_with(|| tags[a].name.cmp(&tags[b].name))
+            });
+            let canonical = &tags[members[0]];
+
+            // 股票中写的是原始名称，每个原始名称各生成一个重命名操作，别名也能被改写
+            let edits = members[1..]
+                .iter()
+                .flat_map(|&i| &tags[i].variants)
+                .map(|variant| TagEdit::Rename {
+                    tag: TagRef {
+                        category_name: category_name.to_string(),
+                        tag_name: variant.clone(),
+                        tag_detail: None,
+                        any_detail: true,
+                    },
+                    new_name: canonical.name.clone(),
+                })
+                .collect();
+
+            let mut group_reasons: BTreeSet<DuplicateReason> = BTreeSet::new();
+            let mut affected: HashSet<&str> = HashSet::new();
+            let members: Vec<DuplicateTagMember> = members
+                .iter()
+                .map(|&i| {
+                    let tag = &tags[i];
+                    affected.extend(tag.stock_codes.iter().map(String::as_str));
+                    let reasons = if i == members[0] {
+                        Vec::new()
+                    } else if tag.key == canonical.key {
+                        pair_reasons(tag, canonical)
+                    } else {
+                        vec![DuplicateReason::EditDistance]
+                    };
+                    group_reasons.extend(reasons.iter().copied());
+                    DuplicateTagMember {
+                        tag_name: tag.name.clone(),
+                        count: tag.stock_codes.len() as u32,
+                        reasons,
+                    }
+                })
+                .collect();
+
+            DuplicateTagGroup {
+                category_name: category_name.to_string(),
+                canonical_name: canonical.name.clone(),
+                members,
+                reasons: group_reasons.into_iter().collect(),
+                confidence: groups.confidence[root],
+                affected_stocks: affected.len() as u32,
+                edits,
+            }
+        })
+        .collect()
+}
+
+/// 生成疑似重复标签报告，按置信度和涉及股票数降序
+pub fn build_duplicate_report(
+    stock_data: &[StockCompanyInfo],
+    index: &TagIndex,
+    params: &DuplicateTagParams,
+) -> DuplicateTagReport {
+    let min_confidence = params.min_confidence.unwrap_or(0.5);
+    let mut categories: Vec<&str> = match &params.category_name {
+        Some(category_name) => vec![category_name.as_str()],
+        None => index.categories.keys().map(String::as_str).collect(),
+    };
+    categories.sort();
+
+    let mut groups: Vec<DuplicateTagGroup> = categories
+        .par_iter()
+        .flat_map_iter(|category_name| detect_category_duplicates(stock_data, index, category_name))
+        .filter(|group| group.confidence >= min_confidence)
+        .collect();
+    groups.sort_by(|a, b| {
+        b.confidence
+            .total_cmp(&a.confidence)
+            .then_with(|| b.affected_stocks.cmp(&a.affected_stocks))
+            .then_with(|| a.category_name.cmp(&b.category_name))
+            .then_with(|| a.canonical_name.cmp(&b.canonical_name))
+    });
+
+    let total_groups = groups.len() as u32;
+    let duplicate_tags = groups.iter().map(|group| group.members.len() as u32).sum();
+    let per_page = params.per_page;
+    let start_index = page_start(params.page, per_page);
+
+    DuplicateTagReport {
+        groups: groups
+            .into_iter()
+            .skip(start_index)
+            .take(per_page as usize)
+            .collect(),
+        total_groups,
+        total_pages: total_groups.div_ceil(per_page),
+        current_page: params.page,
+        duplicate_tags,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture() -> (Vec<StockCompanyInfo>, TagIndex) {
+        let stock_data = vec![
+            test_stock("600001", "概念:人工智能;概念:光刻胶;概念:2023年报"),
+            test_stock("600002", "概念:人工智能;概念:ChatGPT"),
+            test_stock("600003", "概念:人工智能概念;概念:chatgpt;概念:2024年报"),
+            test_stock("600004", "概念:ChatGPT;概念:光刻机"),
+        ];
+        let index = TagIndex::build_with_synonyms(&stock_data, Default::default());
+        (stock_data, index)
+    }
+
+    fn build_report(min_confidence: Option<f64>, page: u32, per_page: u32) -> DuplicateTagReport {
+        let (stock_data, index) = fixture();
+        build_duplicate_report(
+            &stock_data,
+            &index,
+            &DuplicateTagParams {
+                category_name: None,
+                min_confidence,
+                page,
+                per_page,
+            },
+        )
+    }
+
+    #[test]
+    fn normalizes_names_step_by_step() {
+        let (key, reasons) = normalize_name("Chat GPT概念");
+        assert_eq!(key, "chatgpt");
+        assert_eq!(
+            reasons.into_iter().collect::<Vec<_>>(),
+            vec![
+                DuplicateReason::Whitespace,
+                DuplicateReason::LetterCase,
+                DuplicateReason::Suffix
+            ]
+        );
+        // 去掉后缀后不足两个字时保留后缀
+        assert_eq!(normalize_name("5G概念").0, "5g");
+        assert_eq!(normalize_name("云概念").0, "云概念");
+        assert_eq!(edit_distance(&['光', '刻', '胶'], &['光', '刻', '机']), 1);
+        assert!(differs_only_in_digits("2023年报", "2024年报"));
+    }
+
+    #[test]
+    fn groups_duplicates_by_confidence() {
+        let report = build_report(None, 1, 10);
+        assert_eq!((report.total_groups, report.duplicate_tags), (2, 4));
+
+        let case = &report.groups[0];
+        assert_eq!(case.canonical_name, "ChatGPT");
+        assert_eq!(case.reasons, vec![DuplicateReason::LetterCase]);
+        assert_eq!(case.confidence, 0.95);
+        assert_eq!(case.affected_stocks, 3);
+        assert_eq!(
+            case.members
+                .iter()
+                .map(|member| (member.tag_name.as_str(), member.count))
+                .collect::<Vec<_>>(),
+            vec![("ChatGPT", 2), ("chatgpt", 1)]
+        );
+
+        let suffix = &report.groups[1];
+        assert_eq!(suffix.canonical_name, "人工智能");
+        assert_eq!(suffix.reasons, vec![DuplicateReason::Suffix]);
+        assert_eq!(suffix.confidence, 0.8);
+        match suffix.edits.as_slice() {
+            [TagEdit::Rename { tag, new_name }] => {
+                assert_eq!(
+                    (tag.tag_name.as_str(), new_name.as_str()),
+                    ("人工智能概念", "人工智能")
+                );
+                assert!(tag.any_detail);
+            }
+            edits => panic!("unexpected edits {:?}", edits),
+        }
+    }
+
+    #[test]
+    fn edit_distance_groups_have_lower_confidence() {
+        let report = build_report(Some(0.0), 1, 10);
+        assert_eq!(report.total_groups, 3);
+        let typo = &report.groups[2];
+        assert_eq!(typo.canonical_name, "光刻机");
+        assert_eq!(typo.members[1].reasons, vec![DuplicateReason::EditDistance]);
+        assert!((typo.confidence - 0.7 * (1.0 - 1.0 / 3.0)).abs() < 1e-9);
+
+        let page = build_report(Some(0.0), 2, 2);
+        assert_eq!((page.total_pages, page.groups.len()), (2, 1));
+        assert_eq!(page.groups[0].canonical_name, "光刻机");
+    }
+}
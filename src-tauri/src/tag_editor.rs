@@ -0,0 +1,290 @@
+use crate::command_error::*;
+use crate::stock_data::*;
+use crate::tag_processor::*;
+use crate::text_normalize::TagNormalizer;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// 标签引用：分类 + 标签名称 + 补充说明
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagRef {
+    pub category_name: String,
+    pub tag_name: String,
+    pub tag_detail: Option<String>,
+    /// 为 true 时忽略补充说明，匹配同名的所有标签
+    #[serde(default)]
+    pub any_detail: bool,
+}
+
+impl TagRef {
+    fn matches(&self, item: &TagItem) -> bool {
+        item.name == self.tag_name && (self.any_detail || item.detail == self.tag_detail)
+    }
+}
+
+/// 标签编辑操作
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TagEdit {
+    /// 重命名标签（保留补充说明）
+    Rename { tag: TagRef, new_name: String },
+    /// 将 source 合并到 target
+    Merge { source: TagRef, target: TagRef },
+    /// 将标签移动到另一个分类
+    Move { tag: TagRef, new_category: String },
+    /// 修改补充说明，new_detail 为 None 时移除补充说明
+    SetDetail {
+        tag: TagRef,
+        new_detail: Option<String>,
+    },
+    /// 从所有股票中删除标签
+    Delete { tag: TagRef },
+}
+
+impl TagEdit {
+    /// 检查编辑参数
+    pub fn validate(&self) -> CommandResult<()> {
+        let (tag, target_name) = match self {
+            TagEdit::Rename { tag, new_name } => (tag, Some(new_name)),
+            TagEdit::Merge { source, target } => (source, Some(&target.tag_name)),
+            TagEdit::Move { tag, new_category } => (tag, Some(new_category)),
+            TagEdit::SetDetail { tag, .. } | TagEdit::Delete { tag } => (tag, None),
+        };
+
+        if tag.category_name.trim().is_empty() || tag.tag_name.trim().is_empty() {
+            return Err(CommandError::InvalidInput(
+                "Tag reference must have a category and a name".to_string(),
+            ));
+        }
+        if target_name.is_some_and(|name| name.trim().is_empty()) {
+            return Err(CommandError::InvalidInput(
+                "Target name must not be empty".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// 对单只股票的解析结果执行编辑，返回是否有改动
+    fn apply(&self, parsed: &mut ParsedTags) -> bool {
+        match self {
+            TagEdit::Rename { tag, new_name } => {
+                update_items(parsed, tag, |item| item.name = new_name.trim().to_string())
+            }
+            TagEdit::Merge { source, target } => {
+                // 合并到自身不算改动
+                if !source.any_detail
+                    && source.category_name == target.category_name
+                    && source.tag_name == target.tag_name
+                    && source.tag_detail == target.tag_detail
+                {
+                    return false;
+                }
+                let merged = take_items(parsed, source);
+                if merged.is_empty() {
+                    return false;
+                }
+                parsed.push(
+                    &target.category_name,
+                    TagItem {
+                        name: target.tag_name.clone(),
+                        detail: target.tag_detail.clone(),
+                    },
+                );
+                true
+            }
+            TagEdit::Move { tag, new_category } => {
+                if tag.category_name == new_category.trim() {
+                    return false;
+                }
+                let moved = take_items(parsed, tag);
+                if moved.is_empty() {
+                    return false;
+                }
+                for item in moved {
+                    parsed.push(new_category, item);
+                }
+                true
+            }
+            TagEdit::SetDetail { tag, new_detail } => {
+                let new_detail = new_detail
+                    .as_deref()
+                    .map(str::trim)
+                    .filter(|detail| !detail.is_empty())
+                    .map(str::to_string);
+                update_items(parsed, tag, |item| item.detail = new_detail.clone())
+            }
+            TagEdit::Delete { tag } => !take_items(parsed, tag).is_empty(),
+        }
+    }
+}
+
+/// 修改匹配的标签，只有值确实变化时才算改动
+fn update_items(
+    parsed: &mut ParsedTags,
+    tag: &TagRef,
+    mut update: impl FnMut(&mut TagItem),
+) -> bool {
+    let mut changed = false;
+    if let Some(items) = parsed.get_mut(&tag.category_name) {
+        for item in items.iter_mut().filter(|item| tag.matches(item)) {
+            let before = item.clone();
+            update(item);
+            changed |= *item != before;
+        }
+    }
+    changed
+}
+
+/// 取出匹配的标签，分类为空时一并移除
+fn take_items(parsed: &mut ParsedTags, tag: &TagRef) -> Vec<TagItem> {
+    let Some(items) = parsed.get_mut(&tag.category_name) else {
+        return Vec::new();
+    };
+
+    let (taken, kept): (Vec<TagItem>, Vec<TagItem>) =
+        items.drain(..).partition(|item| tag.matches(item));
+    *items = kept;
+    parsed.remove_empty_categories();
+    taken
+}
+
+/// 单只股票的改动
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StockTagChange {
+    pub stock_code: String,
+    pub stock_name: String,
+    pub before: String,
+    pub after: String,
+}
+
+/// 编辑预览结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagEditPreview {
+    pub changes: Vec<StockTagChange>,
+    pub affected_stocks: u32,
+}
+
+/// 计算编辑对全部股票的影响（不修改数据）
+/// 受影响股票的 custom_tags 会先规范化，再保持原有顺序重新生成规范字符串；
+/// 生成结果与原字符串相同的股票不计入改动
+pub fn compute_tag_edit(
+    stock_data: &[StockCompanyInfo],
+    normalizer: &TagNormalizer,
+    edit: &TagEdit,
+) -> TagEditPreview {
+    let changes: Vec<StockTagChange> = stock_data
+        .par_iter()
+        .filter(|stock| !stock.custom_tags.is_empty())
+        .filter_map(|stock| {
+            let mut parsed = parse_normalized_tags(&stock.custom_tags, normalizer);
+            if !edit.apply(&mut parsed) {
+                return None;
+            }
+            let after = serialize_custom_tags(&parsed);
+            if after == stock.custom_tags {
+                return None;
+            }
+            Some(StockTagChange {
+                stock_code: stock.stock_code.clone(),
+                stock_name: stock.stock_name.clone(),
+                before: stock.custom_tags.clone(),
+                after,
+            })
+        })
+        .collect();
+
+    TagEditPreview {
+        affected_stocks: changes.len() as u32,
+        changes,
+    }
+}
+
+/// 将预览中的改动写回股票数据
+pub fn apply_tag_changes(stock_data: &mut [StockCompanyInfo], changes: &[StockTagChange]) {
+    let changes_by_code: HashMap<&str, &StockTagChange> = changes
+        .iter()
+        .map(|change| (change.stock_code.as_str(), change))
+        .collect();
+
+    for stock in stock_data.iter_mut() {
+        if let Some(change) = changes_by_code.get(stock.stock_code.as_str()) {
+            stock.custom_tags = change.after.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tag_ref(category: &str, name: &str) -> TagRef {
+        TagRef {
+            category_name: category.to_string(),
+            tag_name: name.to_string(),
+            tag_detail: None,
+            any_detail: true,
+        }
+    }
+
+    fn stocks() -> Vec<StockCompanyInfo> {
+        [("600001", "概念:AI;行业:芯片"), ("600002", "概念:机器人")]
+            .into_iter()
+            .map(|(code, tags)| test_stock(code, tags))
+            .collect()
+    }
+
+    fn changed_codes(edit: TagEdit) -> Vec<String> {
+        compute_tag_edit(&stocks(), &TagNormalizer::default(), &edit)
+            .changes
+            .into_iter()
+            .map(|change| change.stock_code)
+            .collect()
+    }
+
+    #[test]
+    fn edits_report_affected_stocks() {
+        let rename = TagEdit::Rename {
+            tag: tag_ref("概念", "AI"),
+            new_name: "人工智能".to_string(),
+        };
+        let preview = compute_tag_edit(&stocks(), &TagNormalizer::default(), &rename);
+        assert_eq!(preview.affected_stocks, 1);
+        assert_eq!(preview.changes[0].after, "概念:人工智能;行业:芯片");
+
+        let moved = TagEdit::Move {
+            tag: tag_ref("行业", "芯片"),
+            new_category: "概念".to_string(),
+        };
+        assert_eq!(changed_codes(moved), vec!["600001"]);
+    }
+
+    #[test]
+    fn no_op_edits_are_dropped() {
+        let rename = TagEdit::Rename {
+            tag: tag_ref("概念", "AI"),
+            new_name: " AI ".to_string(),
+        };
+        assert!(changed_codes(rename).is_empty());
+
+        let moved = TagEdit::Move {
+            tag: tag_ref("概念", "AI"),
+            new_category: "概念".to_string(),
+        };
+        assert!(changed_codes(moved).is_empty());
+
+        let set_detail = TagEdit::SetDetail {
+            tag: tag_ref("概念", "AI"),
+            new_detail: Some("  ".to_string()),
+        };
+        assert!(changed_codes(set_detail).is_empty());
+
+        let mut source = tag_ref("概念", "机器人");
+        source.any_detail = false;
+        let merge = TagEdit::Merge {
+            target: source.clone(),
+            source,
+        };
+        assert!(changed_codes(merge).is_empty());
+    }
+}
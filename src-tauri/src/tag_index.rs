@@ -0,0 +1,366 @@
+use crate::pinyin_index::PinyinForms;
+use crate::stock_data::*;
+use crate::tag_processor::parse_normalized_tags;
+use crate::tag_synonyms::TagSynonyms;
+use crate::text_normalize::TagNormalizer;
+use rayon::prelude::*;
+use std::collections::{BTreeMap, HashMap};
+
+/// 索引中的单个标签（分类下按 name:detail 聚合，name 为同义词词典中的规范名称）
+#[derive(Debug, Clone)]
+pub struct IndexedTag {
+    pub name: String,
+    pub detail: Option<String>,
+    /// 携带该标签的股票下标（对应 stock_data 中的位置，升序）
+    pub stock_ids: Vec<usize>,
+    /// 合并到该标签的原始名称 → 股票下标（升序）
+    pub variants: BTreeMap<String, Vec<usize>>,
+    /// 标签名称和补充说明的拼音
+    pub name_pinyin: PinyinForms,
+    pub detail_pinyin: Option<PinyinForms>,
+}
+
+/// 标签倒排索引 - 在 set_stock_data 时构建一次，所有读取命令直接查询
+#[derive(Debug, Default)]
+pub struct TagIndex {
+    /// 每只股票解析后的标签（已换成规范名称并去重），下标与 stock_data 一一对应
+    pub stock_tags: Vec<HashMap<String, Vec<TagItem>>>,
+    /// 每只股票名称的拼音，下标与 stock_data 一一对应
+    pub stock_pinyin: Vec<PinyinForms>,
+    /// 分类 → 标签键 → 标签条目
+    pub categories: HashMap<String, HashMap<String, IndexedTag>>,
+    /// 分类名称的拼音
+    pub category_pinyin: HashMap<String, PinyinForms>,
+    /// 股票代码 → stock_data 中的下标
+    pub stock_positions: HashMap<String, usize>,
+    /// 带有自定义标签的股票数量
+    pub stocks_with_tags: u32,
+    /// 聚合标签时使用的同义词词典
+    pub synonyms: TagSynonyms,
+    /// 解析 custom_tags 之前使用的规范化配置
+    pub normalizer: TagNormalizer,
+    /// 数据集版本，数据或聚合规则每次变化后递增，用于判断分页游标是否过期
+    pub version: u64,
+}
+
+impl TagIndex {
+    /// 使用当前的同义词词典和规范化配置重新构建索引（版本递增）
+    pub fn rebuild(&mut self, stock_data: &[StockCompanyInfo]) {
+        let version = self.version;
+        *self = Self::build_with_options(
+            stock_data,
+            std::mem::take(&mut self.synonyms),
+            std::mem::take(&mut self.normalizer),
+        );
+        self.version = version;
+        self.touch();
+    }
+
+    /// 标记数据集已变化
+    pub fn touch(&mut self) {
+        self.version = self.version.wrapping_add(1);
+    }
+
+    /// 根据股票数据构建索引，别名按词典合并到规范名称
+    pub fn build_with_synonyms(stock_data: &[StockCompanyInfo], synonyms: TagSynonyms) -> Self {
+        Self::build_with_options(stock_data, synonyms, TagNormalizer::default())
+    }
+
+    /// 根据股票数据构建索引，custom_tags 先规范化再解析，别名按词典合并到规范名称
+    pub fn build_with_options(
+        stock_data: &[StockCompanyInfo],
+        synonyms: TagSynonyms,
+        normalizer: TagNormalizer,
+    ) -> Self {
+        // 并行解析每只股票的标签，并计算股票名称的拼音
+        let parsed_stocks: Vec<(HashMap<String, Vec<TagItem>>, PinyinForms)> = stock_data
+            .par_iter()
+            .map(|stock| {
+                (
+                    parse_normalized_tags(&stock.custom_tags, &normalizer).into_map(),
+                    PinyinForms::of(&stock.stock_name),
+                )
+            })
+            .collect();
+
+        let mut index = Self {
+            stock_tags: Vec::with_capacity(stock_data.len()),
+            stock_pinyin: Vec::with_capacity(stock_data.len()),
+            synonyms,
+            normalizer,
+            ..Self::default()
+        };
+        for (stock_id, (stock, (parsed, pinyin))) in
+            stock_data.iter().zip(parsed_stocks).enumerate()
+        {
+            index.add_entries(stock_id, stock, parsed, pinyin);
+        }
+        index
+    }
+
+    /// 获取指定分类下的所有标签
+    pub fn category(&self, category_name: &str) -> Option<&HashMap<String, IndexedTag>> {
+        self.categories.get(category_name)
+    }
+
+    /// 查找指定标签
+    pub fn tag(
+        &self,
+        category_name: &str,
+        tag_name: &str,
+        tag_detail: Option<&str>,
+    ) -> Option<&IndexedTag> {
+        self.category(category_name)?
+            .get(&tag_key(tag_name, tag_detail))
+    }
+
+    /// 按股票代码查找下标
+    pub fn position(&self, stock_code: &str) -> Option<usize> {
+        self.stock_positions.get(stock_code).copied()
+    }
+
+    /// 追加一只股票（与 stock_data.push 同步调用）
+    pub fn push_stock(&mut self, stock: &StockCompanyInfo) {
+        let stock_id = self.stock_tags.len();
+        self.add_entries(
+            stock_id,
+            stock,
+            parse_normalized_tags(&stock.custom_tags, &self.normalizer).into_map(),
+            PinyinForms::of(&stock.stock_name),
+        );
+    }
+
+    /// 更新一只股票的标签（在 stock_data[stock_id] 替换之后调用）
+    pub fn update_stock(
+        &mut self,
+        stock_id: usize,
+        old: &StockCompanyInfo,
+        new: &StockCompanyInfo,
+    ) {
+        self.remove_entries(stock_id, old);
+        if old.stock_code != new.stock_code {
+            self.stock_positions.remove(&old.stock_code);
+            self.stock_positions
+                .insert(new.stock_code.clone(), stock_id);
+        }
+        if old.stock_name != new.stock_name {
+            self.stock_pinyin[stock_id] = PinyinForms::of(&new.stock_name);
+        }
+        if !new.custom_tags.is_empty() {
+            self.stocks_with_tags += 1;
+        }
+        let parsed = parse_normalized_tags(&new.custom_tags, &self.normalizer).into_map();
+        self.insert_tag_entries(stock_id, parsed);
+    }
+
+    /// 移除若干只股票（在 stock_data 删除之前调用，stock_ids 为升序下标）
+    /// 其余股票保持原有顺序，下标整体前移
+    pub fn remove_stocks(&mut self, stock_data: &[StockCompanyInfo], stock_ids: &[usize]) {
+        if stock_ids.is_empty() {
+            return;
+        }
+        for &stock_id in stock_ids {
+            self.remove_entries(stock_id, &stock_data[stock_id]);
+            self.stock_positions
+                .remove(&stock_data[stock_id].stock_code);
+        }
+
+        // 下标映射是单调的，stock_ids 调整后仍保持升序
+        let shift = |id: usize| id - stock_ids.partition_point(|&removed| removed < id);
+        for tag in self
+            .categories
+            .values_mut()
+            .flat_map(|tags| tags.values_mut())
+        {
+            for id in tag
+                .stock_ids
+                .iter_mut()
+                .chain(tag.variants.values_mut().flatten())
+            {
+                *id = shift(*id);
+            }
+        }
+        for position in self.stock_positions.values_mut() {
+            *position = shift(*position);
+        }
+
+        remove_positions(&mut self.stock_tags, stock_ids);
+        remove_positions(&mut self.stock_pinyin, stock_ids);
+    }
+
+    fn add_entries(
+        &mut self,
+        stock_id: usize,
+        stock: &StockCompanyInfo,
+        parsed: HashMap<String, Vec<TagItem>>,
+        pinyin: PinyinForms,
+    ) {
+        if !stock.custom_tags.is_empty() {
+            self.stocks_with_tags += 1;
+        }
+        self.stock_positions
+            .insert(stock.stock_code.clone(), stock_id);
+        if stock_id == self.stock_tags.len() {
+            self.stock_tags.push(HashMap::new());
+            self.stock_pinyin.push(pinyin);
+        } else {
+            self.stock_pinyin[stock_id] = pinyin;
+        }
+        self.insert_tag_entries(stock_id, parsed);
+    }
+
+    /// 将股票的原始标签换成规范名称后写入索引，同一分类下合并后重复的标签只记一次
+    fn insert_tag_entries(&mut self, stock_id: usize, parsed: HashMap<String, Vec<TagItem>>) {
+        let mut canonical_tags: HashMap<String, Vec<TagItem>> =
+            HashMap::with_capacity(parsed.len());
+        for (category, items) in parsed {
+            let tag_map = self.categories.entry(category.clone()).or_default();
+            self.category_pinyin
+                .entry(category.clone())
+                .or_insert_with(|| PinyinForms::of(&category));
+            let canonical_items = canonical_tags.entry(category.clone()).or_default();
+            for item in items {
+                let name = self
+                    .synonyms
+                    .canonical(&category, &item.name)
+                    .unwrap_or(&item.name)
+                    .to_string();
+                let tag = tag_map
+                    .entry(tag_key(&name, item.detail.as_deref()))
+                    .or_insert_with(|| IndexedTag {
+                        name: name.clone(),
+                        detail: item.detail.clone(),
+                        stock_ids: Vec::new(),
+                        variants: BTreeMap::new(),
+                        name_pinyin: PinyinForms::of(&name),
+                        detail_pinyin: item.detail.as_deref().map(PinyinForms::of),
+                    });
+                insert_stock_id(&mut tag.stock_ids, stock_id);
+                insert_stock_id(tag.variants.entry(item.name).or_default(), stock_id);
+
+                let canonical = TagItem {
+                    name,
+                    detail: item.detail,
+                };
+                if !canonical_items.contains(&canonical) {
+                    canonical_items.push(canonical);
+                }
+            }
+        }
+        self.stock_tags[stock_id] = canonical_tags;
+    }
+
+    fn remove_entries(&mut self, stock_id: usize, stock: &StockCompanyInfo) {
+        if !stock.custom_tags.is_empty() {
+            self.stocks_with_tags = self.stocks_with_tags.saturating_sub(1);
+        }
+
+        for (category, items) in &self.stock_tags[stock_id] {
+            let Some(tag_map) = self.categories.get_mut(category) else {
+                continue;
+            };
+            for item in items {
+                let key = tag_key(&item.name, item.detail.as_deref());
+                if let Some(tag) = tag_map.get_mut(&key) {
+                    tag.stock_ids.retain(|&id| id != stock_id);
+                    tag.variants.retain(|_, stock_ids| {
+                        stock_ids.retain(|&id| id != stock_id);
+                        !stock_ids.is_empty()
+                    });
+                    if tag.stock_ids.is_empty() {
+                        tag_map.remove(&key);
+                    }
+                }
+            }
+            if tag_map.is_empty() {
+                self.categories.remove(category);
+                self.category_pinyin.remove(category);
+            }
+        }
+    }
+}
+
+/// 在升序的股票下标中插入一个下标（已存在时跳过）
+fn insert_stock_id(stock_ids: &mut Vec<usize>, stock_id: usize) {
+    if let Err(position) = stock_ids.binary_search(&stock_id) {
+        stock_ids.insert(position, stock_id);
+    }
+}
+
+/// 按升序下标批量删除元素，其余元素保持原有顺序
+pub fn remove_positions<T>(items: &mut Vec<T>, positions: &[usize]) {
+    let mut current = 0;
+    items.retain(|_| {
+        let keep = positions.binary_search(&current).is_err();
+        current += 1;
+        keep
+    });
+}
+
+/// 标签在分类内的唯一键，与原 get_category_data 的聚合规则一致
+pub fn tag_key(name: &str, detail: Option<&str>) -> String {
+    format!("{}:{}", name, detail.unwrap_or(""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 分类、标签键、股票下标和原始名称，排序后用于比较两个索引
+    fn entries(index: &TagIndex) -> Vec<(String, String, Vec<usize>, Vec<String>)> {
+        let mut entries: Vec<_> = index
+            .categories
+            .iter()
+            .flat_map(|(category, tags)| {
+                tags.iter().map(move |(key, tag)| {
+                    (
+                        category.clone(),
+                        key.clone(),
+                        tag.stock_ids.clone(),
+                        tag.variants.keys().cloned().collect(),
+                    )
+                })
+            })
+            .collect();
+        entries.sort();
+        entries
+    }
+
+    fn assert_same_as_rebuild(index: &TagIndex, stock_data: &[StockCompanyInfo]) {
+        let rebuilt = TagIndex::build_with_synonyms(stock_data, Default::default());
+        assert_eq!(entries(index), entries(&rebuilt));
+        assert_eq!(index.stock_tags, rebuilt.stock_tags);
+        assert_eq!(index.stock_positions, rebuilt.stock_positions);
+        assert_eq!(index.stocks_with_tags, rebuilt.stocks_with_tags);
+        assert_eq!(index.stock_pinyin.len(), stock_data.len());
+    }
+
+    #[test]
+    fn incremental_updates_match_rebuild() {
+        let mut stock_data = vec![
+            test_stock("600001", "行业:银行;概念:AI"),
+            test_stock("600002", "行业:银行"),
+            test_stock("600003", "概念:芯片{存储}"),
+            test_stock("600004", ""),
+        ];
+        let mut index = TagIndex::build_with_synonyms(&stock_data, Default::default());
+
+        let pushed = test_stock("600005", "概念:AI;地区:上海");
+        index.push_stock(&pushed);
+        stock_data.push(pushed);
+        assert_same_as_rebuild(&index, &stock_data);
+
+        let updated = test_stock("600002", "行业:保险;概念:AI");
+        index.update_stock(1, &stock_data[1], &updated);
+        stock_data[1] = updated;
+        let cleared = test_stock("600003", "");
+        index.update_stock(2, &stock_data[2], &cleared);
+        stock_data[2] = cleared;
+        assert_same_as_rebuild(&index, &stock_data);
+
+        index.remove_stocks(&stock_data, &[0, 3]);
+        remove_positions(&mut stock_data, &[0, 3]);
+        assert_same_as_rebuild(&index, &stock_data);
+        assert_eq!(index.position("600005"), Some(2));
+    }
+}
@@ -0,0 +1,636 @@
+use crate::stock_data::*;
+use crate::stock_store::{now_millis, write_file_atomic};
+use crate::tag_editor::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+
+/// 默认保留的历史记录条数
+pub const DEFAULT_JOURNAL_CAPACITY: usize = 200;
+
+/// 默认的历史记录总大小上限（按 custom_tags 字节数估算）
+pub const DEFAULT_JOURNAL_MAX_BYTES: usize = 32 * 1024 * 1024;
+
+/// 日志文件名（位于应用数据目录）
+const JOURNAL_FILE_NAME: &str = "tag-journal.json";
+
+/// 修改 stock_data 的操作类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JournalOperation {
+    /// set_stock_data 整体替换数据
+    SetStockData,
+    /// 从快照恢复数据
+    RestoreSnapshot,
+    /// upsert_stocks 增量更新数据
+    UpsertStocks,
+    /// remove_stocks 删除数据
+    RemoveStocks,
+    Rename,
+    Merge,
+    Move,
+    SetDetail,
+    Delete,
+    /// 将规范化后的 custom_tags 写回数据集
+    Normalize,
+}
+
+impl From<&TagEdit> for JournalOperation {
+    fn from(edit: &TagEdit) -> Self {
+        match edit {
+            TagEdit::Rename { .. } => JournalOperation::Rename,
+            TagEdit::Merge { .. } => JournalOperation::Merge,
+            TagEdit::Move { .. } => JournalOperation::Move,
+            TagEdit::SetDetail { .. } => JournalOperation::SetDetail,
+            TagEdit::Delete { .. } => JournalOperation::Delete,
+        }
+    }
+}
+
+/// 一次操作的记录，保存受影响股票 custom_tags 的前后值，以及新增或删除的完整记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub id: u64,
+    pub operation: JournalOperation,
+    /// 毫秒时间戳
+    pub timestamp: u64,
+    pub changes: Vec<StockTagChange>,
+    /// 操作新增的股票，撤销时删除
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub inserted: Vec<StockCompanyInfo>,
+    /// 操作删除的股票，撤销时恢复
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub removed: Vec<StockCompanyInfo>,
+}
+
+impl JournalEntry {
+    /// 估算记录占用的字节数
+    fn size(&self) -> usize {
+        let changes: usize = self
+            .changes
+            .iter()
+            .map(|change| {
+                change.stock_code.len()
+                    + change.stock_name.len()
+                    + change.before.len()
+                    + change.after.len()
+            })
+            .sum();
+        let stocks: usize = self
+            .inserted
+            .iter()
+            .chain(&self.removed)
+            .map(stock_size)
+            .sum();
+        changes + stocks
+    }
+
+    /// 受影响的股票代码
+    fn stock_codes(&self) -> Vec<String> {
+        self.changes
+            .iter()
+            .map(|change| &change.stock_code)
+            .chain(self.inserted.iter().map(|stock| &stock.stock_code))
+            .chain(self.removed.iter().map(|stock| &stock.stock_code))
+            .cloned()
+            .collect()
+    }
+}
+
+/// 估算单条股票记录占用的字节数
+fn stock_size(stock: &StockCompanyInfo) -> usize {
+    bincode::serialized_size(stock).map_or(0, |size| size as usize)
+}
+
+/// 历史列表中的条目（不含 custom_tags 内容）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryItem {
+    pub id: u64,
+    pub operation: JournalOperation,
+    pub timestamp: u64,
+    pub stock_codes: Vec<String>,
+    /// 是否已被撤销（位于重做栈中）
+    pub undone: bool,
+}
+
+/// 撤销/重做的执行结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalApplyResult {
+    pub id: u64,
+    pub operation: JournalOperation,
+    pub applied_stocks: u32,
+    /// custom_tags 已被其他操作改动而跳过的股票
+    pub conflicts: Vec<String>,
+}
+
+/// 撤销/重做日志
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagJournal {
+    undo_stack: VecDeque<JournalEntry>,
+    redo_stack: Vec<JournalEntry>,
+    next_id: u64,
+    capacity: usize,
+    /// 撤销栈与重做栈的总大小上限
+    #[serde(default = "default_max_bytes")]
+    max_bytes: usize,
+    /// 持久化文件路径，未设置时仅保存在内存中
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+fn default_max_bytes() -> usize {
+    DEFAULT_JOURNAL_MAX_BYTES
+}
+
+impl Default for TagJournal {
+    fn default() -> Self {
+        Self::new(DEFAULT_JOURNAL_CAPACITY, DEFAULT_JOURNAL_MAX_BYTES)
+    }
+}
+
+impl TagJournal {
+    pub fn new(capacity: usize, max_bytes: usize) -> Self {
+        Self {
+            undo_stack: VecDeque::new(),
+            redo_stack: Vec::new(),
+            next_id: 1,
+            capacity: capacity.max(1),
+            max_bytes,
+            path: None,
+        }
+    }
+
+    /// 从数据目录加载日志，文件不存在时返回空日志
+    pub fn load(data_dir: &Path) -> Result<Self, String> {
+        let path = data_dir.join(JOURNAL_FILE_NAME);
+        let mut journal = if path.exists() {
+            let json = std::fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to read tag journal: {}", e))?;
+            serde_json::from_str(&json)
+                .map_err(|e| format!("Failed to parse tag journal: {}", e))?
+        } else {
+            Self::default()
+        };
+        journal.path = Some(path);
+        journal.evict();
+        Ok(journal)
+    }
+
+    /// 写入临时文件后再替换持久化文件，避免写入中断时损坏日志
+    fn persist(&self) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let json = serde_json::to_string(self)
+            .map_err(|e| format!("Failed to serialize tag journal: {}", e))?;
+        write_file_atomic(path, json).map_err(|e| format!("Failed to write tag journal: {}", e))
+    }
+
+    /// 按条数和总大小淘汰最早的记录，最近一次操作总是保留
+    fn evict(&mut self) {
+        let mut total_bytes: usize = self
+            .undo_stack
+            .iter()
+            .chain(&self.redo_stack)
+            .map(JournalEntry::size)
+            .sum();
+        while self.undo_stack.len() > 1
+            && (self.undo_stack.len() > self.capacity || total_bytes > self.max_bytes)
+        {
+            if let Some(entry) = self.undo_stack.pop_front() {
+                total_bytes -= entry.size();
+            }
+        }
+    }
+
+    /// 记录一次操作，清空重做栈并按条数和总大小淘汰最早的记录
+    pub fn record(
+        &mut self,
+        operation: JournalOperation,
+        changes: Vec<StockTagChange>,
+    ) -> Result<(), String> {
+        self.record_stocks(operation, changes, Vec::new(), Vec::new())
+    }
+
+    /// 记录一次包含新增或删除股票的操作
+    pub fn record_stocks(
+        &mut self,
+        operation: JournalOperation,
+        changes: Vec<StockTagChange>,
+        inserted: Vec<StockCompanyInfo>,
+        removed: Vec<StockCompanyInfo>,
+    ) -> Result<(), String> {
+        if changes.is_empty() && inserted.is_empty() && removed.is_empty() {
+            return Ok(());
+        }
+
+        let entry = JournalEntry {
+            id: self.next_id,
+            operation,
+            timestamp: now_millis(),
+            changes,
+            inserted,
+            removed,
+        };
+        self.commit(|journal| {
+            journal.undo_stack.push_back(entry);
+            journal.next_id += 1;
+            journal.redo_stack.clear();
+            journal.evict();
+        })
+    }
+
+    /// 撤销最近一次操作
+    /// 日志先写入成功再修改股票数据，写入失败时两者都保持不变
+    pub fn undo(
+        &mut self,
+        stock_data: &mut Vec<StockCompanyInfo>,
+    ) -> Result<JournalApplyResult, String> {
+        let entry = self
+            .undo_stack
+            .back()
+            .cloned()
+            .ok_or_else(|| "Nothing to undo".to_string())?;
+        self.commit(|journal| {
+            let entry = journal.undo_stack.pop_back();
+            journal.redo_stack.extend(entry);
+        })?;
+        Ok(apply_entry(stock_data, &entry, true))
+    }
+
+    /// 重做最近一次撤销的操作
+    /// 日志先写入成功再修改股票数据，写入失败时两者都保持不变
+    pub fn redo(
+        &mut self,
+        stock_data: &mut Vec<StockCompanyInfo>,
+    ) -> Result<JournalApplyResult, String> {
+        let entry = self
+            .redo_stack
+            .last()
+            .cloned()
+            .ok_or_else(|| "Nothing to redo".to_string())?;
+        self.commit(|journal| {
+            let entry = journal.redo_stack.pop();
+            journal.undo_stack.extend(entry);
+        })?;
+        Ok(apply_entry(stock_data, &entry, false))
+    }
+
+    /// 修改日志并写入持久化文件，写入失败时恢复修改前的状态
+    fn commit(&mut self, update: impl FnOnce(&mut Self)) -> Result<(), String> {
+        let previous = self.path.is_some().then(|| self.clone());
+        update(self);
+        let result = self.persist();
+        if let (Err(_), Some(previous)) = (&result, previous) {
+            *self = previous;
+        }
+        result
+    }
+
+    /// 历史记录，最新的在前
+    pub fn history(&self) -> Vec<HistoryItem> {
+        let to_item = |entry: &JournalEntry, undone: bool| HistoryItem {
+            id: entry.id,
+            operation: entry.operation,
+            timestamp: entry.timestamp,
+            stock_codes: entry.stock_codes(),
+            undone,
+        };
+
+        self.redo_stack
+            .iter()
+            .map(|entry| to_item(entry, true))
+            .chain(
+                self.undo_stack
+                    .iter()
+                    .rev()
+                    .map(|entry| to_item(entry, false)),
+            )
+            .collect()
+    }
+}
+
+/// 将记录中的值写回股票数据（undo 为 true 时反向执行）；当前值与预期不一致的股票视为冲突并跳过
+/// 恢复的股票追加到数据末尾
+fn apply_entry(
+    stock_data: &mut Vec<StockCompanyInfo>,
+    entry: &JournalEntry,
+    undo: bool,
+) -> JournalApplyResult {
+    let (to_add, to_drop) = if undo {
+        (&entry.removed, &entry.inserted)
+    } else {
+        (&entry.inserted, &entry.removed)
+    };
+    let changes_by_code: HashMap<&str, &StockTagChange> = entry
+        .changes
+        .iter()
+        .map(|change| (change.stock_code.as_str(), change))
+        .collect();
+
+    let mut applied_stocks = 0;
+    let mut conflicts = Vec::new();
+    for stock in stock_data.iter_mut() {
+        if let Some(change) = changes_by_code.get(stock.stock_code.as_str()) {
+            let (expected, target) = if undo {
+                (&change.after, &change.before)
+            } else {
+                (&change.before, &change.after)
+            };
+            if &stock.custom_tags == expected {
+                stock.custom_tags = target.clone();
+                applied_stocks += 1;
+            } else {
+                conflicts.push(stock.stock_code.clone());
+            }
+        }
+    }
+
+    // 只删除与记录完全一致的股票，之后被修改过的视为冲突
+    let drop_by_code: HashMap<&str, &StockCompanyInfo> = to_drop
+        .iter()
+        .map(|stock| (stock.stock_code.as_str(), stock))
+        .collect();
+    stock_data.retain(|stock| match drop_by_code.get(stock.stock_code.as_str()) {
+        Some(&expected) if expected == stock => {
+            applied_stocks += 1;
+            false
+        }
+        Some(_) => {
+            conflicts.push(stock.stock_code.clone());
+            true
+        }
+        None => true,
+    });
+
+    // 股票代码已存在时不再恢复
+    let existing: HashSet<String> = stock_data
+        .iter()
+        .map(|stock| stock.stock_code.clone())
+        .collect();
+    for stock in to_add {
+        if existing.contains(&stock.stock_code) {
+            conflicts.push(stock.stock_code.clone());
+        } else {
+            stock_data.push(stock.clone());
+            applied_stocks += 1;
+        }
+    }
+
+    JournalApplyResult {
+        id: entry.id,
+        operation: entry.operation,
+        applied_stocks,
+        conflicts,
+    }
+}
+
+/// 整体替换数据集时需要记录的变化
+#[derive(Debug, Clone, Default)]
+pub struct JournalChanges {
+    /// 新旧数据中同一 stock_code 的 custom_tags 变化
+    pub changes: Vec<StockTagChange>,
+    /// 只在新数据中出现的股票
+    pub inserted: Vec<StockCompanyInfo>,
+    /// 只在旧数据中出现的股票
+    pub removed: Vec<StockCompanyInfo>,
+}
+
+/// 比较新旧数据集：custom_tags 的变化，以及按 stock_code 新增和删除的股票
+pub fn diff_journal_changes(
+    old_data: &[StockCompanyInfo],
+    new_data: &[StockCompanyInfo],
+) -> JournalChanges {
+    let old_by_code: HashMap<&str, &StockCompanyInfo> = old_data
+        .iter()
+        .map(|stock| (stock.stock_code.as_str(), stock))
+        .collect();
+    let new_codes: HashSet<&str> = new_data
+        .iter()
+        .map(|stock| stock.stock_code.as_str())
+        .collect();
+
+    let mut changes = JournalChanges::default();
+    for stock in new_data {
+        match old_by_code.get(stock.stock_code.as_str()) {
+            Some(old) if old.custom_tags != stock.custom_tags => {
+                changes.changes.push(StockTagChange {
+                    stock_code: stock.stock_code.clone(),
+                    stock_name: stock.stock_name.clone(),
+                    before: old.custom_tags.clone(),
+                    after: stock.custom_tags.clone(),
+                });
+            }
+            Some(_) => {}
+            None => changes.inserted.push(stock.clone()),
+        }
+    }
+    changes.removed = old_data
+        .iter()
+        .filter(|stock| !new_codes.contains(stock.stock_code.as_str()))
+        .cloned()
+        .collect();
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(code: &str, before: &str, after: &str) -> StockTagChange {
+        StockTagChange {
+            stock_code: code.to_string(),
+            stock_name: String::new(),
+            before: before.to_string(),
+            after: after.to_string(),
+        }
+    }
+
+    #[test]
+    fn undo_then_redo_restores_both_states() {
+        let mut stocks = vec![
+            test_stock("600001", "概念:AI"),
+            test_stock("600002", "概念:机器人"),
+        ];
+        let mut journal = TagJournal::default();
+        journal
+            .record(
+                JournalOperation::Rename,
+                vec![change("600001", "概念:AI", "概念:人工智能")],
+            )
+            .unwrap();
+        stocks[0].custom_tags = "概念:人工智能".to_string();
+
+        let undone = journal.undo(&mut stocks).unwrap();
+        assert_eq!(undone.applied_stocks, 1);
+        assert_eq!(stocks[0].custom_tags, "概念:AI");
+        assert!(journal.history()[0].undone);
+
+        let redone = journal.redo(&mut stocks).unwrap();
+        assert_eq!(redone.id, undone.id);
+        assert_eq!(stocks[0].custom_tags, "概念:人工智能");
+        assert_eq!(stocks[1].custom_tags, "概念:机器人");
+        assert!(journal.redo(&mut stocks).is_err());
+    }
+
+    #[test]
+    fn undo_and_redo_restore_removed_and_inserted_stocks() {
+        let mut stocks = vec![test_stock("600001", "概念:AI"), test_stock("600002", "")];
+        let mut journal = TagJournal::default();
+        let removed = stocks.remove(0);
+        stocks.push(test_stock("600003", "行业:银行"));
+        journal
+            .record_stocks(
+                JournalOperation::UpsertStocks,
+                Vec::new(),
+                vec![test_stock("600003", "行业:银行")],
+                vec![removed],
+            )
+            .unwrap();
+        assert_eq!(journal.history()[0].stock_codes, vec!["600003", "600001"]);
+
+        let undone = journal.undo(&mut stocks).unwrap();
+        assert_eq!(undone.applied_stocks, 2);
+        let codes: Vec<&str> = stocks.iter().map(|s| s.stock_code.as_str()).collect();
+        assert_eq!(codes, vec!["600002", "600001"]);
+        assert_eq!(stocks[1].custom_tags, "概念:AI");
+
+        let redone = journal.redo(&mut stocks).unwrap();
+        assert_eq!(redone.applied_stocks, 2);
+        let codes: Vec<&str> = stocks.iter().map(|s| s.stock_code.as_str()).collect();
+        assert_eq!(codes, vec!["600002", "600003"]);
+
+        // 新增的股票之后被修改过，撤销时不删除
+        stocks[1].custom_tags = "行业:保险".to_string();
+        let undone = journal.undo(&mut stocks).unwrap();
+        assert_eq!(undone.conflicts, vec!["600003"]);
+        assert_eq!(stocks.len(), 3);
+    }
+
+    #[test]
+    fn replacing_a_dataset_can_be_undone() {
+        let old = vec![
+            test_stock("600001", "概念:AI"),
+            test_stock("600002", "行业:银行"),
+        ];
+        let new = vec![
+            test_stock("600001", "概念:人工智能"),
+            test_stock("600003", "行业:保险"),
+        ];
+        let diff = diff_journal_changes(&old, &new);
+        assert_eq!(diff.changes.len(), 1);
+        assert_eq!(diff.changes[0].before, "概念:AI");
+        assert_eq!(diff.changes[0].after, "概念:人工智能");
+        assert_eq!(diff.inserted, vec![test_stock("600003", "行业:保险")]);
+        assert_eq!(diff.removed, vec![test_stock("600002", "行业:银行")]);
+
+        let mut journal = TagJournal::default();
+        journal
+            .record_stocks(
+                JournalOperation::SetStockData,
+                diff.changes,
+                diff.inserted,
+                diff.removed,
+            )
+            .unwrap();
+        let mut stocks = new.clone();
+        let undone = journal.undo(&mut stocks).unwrap();
+        assert_eq!(undone.applied_stocks, 3);
+        assert_eq!(stocks, old);
+
+        journal.redo(&mut stocks).unwrap();
+        assert_eq!(stocks, new);
+    }
+
+    #[test]
+    fn undo_skips_conflicting_stocks() {
+        let mut stocks = vec![test_stock("600001", "概念:其他")];
+        let mut journal = TagJournal::default();
+        journal
+            .record(
+                JournalOperation::Delete,
+                vec![change("600001", "概念:AI", "")],
+            )
+            .unwrap();
+
+        let result = journal.undo(&mut stocks).unwrap();
+        assert_eq!(result.applied_stocks, 0);
+        assert_eq!(result.conflicts, vec!["600001"]);
+        assert_eq!(stocks[0].custom_tags, "概念:其他");
+    }
+
+    #[test]
+    fn evicts_by_count_and_size() {
+        let mut journal = TagJournal::new(2, DEFAULT_JOURNAL_MAX_BYTES);
+        for i in 0..3 {
+            journal
+                .record(
+                    JournalOperation::Rename,
+                    vec![change("600001", &i.to_string(), &(i + 1).to_string())],
+                )
+                .unwrap();
+        }
+        let ids: Vec<u64> = journal.history().iter().map(|item| item.id).collect();
+        assert_eq!(ids, vec![3, 2]);
+
+        let large = "概".repeat(100);
+        let mut journal = TagJournal::new(DEFAULT_JOURNAL_CAPACITY, 500);
+        for _ in 0..3 {
+            journal
+                .record(
+                    JournalOperation::Normalize,
+                    vec![change("600001", &large, &large)],
+                )
+                .unwrap();
+        }
+        assert_eq!(journal.history().len(), 1);
+    }
+
+    #[test]
+    fn failed_writes_leave_the_journal_unchanged() {
+        let mut stocks = vec![test_stock("600001", "概念:人工智能")];
+        let mut journal = TagJournal::default();
+        journal
+            .record(
+                JournalOperation::Rename,
+                vec![change("600001", "概念:AI", "概念:人工智能")],
+            )
+            .unwrap();
+        journal.path = Some(
+            std::env::temp_dir()
+                .join(format!("tag-journal-missing-{}", std::process::id()))
+                .join(JOURNAL_FILE_NAME),
+        );
+
+        assert!(journal
+            .record(
+                JournalOperation::Delete,
+                vec![change("600001", "概念:人工智能", "")]
+            )
+            .is_err());
+        assert!(journal.undo(&mut stocks).is_err());
+        assert_eq!(stocks[0].custom_tags, "概念:人工智能");
+        let history = journal.history();
+        assert_eq!(history.len(), 1);
+        assert!(!history[0].undone);
+    }
+
+    #[test]
+    fn persists_and_reloads() {
+        let dir = std::env::temp_dir().join(format!("tag-journal-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut journal = TagJournal::load(&dir).unwrap();
+        journal
+            .record(
+                JournalOperation::Move,
+                vec![change("600001", "行业:芯片", "概念:芯片")],
+            )
+            .unwrap();
+
+        let reloaded = TagJournal::load(&dir).unwrap();
+        assert_eq!(reloaded.history().len(), 1);
+        assert!(!dir.join(JOURNAL_FILE_NAME).with_extension("tmp").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,388 +1,1175 @@
+use crate::collation::*;
+use crate::command_error::*;
+use crate::fuzzy_match::*;
+use crate::page_cursor::*;
+use crate::pinyin_index::*;
 use crate::stock_data::*;
+use crate::stock_merge::parse_timestamp;
+use crate::tag_blacklist::*;
+use crate::tag_index::*;
+use crate::tag_taxonomy::*;
+use crate::tag_validation::*;
+use crate::text_normalize::TagNormalizer;
 use dashmap::DashMap;
 use once_cell::sync::Lazy;
-use rayon::prelude::*;
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::RwLock;
 
 // 全局缓存，避免重复计算
-static TAG_VALIDATION_CACHE: Lazy<DashMap<String, String>> = Lazy::new(|| DashMap::new());
+static TAG_VALIDATION_CACHE: Lazy<DashMap<String, TagValidationResult>> = Lazy::new(DashMap::new);
 
-/// 标签验证状态
-#[derive(Debug, Clone)]
-pub enum ValidationStatus {
-    Valid,
-    Warning,
-    Error,
-    Special,
+/// 摘要模式下每个标签默认附带的示例股票数量
+const DEFAULT_SAMPLE_SIZE: u32 = 3;
+
+// 当前生效的验证规则
+static VALIDATION_RULES: Lazy<RwLock<ValidationRules>> =
+    Lazy::new(|| RwLock::new(ValidationRules::builtin()));
+
+/// custom_tags 中需要在前面加 "\" 转义的特殊字符
+const TAG_SPECIAL_CHARS: [char; 5] = ['\\', ';', ':', '{', '}'];
+
+/// 解析自定义标签字符串
+/// 对应前端的 parseCustomTags 函数
+pub fn parse_custom_tags(tags: &str) -> HashMap<String, Vec<TagItem>> {
+    parse_custom_tags_ordered(tags).into_map()
 }
 
-impl ValidationStatus {
-    pub fn as_str(&self) -> &'static str {
-        match self {
-            ValidationStatus::Valid => "valid",
-            ValidationStatus::Warning => "warning",
-            ValidationStatus::Error => "error",
-            ValidationStatus::Special => "special",
+/// 解析自定义标签字符串，保留分类和标签的原有顺序
+/// 格式为 "分类:标签{补充};..."，特殊字符 \ ; : { } 前加 "\" 表示字面字符
+pub fn parse_custom_tags_ordered(tags: &str) -> ParsedTags {
+    let mut parsed = ParsedTags::default();
+
+    // 按未转义的 ";" 分割每个标签项
+    let mut section_start = 0;
+    let separators = unescaped_chars(tags)
+        .filter(|&(_, ch)| ch == ';')
+        .map(|(i, _)| i)
+        .chain(std::iter::once(tags.len()));
+    for section_end in separators {
+        let section = tags[section_start..section_end].trim();
+        section_start = section_end + 1;
+        if let Some((category, item)) = parse_tag_section(section) {
+            parsed.push(&category, item);
         }
     }
 
-    pub fn weight(&self) -> u8 {
-        match self {
-            ValidationStatus::Error => 4,
-            ValidationStatus::Warning => 3,
-            ValidationStatus::Special => 2,
-            ValidationStatus::Valid => 1,
+    parsed
+}
+
+/// 解析单个 "分类:标签{补充}"，缺少分类或标签内容时返回 None
+fn parse_tag_section(section: &str) -> Option<(String, TagItem)> {
+    let (colon_index, _) = unescaped_chars(section).find(|&(_, ch)| ch == ':')?;
+    if colon_index == 0 {
+        return None;
+    }
+    let category = unescape_tag_text(&section[..colon_index]);
+    let value_with_detail = section[colon_index + 1..].trim();
+
+    // 检查是否有 {补充信息}：以未转义的 "}" 结尾，且标签名称和补充信息都不为空
+    let closing = unescaped_chars(value_with_detail)
+        .last()
+        .filter(|&(_, ch)| ch == '}')
+        .map(|(i, _)| i);
+    let opening = closing.and_then(|closing| {
+        unescaped_chars(value_with_detail)
+            .find(|&(i, ch)| ch == '{' && i > 0)
+            .map(|(i, _)| i)
+            .filter(|&opening| opening + 1 < closing)
+    });
+
+    let item = match (opening, closing) {
+        // 有补充信息：tag{detail}
+        (Some(opening), Some(closing)) => TagItem {
+            name: unescape_tag_text(&value_with_detail[..opening]),
+            detail: Some(unescape_tag_text(&value_with_detail[opening + 1..closing])),
+        },
+        // 没有补充信息：纯tag
+        _ => TagItem {
+            name: unescape_tag_text(value_with_detail),
+            detail: None,
+        },
+    };
+    Some((category, item))
+}
+
+/// 遍历未被转义的字符，返回 (字节位置, 字符)
+/// "\" 后跟特殊字符时视为转义序列整体跳过，后跟其他字符时 "\" 按字面字符处理
+pub fn unescaped_chars(text: &str) -> impl Iterator<Item = (usize, char)> + '_ {
+    let mut chars = text.char_indices().peekable();
+    std::iter::from_fn(move || {
+        while let Some((i, ch)) = chars.next() {
+            if ch == '\\'
+                && chars
+                    .peek()
+                    .is_some_and(|(_, next)| TAG_SPECIAL_CHARS.contains(next))
+            {
+                chars.next();
+                continue;
+            }
+            return Some((i, ch));
+        }
+        None
+    })
+}
+
+/// 去掉转义符，得到字面文本
+pub fn unescape_tag_text(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match chars.peek() {
+            Some(next) if ch == '\\' && TAG_SPECIAL_CHARS.contains(next) => {
+                unescaped.push(*next);
+                chars.next();
+            }
+            _ => unescaped.push(ch),
         }
     }
+    unescaped
 }
 
-/// 解析自定义标签字符串
-/// 对应前端的 parseCustomTags 函数
-pub fn parse_custom_tags(tags: &str) -> HashMap<String, Vec<TagItem>> {
-    if tags.is_empty() {
-        return HashMap::new();
+/// 为字面文本中的特殊字符加上转义符
+fn escape_tag_text(text: &str, special_chars: &[char]) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        if special_chars.contains(&ch) {
+            escaped.push('\\');
+        }
+        escaped.push(ch);
     }
+    escaped
+}
 
-    let mut parsed_data: HashMap<String, Vec<TagItem>> = HashMap::new();
+/// 先按数据集的规范化配置处理（全角、NFKC、繁简），再解析自定义标签
+pub fn parse_normalized_tags(tags: &str, normalizer: &TagNormalizer) -> ParsedTags {
+    parse_custom_tags_ordered(&normalizer.normalize(tags).0)
+}
 
-    // 分割每个标签项
-    let sections: Vec<&str> = tags
-        .split(';')
-        .map(|s| s.trim())
-        .filter(|s| !s.is_empty())
+/// 将解析后的标签序列化为规范的 custom_tags 字符串
+/// 分类和标签保持原有顺序，同名分类合并，重复标签和空标签去除，特殊字符转义
+/// 对任意输入都满足 parse(serialize(parse(s))) == parse(s)
+pub fn serialize_custom_tags(parsed: &ParsedTags) -> String {
+    let canonical = parsed.canonical();
+    let sections: Vec<String> = canonical
+        .categories
+        .iter()
+        .flat_map(|category| {
+            category
+                .items
+                .iter()
+                .map(|item| format_tag_section(&category.name, item))
+        })
         .collect();
+    sections.join(";")
+}
 
-    for section in sections {
-        if let Some(colon_index) = section.find(':') {
-            if colon_index > 0 {
-                let category = section[..colon_index].trim().to_string();
-                let value_with_detail = section[colon_index + 1..].trim();
-
-                // 初始化分类数组
-                let category_tags = parsed_data.entry(category).or_insert_with(Vec::new);
-
-                // 检查是否有 {补充信息}
-                static DETAIL_REGEX: Lazy<Regex> =
-                    Lazy::new(|| Regex::new(r"^(.+?)\{(.+?)\}$").unwrap());
-
-                if let Some(captures) = DETAIL_REGEX.captures(value_with_detail) {
-                    // 有补充信息：tag{detail}
-                    let tag_name = captures.get(1).unwrap().as_str().trim().to_string();
-                    let detail = captures.get(2).unwrap().as_str().trim().to_string();
-                    category_tags.push(TagItem {
-                        name: tag_name,
-                        detail: Some(detail),
-                    });
-                } else if !value_with_detail.is_empty() {
-                    // 没有补充信息：纯tag
-                    category_tags.push(TagItem {
-                        name: value_with_detail.to_string(),
-                        detail: None,
-                    });
-                }
-            }
-        }
+/// 格式化单个标签为 "分类:标签{补充}"，特殊字符加上转义符
+pub fn format_tag_section(category: &str, item: &TagItem) -> String {
+    // 标签名称和补充说明中的 ":" 不会引起歧义，无需转义
+    const VALUE_SPECIAL_CHARS: [char; 4] = ['\\', ';', '{', '}'];
+    let category = escape_tag_text(category, &TAG_SPECIAL_CHARS);
+    let name = escape_tag_text(&item.name, &VALUE_SPECIAL_CHARS);
+    match item.detail.as_deref().filter(|detail| !detail.is_empty()) {
+        Some(detail) => format!(
+            "{}:{}{{{}}}",
+            category,
+            name,
+            escape_tag_text(detail, &VALUE_SPECIAL_CHARS)
+        ),
+        None => format!("{}:{}", category, name),
     }
+}
 
-    parsed_data
+/// 标签的稳定 ID：规范格式的 "分类:标签{补充}"，不随数据重新加载变化，可直接解析回标签引用
+pub fn tag_id(category_name: &str, tag_name: &str, tag_detail: Option<&str>) -> String {
+    format_tag_section(
+        category_name,
+        &TagItem {
+            name: tag_name.to_string(),
+            detail: tag_detail.map(str::to_string),
+        },
+    )
 }
 
-/// 验证标签格式 - 与前端 validateTagStructureFormat 逻辑一致
+/// 解析标签 ID，返回 (分类, 标签)；不是单个标签时返回 None
+pub fn parse_tag_id(tag_id: &str) -> Option<(String, TagItem)> {
+    let parsed = parse_custom_tags_ordered(tag_id);
+    match parsed.categories.as_slice() {
+        [category] if category.items.len() == 1 => {
+            Some((category.name.clone(), category.items[0].clone()))
+        }
+        _ => None,
+    }
+}
+
+/// 验证标签格式 - 与前端 validateTagFormat / validateTagStructureFormat 规则一致
 /// 检查标签是否符合 "分类:内容{补充}" 格式
 pub fn validate_tag_format(tag_name: &str, tag_detail: Option<&str>) -> ValidationStatus {
+    validate_tag_detailed(tag_name, tag_detail).status
+}
+
+/// 验证标签格式并返回原因代码和提示信息
+pub fn validate_tag_detailed(tag_name: &str, tag_detail: Option<&str>) -> TagValidationResult {
     let cache_key = format!("{}:{}", tag_name, tag_detail.unwrap_or(""));
 
     // 检查缓存
-    if let Some(cached_status) = TAG_VALIDATION_CACHE.get(&cache_key) {
-        return match cached_status.as_str() {
-            "valid" => ValidationStatus::Valid,
-            "warning" => ValidationStatus::Warning,
-            "error" => ValidationStatus::Error,
-            "special" => ValidationStatus::Special,
-            _ => ValidationStatus::Valid,
-        };
+    if let Some(cached) = TAG_VALIDATION_CACHE.get(&cache_key) {
+        return cached.clone();
     }
 
-    let mut has_error = false;
+    let result = match VALIDATION_RULES.read() {
+        Ok(rules) => evaluate_tag(&rules, tag_name, tag_detail),
+        Err(poisoned) => evaluate_tag(&poisoned.into_inner(), tag_name, tag_detail),
+    };
 
-    // 1. 检查标签内容（tag_name）
-    if tag_name.is_empty() || tag_name.trim().is_empty() {
-        has_error = true;
-    } else {
-        // 检查是否包含非法字符
-        if tag_name.contains(':') {
-            has_error = true;
-        }
-        if tag_name.contains('{') || tag_name.contains('}') {
-            has_error = true;
-        }
-        // 检查是否有前后空格
-        if tag_name != tag_name.trim() {
-            has_error = true;
-        }
+    // 缓存结果
+    TAG_VALIDATION_CACHE.insert(cache_key, result.clone());
+
+    result
+}
+
+/// 获取当前生效的验证规则
+pub fn get_validation_rules() -> ValidationRules {
+    match VALIDATION_RULES.read() {
+        Ok(rules) => rules.clone(),
+        Err(poisoned) => poisoned.into_inner().clone(),
     }
+}
+
+/// 替换验证规则，并清空验证缓存
+pub fn set_validation_rules(rules: ValidationRules) {
+    match VALIDATION_RULES.write() {
+        Ok(mut current) => *current = rules,
+        Err(poisoned) => *poisoned.into_inner() = rules,
+    }
+    TAG_VALIDATION_CACHE.clear();
+}
+
+/// 搜索查询中的字段限定符
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryField {
+    /// 未限定：匹配分类、标签名称和补充说明
+    Any,
+    Category,
+    Tag,
+    Detail,
+    Code,
+    Exchange,
+    Concept,
+    /// 股票名称（容错模式下支持拼音）
+    Stock,
+}
 
-    // 2. 检查补充说明（tag_detail）
-    if let Some(detail) = tag_detail {
-        if detail.contains(':') {
-            has_error = true;
+impl QueryField {
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "category" => Some(QueryField::Category),
+            "tag" => Some(QueryField::Tag),
+            "detail" => Some(QueryField::Detail),
+            "code" => Some(QueryField::Code),
+            "exchange" => Some(QueryField::Exchange),
+            "concept" => Some(QueryField::Concept),
+            "stock" => Some(QueryField::Stock),
+            _ => None,
         }
-        if detail.contains('{') || detail.contains('}') {
-            has_error = true;
+    }
+
+    fn name(&self) -> Option<&'static str> {
+        match self {
+            QueryField::Any => None,
+            QueryField::Category => Some("category"),
+            QueryField::Tag => Some("tag"),
+            QueryField::Detail => Some("detail"),
+            QueryField::Code => Some("code"),
+            QueryField::Exchange => Some("exchange"),
+            QueryField::Concept => Some("concept"),
+            QueryField::Stock => Some("stock"),
         }
-        if detail != detail.trim() {
-            has_error = true;
+    }
+
+    /// 是否需要股票信息才能判断
+    fn is_stock_field(&self) -> bool {
+        matches!(
+            self,
+            QueryField::Code | QueryField::Exchange | QueryField::Concept | QueryField::Stock
+        )
+    }
+}
+
+/// 搜索词的匹配方式
+#[derive(Debug, Clone)]
+pub enum QueryPattern {
+    /// 不区分大小写的包含匹配（保存小写形式）
+    /// pinyin 为 true 时（纯字母数字）容错模式下同时匹配简拼和全拼
+    Text { text: String, pinyin: bool },
+    /// 正则表达式（不区分大小写）
+    Regex(Regex),
+}
+
+impl QueryPattern {
+    fn text(value: &str) -> Self {
+        let text = value.to_lowercase();
+        QueryPattern::Text {
+            pinyin: is_pinyin_query(&text),
+            text,
         }
-        if detail.len() > 100 {
-            has_error = true;
+    }
+
+    /// 精确匹配（用于代码、交易所等不适合容错的字段）
+    fn is_match(&self, value: &str) -> bool {
+        match self {
+            QueryPattern::Text { text, .. } => value.to_lowercase().contains(text),
+            QueryPattern::Regex(regex) => regex.is_match(value),
         }
     }
 
-    let status = if has_error {
-        ValidationStatus::Error
-    } else {
-        ValidationStatus::Valid
-    };
+    /// 匹配原文；容错模式下原文未命中且为拼音查询时再匹配预先计算的拼音
+    fn find(
+        &self,
+        value: &str,
+        pinyin: Option<&PinyinForms>,
+        mode: MatchMode,
+    ) -> Option<FieldMatch> {
+        match self {
+            QueryPattern::Text {
+                text,
+                pinyin: is_pinyin,
+            } => match mode {
+                MatchMode::Exact => exact_match(text, value),
+                MatchMode::Fuzzy => fuzzy_match(text, value).or_else(|| {
+                    (*is_pinyin && pinyin.is_some_and(|forms| forms.matches(text)))
+                        .then(pinyin_match)
+                }),
+            },
+            QueryPattern::Regex(regex) => regex_match(regex, value),
+        }
+    }
+}
 
-    // 缓存结果
-    TAG_VALIDATION_CACHE.insert(cache_key, status.as_str().to_string());
+/// 搜索查询语法树
+#[derive(Debug, Clone)]
+pub enum QueryExpr {
+    Term {
+        field: QueryField,
+        pattern: QueryPattern,
+    },
+    And(Box<QueryExpr>, Box<QueryExpr>),
+    Or(Box<QueryExpr>, Box<QueryExpr>),
+    Not(Box<QueryExpr>),
+}
 
-    status
+impl std::fmt::Display for QueryExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QueryExpr::Term { field, pattern } => {
+                if let Some(name) = field.name() {
+                    write!(f, "{}:", name)?;
+                }
+                match pattern {
+                    QueryPattern::Text { text, .. } => {
+                        write!(f, "\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+                    }
+                    QueryPattern::Regex(regex) => {
+                        write!(f, "/{}/", regex.as_str().replace('/', "\\/"))
+                    }
+                }
+            }
+            QueryExpr::And(left, right) => write!(f, "({} AND {})", left, right),
+            QueryExpr::Or(left, right) => write!(f, "({} OR {})", left, right),
+            QueryExpr::Not(inner) => write!(f, "NOT {}", inner),
+        }
+    }
 }
 
-/// 获取分类列表和统计信息
-pub fn get_category_list(
-    stock_data: &[StockCompanyInfo],
-    search_query: Option<&str>,
-) -> CategoryListResult {
-    // 使用并行处理来提高性能
-    let categories: Vec<String> = stock_data
-        .par_iter()
-        .filter(|stock| !stock.custom_tags.is_empty())
-        .flat_map(|stock| {
-            let parsed = parse_custom_tags(&stock.custom_tags);
-            parsed.keys().cloned().collect::<Vec<_>>()
-        })
-        .collect::<std::collections::HashSet<_>>()
-        .into_iter()
-        .collect();
+/// 搜索查询的解析错误类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryErrorKind {
+    /// 查询意外结束（例如 "AND" 之后没有内容）
+    UnexpectedEnd,
+    /// 出现在错误位置的运算符
+    UnexpectedToken,
+    /// 括号不匹配
+    UnmatchedParen,
+    /// 引号未闭合
+    UnclosedQuote,
+    /// 正则表达式未闭合
+    UnclosedRegex,
+    /// 正则表达式无效
+    InvalidRegex,
+    /// 未知的字段限定符
+    UnknownField,
+    /// 搜索内容为空
+    EmptyValue,
+}
 
-    let mut filtered_categories = categories;
+/// 搜索查询的解析错误，position 为出错位置的字符下标
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueryParseError {
+    pub kind: QueryErrorKind,
+    pub position: usize,
+    pub message: String,
+}
 
-    // 如果有搜索查询，过滤分类
-    if let Some(query) = search_query {
-        if !query.trim().is_empty() {
-            let query_lower = query.to_lowercase();
-            filtered_categories.retain(|category| {
-                category.to_lowercase().contains(&query_lower)
-                    || has_matching_tags_in_category(stock_data, category, &query_lower)
-            });
+impl QueryParseError {
+    fn new(kind: QueryErrorKind, position: usize, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            position,
+            message: message.into(),
         }
     }
+}
 
-    // 按中文排序
-    filtered_categories.sort_by(|a, b| a.cmp(b));
+impl std::fmt::Display for QueryParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}（位置 {}）", self.message, self.position)
+    }
+}
 
-    let total_tags = count_total_tags(stock_data, &filtered_categories, search_query);
+#[derive(Debug)]
+enum QueryTokenKind {
+    LParen,
+    RParen,
+    And,
+    Or,
+    Not,
+    Term(QueryField, QueryPattern),
+}
 
-    CategoryListResult {
-        categories: filtered_categories.clone(),
-        statistics: TagStatistics {
-            total_tags,
-            total_categories: filtered_categories.len() as u32,
-            selected_category_tags_count: 0,
-            current_page_tags_count: 0,
-            error_tags_count: 0,
-            warning_tags_count: 0,
-            valid_tags_count: 0,
-        },
+#[derive(Debug)]
+struct QueryToken {
+    kind: QueryTokenKind,
+    position: usize,
+}
+
+/// 将查询拆分为词法单元
+fn tokenize_query(chars: &[char]) -> Result<Vec<QueryToken>, QueryParseError> {
+    let is_boundary = |ch: char| ch.is_whitespace() || ch == '(' || ch == ')';
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let ch = chars[i];
+        if ch.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        let position = i;
+        let kind = match ch {
+            '(' => {
+                i += 1;
+                QueryTokenKind::LParen
+            }
+            ')' => {
+                i += 1;
+                QueryTokenKind::RParen
+            }
+            '&' if chars.get(i + 1) == Some(&'&') => {
+                i += 2;
+                QueryTokenKind::And
+            }
+            '|' if chars.get(i + 1) == Some(&'|') => {
+                i += 2;
+                QueryTokenKind::Or
+            }
+            // "-词" 或 "!词" 表示取反
+            '-' | '!' if chars.get(i + 1).is_some_and(|next| !next.is_whitespace()) => {
+                i += 1;
+                QueryTokenKind::Not
+            }
+            _ => {
+                // 字段限定符：ASCII 字母组成的名称后紧跟 ":"
+                let name_end = chars[i..]
+                    .iter()
+                    .position(|ch| !ch.is_ascii_alphabetic())
+                    .map_or(chars.len(), |offset| i + offset);
+                let field = if name_end > i && chars.get(name_end) == Some(&':') {
+                    let name: String = chars[i..name_end].iter().collect();
+                    let field = QueryField::from_name(&name).ok_or_else(|| {
+                        QueryParseError::new(
+                            QueryErrorKind::UnknownField,
+                            position,
+                            format!("未知的字段 \"{}\"", name),
+                        )
+                    })?;
+                    i = name_end + 1;
+                    Some(field)
+                } else {
+                    None
+                };
+
+                match (chars.get(i), field) {
+                    (Some('"'), _) | (Some('/'), _) => {
+                        let (pattern, next) = read_quoted_or_regex(chars, i)?;
+                        i = next;
+                        QueryTokenKind::Term(field.unwrap_or(QueryField::Any), pattern)
+                    }
+                    (None, Some(_)) => {
+                        return Err(QueryParseError::new(
+                            QueryErrorKind::EmptyValue,
+                            i,
+                            "字段限定符后缺少搜索内容",
+                        ))
+                    }
+                    (Some(&next), Some(_)) if is_boundary(next) => {
+                        return Err(QueryParseError::new(
+                            QueryErrorKind::EmptyValue,
+                            i,
+                            "字段限定符后缺少搜索内容",
+                        ))
+                    }
+                    _ => {
+                        let start = i;
+                        while i < chars.len() && !is_boundary(chars[i]) {
+                            i += 1;
+                        }
+                        let word: String = chars[start..i].iter().collect();
+                        match (field, word.as_str()) {
+                            (None, "AND") => QueryTokenKind::And,
+                            (None, "OR") => QueryTokenKind::Or,
+                            (None, "NOT") => QueryTokenKind::Not,
+                            _ => QueryTokenKind::Term(
+                                field.unwrap_or(QueryField::Any),
+                                QueryPattern::text(&word),
+                            ),
+                        }
+                    }
+                }
+            }
+        };
+        tokens.push(QueryToken { kind, position });
     }
+
+    Ok(tokens)
 }
 
-/// 检查分类下是否有匹配的标签
-fn has_matching_tags_in_category(
-    stock_data: &[StockCompanyInfo],
-    category: &str,
-    query: &str,
-) -> bool {
-    stock_data
-        .par_iter()
-        .filter(|stock| !stock.custom_tags.is_empty())
-        .any(|stock| {
-            let parsed = parse_custom_tags(&stock.custom_tags);
-            if let Some(category_items) = parsed.get(category) {
-                category_items.iter().any(|item| {
-                    item.name.to_lowercase().contains(query)
-                        || item
-                            .detail
-                            .as_ref()
-                            .map_or(false, |d| d.to_lowercase().contains(query))
+/// 读取 "短语" 或 /正则/，返回匹配方式和结束后的下标
+fn read_quoted_or_regex(
+    chars: &[char],
+    start: usize,
+) -> Result<(QueryPattern, usize), QueryParseError> {
+    let delimiter = chars[start];
+    let mut value = String::new();
+    let mut i = start + 1;
+
+    loop {
+        match chars.get(i) {
+            None => {
+                return Err(if delimiter == '"' {
+                    QueryParseError::new(QueryErrorKind::UnclosedQuote, start, "引号未闭合")
+                } else {
+                    QueryParseError::new(QueryErrorKind::UnclosedRegex, start, "正则表达式未闭合")
                 })
-            } else {
-                false
             }
-        })
+            Some('\\') if chars.get(i + 1) == Some(&delimiter) => {
+                value.push(delimiter);
+                i += 2;
+            }
+            // 短语中 "\\" 表示反斜杠本身，正则中保留转义交给正则引擎处理
+            Some('\\') if delimiter == '"' && chars.get(i + 1) == Some(&'\\') => {
+                value.push('\\');
+                i += 2;
+            }
+            Some(&ch) if ch == delimiter => break,
+            Some(&ch) => {
+                value.push(ch);
+                i += 1;
+            }
+        }
+    }
+
+    if value.is_empty() {
+        return Err(QueryParseError::new(
+            QueryErrorKind::EmptyValue,
+            start,
+            "搜索内容为空",
+        ));
+    }
+
+    let pattern = if delimiter == '"' {
+        QueryPattern::text(&value)
+    } else {
+        let regex = regex::RegexBuilder::new(&value)
+            .case_insensitive(true)
+            .build()
+            .map_err(|e| {
+                QueryParseError::new(
+                    QueryErrorKind::InvalidRegex,
+                    start,
+                    format!("正则表达式无效: {}", e),
+                )
+            })?;
+        QueryPattern::Regex(regex)
+    };
+    Ok((pattern, i + 1))
 }
 
-/// 计算总标签数量
-fn count_total_tags(
-    stock_data: &[StockCompanyInfo],
-    categories: &[String],
-    search_query: Option<&str>,
-) -> u32 {
-    let mut count = 0;
-
-    for category in categories {
-        let category_data = get_category_data(stock_data, category);
-        if let Some(query) = search_query {
-            if !query.trim().is_empty() {
-                let query_lower = query.to_lowercase();
-                count += category_data
-                    .tags
-                    .iter()
-                    .filter(|tag| {
-                        tag.name.to_lowercase().contains(&query_lower)
-                            || tag
-                                .detail
-                                .as_ref()
-                                .map_or(false, |d| d.to_lowercase().contains(&query_lower))
-                            || category.to_lowercase().contains(&query_lower)
-                    })
-                    .count() as u32;
-            } else {
-                count += category_data.tags.len() as u32;
+/// 递归下降解析器，优先级 NOT > AND（含隐式 AND）> OR
+struct QueryParser {
+    tokens: std::iter::Peekable<std::vec::IntoIter<QueryToken>>,
+    end_position: usize,
+}
+
+impl QueryParser {
+    fn parse_or(&mut self) -> Result<QueryExpr, QueryParseError> {
+        let mut left = self.parse_and()?;
+        while self
+            .tokens
+            .next_if(|token| matches!(token.kind, QueryTokenKind::Or))
+            .is_some()
+        {
+            let right = self.parse_and()?;
+            left = QueryExpr::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<QueryExpr, QueryParseError> {
+        let mut left = self.parse_not()?;
+        loop {
+            match self.tokens.peek().map(|token| &token.kind) {
+                Some(QueryTokenKind::And) => {
+                    self.tokens.next();
+                }
+                // 相邻的搜索词视为 AND
+                Some(QueryTokenKind::Term(..) | QueryTokenKind::LParen | QueryTokenKind::Not) => {}
+                _ => break,
             }
-        } else {
-            count += category_data.tags.len() as u32;
+            let right = self.parse_not()?;
+            left = QueryExpr::And(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_not(&mut self) -> Result<QueryExpr, QueryParseError> {
+        if self
+            .tokens
+            .next_if(|token| matches!(token.kind, QueryTokenKind::Not))
+            .is_some()
+        {
+            return Ok(QueryExpr::Not(Box::new(self.parse_not()?)));
         }
+        self.parse_primary()
     }
 
-    count
+    fn parse_primary(&mut self) -> Result<QueryExpr, QueryParseError> {
+        let Some(token) = self.tokens.next() else {
+            return Err(QueryParseError::new(
+                QueryErrorKind::UnexpectedEnd,
+                self.end_position,
+                "查询不完整",
+            ));
+        };
+
+        match token.kind {
+            QueryTokenKind::Term(field, pattern) => Ok(QueryExpr::Term { field, pattern }),
+            QueryTokenKind::LParen => {
+                let expr = self.parse_or()?;
+                match self.tokens.next() {
+                    Some(QueryToken {
+                        kind: QueryTokenKind::RParen,
+                        ..
+                    }) => Ok(expr),
+                    _ => Err(QueryParseError::new(
+                        QueryErrorKind::UnmatchedParen,
+                        token.position,
+                        "缺少右括号",
+                    )),
+                }
+            }
+            QueryTokenKind::RParen => Err(QueryParseError::new(
+                QueryErrorKind::UnmatchedParen,
+                token.position,
+                "多余的右括号",
+            )),
+            QueryTokenKind::And | QueryTokenKind::Or | QueryTokenKind::Not => {
+                Err(QueryParseError::new(
+                    QueryErrorKind::UnexpectedToken,
+                    token.position,
+                    "运算符缺少搜索词",
+                ))
+            }
+        }
+    }
 }
 
-/// 获取指定分类的标签数据
-pub fn get_category_data(stock_data: &[StockCompanyInfo], category_name: &str) -> TagCategory {
-    let mut tag_map: HashMap<String, TagDetails> = HashMap::new();
-
-    // 并行处理股票数据
-    let tag_entries: Vec<_> = stock_data
-        .par_iter()
-        .filter_map(|stock| {
-            if !stock.custom_tags.is_empty() {
-                let parsed = parse_custom_tags(&stock.custom_tags);
-                if let Some(category_items) = parsed.get(category_name) {
-                    Some(
-                        category_items
-                            .iter()
-                            .map(|item| (item.clone(), stock.clone()))
-                            .collect::<Vec<_>>(),
+/// 相关度计算中各字段的权重：标签名称 > 补充说明 > 分类
+const NAME_MATCH_WEIGHT: f64 = 1.0;
+const DETAIL_MATCH_WEIGHT: f64 = 0.6;
+const CATEGORY_MATCH_WEIGHT: f64 = 0.4;
+
+/// 解析后的搜索查询
+#[derive(Debug, Clone)]
+pub struct SearchQuery {
+    pub expr: QueryExpr,
+    /// 文本搜索词的匹配方式
+    pub match_mode: MatchMode,
+    /// 是否包含 code: / exchange: / concept: 等需要股票信息的条件
+    uses_stock_fields: bool,
+}
+
+impl SearchQuery {
+    /// 解析搜索框中的查询，空白查询返回 None
+    ///
+    /// 语法：AND / OR / NOT（也可写作 && / || / -词），括号分组，相邻的词默认为 AND；
+    /// 字段限定符 category: tag: detail: code: exchange: concept: stock:；
+    /// "带空格的短语"；/正则表达式/（不区分大小写）；
+    /// 容错模式下纯字母数字的搜索词同时匹配分类、标签、补充说明和股票名称的简拼或全拼
+    pub fn parse(query: &str) -> Result<Option<Self>, QueryParseError> {
+        let chars: Vec<char> = query.chars().collect();
+        let tokens = tokenize_query(&chars)?;
+        if tokens.is_empty() {
+            return Ok(None);
+        }
+
+        let mut parser = QueryParser {
+            tokens: tokens.into_iter().peekable(),
+            end_position: chars.len(),
+        };
+        let expr = parser.parse_or()?;
+        if let Some(token) = parser.tokens.next() {
+            // parse_and 只会在 ")" 或 OR 之前停下，剩下的只能是多余的右括号
+            return Err(QueryParseError::new(
+                QueryErrorKind::UnmatchedParen,
+                token.position,
+                "多余的右括号",
+            ));
+        }
+
+        Ok(Some(Self {
+            uses_stock_fields: expr_uses_stock_fields(&expr),
+            match_mode: MatchMode::default(),
+            expr,
+        }))
+    }
+
+    /// 设置文本搜索词的匹配方式
+    pub fn with_match_mode(mut self, match_mode: MatchMode) -> Self {
+        self.match_mode = match_mode;
+        self
+    }
+
+    /// 判断索引中的标签是否匹配查询；包含股票条件时，只要有一只股票满足整个查询即可
+    pub fn matches_tag(
+        &self,
+        stock_data: &[StockCompanyInfo],
+        index: &TagIndex,
+        category: &str,
+        tag: &IndexedTag,
+    ) -> bool {
+        let subject = |stock| QuerySubject {
+            category,
+            category_pinyin: index.category_pinyin.get(category),
+            tag,
+            stock,
+            match_mode: self.match_mode,
+        };
+        if self.uses_stock_fields {
+            tag.stock_ids.iter().any(|&id| {
+                let stock = stock_data.get(id).zip(index.stock_pinyin.get(id));
+                stock.is_some() && eval_query(&self.expr, &subject(stock))
+            })
+        } else {
+            eval_query(&self.expr, &subject(None))
+        }
+    }
+
+    /// 计算标签的相关度和高亮区间，只统计未被 NOT 排除的搜索词
+    pub fn score_tag(&self, index: &TagIndex, category: &str, tag: &IndexedTag) -> TagSearchMatch {
+        let mut terms = Vec::new();
+        collect_positive_terms(&self.expr, &mut terms);
+
+        let mut result = TagSearchMatch::default();
+        for (field, pattern) in terms {
+            let applies =
+                |fields: &[QueryField]| *field == QueryField::Any || fields.contains(field);
+            let name = applies(&[QueryField::Tag])
+                .then(|| pattern.find(&tag.name, Some(&tag.name_pinyin), self.match_mode))
+                .flatten();
+            let detail = applies(&[QueryField::Detail])
+                .then(|| {
+                    let detail = tag.detail.as_deref()?;
+                    pattern.find(detail, tag.detail_pinyin.as_ref(), self.match_mode)
+                })
+                .flatten();
+            let category = applies(&[QueryField::Category])
+                .then(|| {
+                    pattern.find(
+                        category,
+                        index.category_pinyin.get(category),
+                        self.match_mode,
                     )
-                } else {
-                    Some(Vec::new())
+                })
+                .flatten();
+
+            let weighted = |found: &Option<FieldMatch>, weight: f64| {
+                found.as_ref().map_or(0.0, |found| found.score * weight)
+            };
+            result.score += weighted(&name, NAME_MATCH_WEIGHT)
+                .max(weighted(&detail, DETAIL_MATCH_WEIGHT))
+                .max(weighted(&category, CATEGORY_MATCH_WEIGHT));
+
+            for (found, spans) in [
+                (name, &mut result.name_spans),
+                (detail, &mut result.detail_spans),
+                (category, &mut result.category_spans),
+            ] {
+                if let Some(found) = found {
+                    spans.extend(found.spans);
                 }
-            } else {
-                None
             }
-        })
-        .flatten()
-        .collect();
+        }
 
-    // 构建标签映射
-    for (item, stock) in tag_entries {
-        let tag_key = format!("{}:{}", item.name, item.detail.as_deref().unwrap_or(""));
-        let tag = tag_map.entry(tag_key).or_insert_with(|| TagDetails {
-            name: item.name.clone(),
-            detail: item.detail.clone(),
-            count: 0,
-            stocks: Vec::new(),
-        });
-        tag.count += 1;
-        tag.stocks.push(stock);
+        result.name_spans = merge_spans(std::mem::take(&mut result.name_spans));
+        result.detail_spans = merge_spans(std::mem::take(&mut result.detail_spans));
+        result.category_spans = merge_spans(std::mem::take(&mut result.category_spans));
+        result
     }
+}
 
-    // 对标签进行排序
-    let mut tags: Vec<TagDetails> = tag_map.into_values().collect();
-    tags.sort_by(|a, b| {
-        let a_status = validate_tag_format(&a.name, a.detail.as_deref());
-        let b_status = validate_tag_format(&b.name, b.detail.as_deref());
+/// 收集未被 NOT 包含的搜索词
+fn collect_positive_terms<'a>(
+    expr: &'a QueryExpr,
+    terms: &mut Vec<(&'a QueryField, &'a QueryPattern)>,
+) {
+    match expr {
+        QueryExpr::Term { field, pattern } => terms.push((field, pattern)),
+        QueryExpr::And(left, right) | QueryExpr::Or(left, right) => {
+            collect_positive_terms(left, terms);
+            collect_positive_terms(right, terms);
+        }
+        QueryExpr::Not(_) => {}
+    }
+}
 
-        // 首先按验证状态排序（错误优先）
-        let weight_cmp = b_status.weight().cmp(&a_status.weight());
-        if weight_cmp != std::cmp::Ordering::Equal {
-            return weight_cmp;
+fn expr_uses_stock_fields(expr: &QueryExpr) -> bool {
+    match expr {
+        QueryExpr::Term { field, .. } => field.is_stock_field(),
+        QueryExpr::And(left, right) | QueryExpr::Or(left, right) => {
+            expr_uses_stock_fields(left) || expr_uses_stock_fields(right)
         }
+        QueryExpr::Not(inner) => expr_uses_stock_fields(inner),
+    }
+}
+
+/// 查询求值的对象：一个标签，以及（可选的）携带该标签的一只股票
+struct QuerySubject<'a> {
+    category: &'a str,
+    category_pinyin: Option<&'a PinyinForms>,
+    tag: &'a IndexedTag,
+    /// 股票及其名称的拼音
+    stock: Option<(&'a StockCompanyInfo, &'a PinyinForms)>,
+    match_mode: MatchMode,
+}
+
+fn eval_query(expr: &QueryExpr, subject: &QuerySubject) -> bool {
+    match expr {
+        QueryExpr::Term { field, pattern } => match field {
+            QueryField::Any => {
+                match_category(pattern, subject)
+                    || match_tag_name(pattern, subject)
+                    || match_tag_detail(pattern, subject)
+            }
+            QueryField::Category => match_category(pattern, subject),
+            QueryField::Tag => match_tag_name(pattern, subject),
+            QueryField::Detail => match_tag_detail(pattern, subject),
+            QueryField::Code => subject
+                .stock
+                .is_some_and(|(stock, _)| pattern.is_match(&stock.stock_code)),
+            QueryField::Exchange => subject
+                .stock
+                .is_some_and(|(stock, _)| pattern.is_match(&stock.exchange)),
+            QueryField::Concept => subject.stock.is_some_and(|(stock, _)| {
+                stock
+                    .sectors_concepts
+                    .iter()
+                    .any(|concept| pattern.find(concept, None, subject.match_mode).is_some())
+            }),
+            QueryField::Stock => subject.stock.is_some_and(|(stock, pinyin)| {
+                pattern
+                    .find(&stock.stock_name, Some(pinyin), subject.match_mode)
+                    .is_some()
+            }),
+        },
+        QueryExpr::And(left, right) => eval_query(left, subject) && eval_query(right, subject),
+        QueryExpr::Or(left, right) => eval_query(left, subject) || eval_query(right, subject),
+        QueryExpr::Not(inner) => !eval_query(inner, subject),
+    }
+}
+
+fn match_category(pattern: &QueryPattern, subject: &QuerySubject) -> bool {
+    pattern
+        .find(
+            subject.category,
+            subject.category_pinyin,
+            subject.match_mode,
+        )
+        .is_some()
+}
+
+fn match_tag_name(pattern: &QueryPattern, subject: &QuerySubject) -> bool {
+    let tag = subject.tag;
+    pattern
+        .find(&tag.name, Some(&tag.name_pinyin), subject.match_mode)
+        .is_some()
+}
 
-        // 然后按使用次数降序
-        let count_cmp = b.count.cmp(&a.count);
-        if count_cmp != std::cmp::Ordering::Equal {
-            return count_cmp;
+fn match_tag_detail(pattern: &QueryPattern, subject: &QuerySubject) -> bool {
+    let tag = subject.tag;
+    tag.detail.as_deref().is_some_and(|detail| {
+        pattern
+            .find(detail, tag.detail_pinyin.as_ref(), subject.match_mode)
+            .is_some()
+    })
+}
+
+/// 解析可选的搜索查询，未提供或为空白时返回 None
+pub fn parse_search_query(
+    query: Option<&str>,
+    match_mode: MatchMode,
+) -> Result<Option<SearchQuery>, QueryParseError> {
+    let query = query.map(SearchQuery::parse).transpose()?.flatten();
+    Ok(query.map(|query| query.with_match_mode(match_mode)))
+}
+
+/// 获取分类列表和统计信息
+/// 有搜索查询时，只保留包含匹配标签的分类，并只统计匹配的标签
+pub fn get_category_list(
+    stock_data: &[StockCompanyInfo],
+    index: &TagIndex,
+    search_query: Option<&SearchQuery>,
+    collation: CollationOrder,
+) -> CategoryListResult {
+    let mut filtered_categories: Vec<String> = Vec::new();
+    let mut total_tags = 0;
+
+    for (category, tags) in &index.categories {
+        let matched = match search_query {
+            Some(query) => tags
+                .values()
+                .filter(|tag| query.matches_tag(stock_data, index, category, tag))
+                .count(),
+            None => tags.len(),
+        };
+        if matched > 0 {
+            filtered_categories.push(category.clone());
+            total_tags += matched as u32;
         }
+    }
+
+    // 按指定的排序方式（默认拼音）排序
+    sort_names(&mut filtered_categories, collation);
+
+    CategoryListResult {
+        categories: filtered_categories.clone(),
+        statistics: TagStatistics {
+            total_tags,
+            total_categories: filtered_categories.len() as u32,
+            selected_category_tags_count: 0,
+            current_page_tags_count: 0,
+            error_tags_count: 0,
+            warning_tags_count: 0,
+            valid_tags_count: 0,
+        },
+    }
+}
 
-        // 最后按名称排序
-        a.name.cmp(&b.name)
+/// 获取指定分类的标签数据
+/// 传入黑名单时，为命中的标签标记所属黑名单分组；传入标签层级时，同时构建标签树
+pub fn get_category_data(
+    stock_data: &[StockCompanyInfo],
+    index: &TagIndex,
+    blacklist: Option<&TagBlacklist>,
+    taxonomy: Option<&TagTaxonomy>,
+    category_name: &str,
+    collation: CollationOrder,
+) -> TagCategory {
+    // 对标签进行排序：首先按验证状态（错误优先），然后按使用次数降序，最后按名称
+    let mut entries: Vec<(TagOrderKey, &IndexedTag)> = index
+        .category(category_name)
+        .into_iter()
+        .flat_map(|tag_map| tag_map.values())
+        .map(|tag| (TagOrderKey::of(stock_data, tag, collation), tag))
+        .collect();
+    entries.sort_by(|(a_key, a), (b_key, b)| {
+        compare_tags(TagSortKey::Validation, collation, (a_key, a), (b_key, b))
     });
 
+    // 排序后再从索引中取出标签的股票
+    let tags = entries
+        .into_iter()
+        .map(|(_, tag)| {
+            let blacklist_group = blacklist.and_then(|blacklist| blacklist.match_group(&tag.name));
+            let mut details = listed_tag_details(category_name, tag, blacklist_group);
+            details.stocks = tag_stocks(stock_data, tag, None);
+            details
+        })
+        .collect();
+
     TagCategory {
         name: category_name.to_string(),
         tags,
+        tree: taxonomy
+            .map(|taxonomy| taxonomy.build_tree(index, category_name))
+            .unwrap_or_default(),
     }
 }
 
+/// 将索引条目转换为标签详情（不含股票数据）
+pub fn to_tag_details(category_name: &str, tag: &IndexedTag) -> TagDetails {
+    TagDetails {
+        tag_id: tag_id(category_name, &tag.name, tag.detail.as_deref()),
+        name: tag.name.clone(),
+        detail: tag.detail.clone(),
+        count: tag.stock_ids.len() as u32,
+        stocks: Vec::new(),
+        blacklist_group: None,
+        validation: None,
+        search_match: None,
+        sample_stocks: Vec::new(),
+        variants: if tag.variants.len() > 1 || !tag.variants.contains_key(&tag.name) {
+            tag.variants
+                .iter()
+                .map(|(name, stock_ids)| TagVariant {
+                    name: name.clone(),
+                    count: stock_ids.len() as u32,
+                })
+                .collect()
+        } else {
+            Vec::new()
+        },
+    }
+}
+
+/// 标签列表中的标签详情：附带黑名单分组和验证结果（不含股票数据）
+fn listed_tag_details(
+    category_name: &str,
+    tag: &IndexedTag,
+    blacklist_group: Option<&str>,
+) -> TagDetails {
+    let mut details = to_tag_details(category_name, tag);
+    details.blacklist_group = blacklist_group.map(str::to_string);
+    details.validation = Some(validate_tag_detailed(&tag.name, tag.detail.as_deref()));
+    details
+}
+
+/// 标签列表中待排序的标签（尚未展开股票数据）
+struct ListedTag<'a> {
+    tag: &'a IndexedTag,
+    key: TagOrderKey,
+    blacklist_group: Option<&'a str>,
+    search_match: Option<TagSearchMatch>,
+}
+
 /// 获取标签列表（带分页和搜索）
-pub fn get_tag_list(stock_data: &[StockCompanyInfo], params: &SearchParams) -> TagListResult {
+/// 过滤、排序和分页都在索引条目上进行，只为当前页的标签展开股票数据
+pub fn get_tag_list(
+    stock_data: &[StockCompanyInfo],
+    index: &TagIndex,
+    blacklist: &TagBlacklist,
+    params: &SearchParams,
+    search_query: Option<&SearchQuery>,
+) -> CommandResult<TagListResult> {
+    // 游标只对生成它的分类、搜索条件和排序方式有效
+    let fingerprint = query_fingerprint(&(
+        &params.category_name,
+        &params.search_query,
+        params.match_mode,
+        params.blacklist_mode,
+        params.tags_sort_by,
+        params.tags_reverse,
+        params.collation,
+    ));
+    let offset = page_offset(
+        params.cursor.as_deref(),
+        index.version,
+        fingerprint,
+        params.tags_page,
+        params.tags_per_page,
+    )?;
     let category_name = match &params.category_name {
         Some(name) => name,
         None => {
-            return TagListResult {
+            return Ok(TagListResult {
                 tags: Vec::new(),
                 total_tags: 0,
                 total_pages: 0,
-                current_page: params.tags_page,
+                current_page: page_number(offset, params.tags_per_page),
                 error_tags_count: 0,
                 warning_tags_count: 0,
                 valid_tags_count: 0,
-            }
+                blacklisted_tags_count: 0,
+                next_cursor: None,
+                dataset_version: index.version,
+            })
         }
     };
 
-    let category_data = get_category_data(stock_data, category_name);
-    let mut tags = category_data.tags;
-
-    // 如果有搜索查询，过滤标签
-    if let Some(query) = &params.search_query {
-        if !query.trim().is_empty() {
-            let query_lower = query.to_lowercase();
-            tags.retain(|tag| {
-                tag.name.to_lowercase().contains(&query_lower)
-                    || tag
-                        .detail
-                        .as_ref()
-                        .map_or(false, |d| d.to_lowercase().contains(&query_lower))
-                    || category_name.to_lowercase().contains(&query_lower)
-            });
-        }
-    }
+    let blacklist = match params.blacklist_mode {
+        BlacklistMode::Off => None,
+        BlacklistMode::Flag | BlacklistMode::Hide => Some(blacklist),
+    };
 
-    // 重要：对所有标签按验证状态排序（错误优先），然后再分页
-    tags.sort_by(|a, b| {
-        let a_status = validate_tag_format(&a.name, a.detail.as_deref());
-        let b_status = validate_tag_format(&b.name, b.detail.as_deref());
+    // 如果有搜索查询，过滤标签并计算相关度和高亮区间
+    // 黑名单处理：隐藏模式下移除命中的标签，标记模式下仅统计
+    let mut blacklisted_tags_count = 0;
+    let mut entries: Vec<ListedTag> = index
+        .category(category_name)
+        .into_iter()
+        .flat_map(|tag_map| tag_map.values())
+        .filter_map(|tag| {
+            let search_match = match search_query {
+                Some(query) if !query.matches_tag(stock_data, index, category_name, tag) => {
+                    return None
+                }
+                Some(query) => Some(query.score_tag(index, category_name, tag)),
+                None => None,
+            };
+            let blacklist_group = blacklist.and_then(|blacklist| blacklist.match_group(&tag.name));
+            if blacklist_group.is_some() {
+                blacklisted_tags_count += 1;
+                if params.blacklist_mode == BlacklistMode::Hide {
+                    return None;
+                }
+            }
+            Some(ListedTag {
+                tag,
+                key: TagOrderKey::of(stock_data, tag, params.collation),
+                blacklist_group,
+                search_match,
+            })
+        })
+        .collect();
+    let rank_by_relevance = search_query.is_some_and(|query| query.match_mode == MatchMode::Fuzzy);
 
-        // 首先按验证状态排序（错误优先，权重高的在前）
-        let weight_cmp = b_status.weight().cmp(&a_status.weight());
-        if weight_cmp != std::cmp::Ordering::Equal {
-            return weight_cmp;
+    // 重要：对所有标签排序后再分页
+    // 容错搜索时先按相关度降序，其余情况按所选的排序方式
+    // 排序键各不相同的标签最终按名称和补充说明排序，保证同一版本的数据分页稳定
+    entries.sort_by(|a, b| {
+        if rank_by_relevance {
+            let score = |entry: &ListedTag| entry.search_match.as_ref().map_or(0.0, |m| m.score);
+            let score_cmp = score(b).total_cmp(&score(a));
+            if score_cmp != std::cmp::Ordering::Equal {
+                return score_cmp;
+            }
         }
 
-        // 然后按使用次数降序
-        let count_cmp = b.count.cmp(&a.count);
-        if count_cmp != std::cmp::Ordering::Equal {
-            return count_cmp;
+        let ordering = compare_tags(
+            params.tags_sort_by,
+            params.collation,
+            (&a.key, a.tag),
+            (&b.key, b.tag),
+        );
+        if params.tags_reverse {
+            ordering.reverse()
+        } else {
+            ordering
         }
-
-        // 最后按名称排序
-        a.name.cmp(&b.name)
     });
 
     // 计算整个分类下所有标签的验证统计（不仅仅是当前页）
@@ -390,56 +1177,295 @@ pub fn get_tag_list(stock_data: &[StockCompanyInfo], params: &SearchParams) -> T
     let mut warning_count = 0;
     let mut valid_count = 0;
 
-    for tag in &tags {
-        let status = validate_tag_format(&tag.name, tag.detail.as_deref());
-        match status {
+    for entry in &entries {
+        match entry.key.status {
             ValidationStatus::Error => error_count += 1,
             ValidationStatus::Warning => warning_count += 1,
             ValidationStatus::Valid | ValidationStatus::Special => valid_count += 1,
         }
     }
 
-    let total_tags = tags.len() as u32;
-    let total_pages = ((total_tags as f64) / (params.tags_per_page as f64)).ceil() as u32;
+    let total_tags = entries.len() as u32;
+    let total_pages = total_tags.div_ceil(params.tags_per_page);
+    let next_cursor = next_cursor(
+        index.version,
+        fingerprint,
+        offset,
+        params.tags_per_page,
+        entries.len(),
+    );
 
-    // 标签分页（在排序后进行）
-    let start_index = ((params.tags_page - 1) * params.tags_per_page) as usize;
-    let end_index = (start_index + params.tags_per_page as usize).min(tags.len());
-    let paged_tags = tags
+    // 标签分页（在排序后进行），超出末页时返回空页
+    // 只为当前页的标签取出股票：摘要模式只取少量示例股票，否则按 stock_fields 只取部分字段
+    let sample_size = params.sample_size.unwrap_or(DEFAULT_SAMPLE_SIZE) as usize;
+    let paged_tags: Vec<TagDetails> = entries
         .into_iter()
-        .skip(start_index)
-        .take(end_index - start_index)
+        .skip(offset)
+        .take(params.tags_per_page as usize)
+        .map(|entry| {
+            let mut details = listed_tag_details(category_name, entry.tag, entry.blacklist_group);
+            details.search_match = entry.search_match;
+            match params.projection {
+                TagProjection::Summary => {
+                    details.sample_stocks = entry
+                        .tag
+                        .stock_ids
+                        .iter()
+                        .filter_map(|&id| stock_data.get(id))
+                        .take(sample_size)
+                        .map(|stock| StockSample {
+                            stock_code: stock.stock_code.clone(),
+                            stock_name: stock.stock_name.clone(),
+                        })
+                        .collect();
+                }
+                TagProjection::Full => {
+                    details.stocks =
+                        tag_stocks(stock_data, entry.tag, params.stock_fields.as_deref());
+                }
+            }
+            details
+        })
         .collect();
 
-    TagListResult {
+    Ok(TagListResult {
         tags: paged_tags,
         total_tags,
         total_pages,
-        current_page: params.tags_page,
+        current_page: page_number(offset, params.tags_per_page),
         error_tags_count: error_count,
         warning_tags_count: warning_count,
         valid_tags_count: valid_count,
+        blacklisted_tags_count,
+        next_cursor,
+        dataset_version: index.version,
+    })
+}
+
+/// 标签排序时用到的预先计算的值
+struct TagOrderKey {
+    status: ValidationStatus,
+    /// 名称的排序键
+    name: CollationKey,
+    /// 标签下股票的最近更新时间（UTC 毫秒），无法解析的时间视为最早
+    updated_at: Option<i64>,
+}
+
+impl TagOrderKey {
+    fn of(stock_data: &[StockCompanyInfo], tag: &IndexedTag, collation: CollationOrder) -> Self {
+        Self {
+            status: validate_tag_format(&tag.name, tag.detail.as_deref()),
+            name: CollationKey::new(&tag.name, collation),
+            updated_at: tag
+                .stock_ids
+                .iter()
+                .filter_map(|&id| stock_data.get(id))
+                .filter_map(|stock| parse_timestamp(&stock.updated_at))
+                .max(),
+        }
     }
 }
 
-/// 获取股票列表（带分页）
-pub fn get_stock_list(selected_tag: &SelectedTag, params: &SearchParams) -> StockListResult {
-    let total_stocks = selected_tag.stocks.len() as u32;
-    let total_pages = ((total_stocks as f64) / (params.stocks_per_page as f64)).ceil() as u32;
+/// 按排序方式比较两个标签，相同时按名称和补充说明排序
+fn compare_tags(
+    sort_by: TagSortKey,
+    collation: CollationOrder,
+    (a_key, a): (&TagOrderKey, &IndexedTag),
+    (b_key, b): (&TagOrderKey, &IndexedTag),
+) -> std::cmp::Ordering {
+    let (a_count, b_count) = (a.stock_ids.len(), b.stock_ids.len());
+    match sort_by {
+        // 错误优先（权重高的在前），然后按使用次数降序
+        TagSortKey::Validation => b_key
+            .status
+            .weight()
+            .cmp(&a_key.status.weight())
+            .then_with(|| b_count.cmp(&a_count)),
+        TagSortKey::Count => b_count.cmp(&a_count),
+        TagSortKey::Name => std::cmp::Ordering::Equal,
+        TagSortKey::UpdatedAt => b_key.updated_at.cmp(&a_key.updated_at),
+    }
+    .then_with(|| a_key.name.cmp(&b_key.name))
+    .then_with(|| collate_option(a.detail.as_deref(), b.detail.as_deref(), collation))
+}
+
+/// 标签下的股票，传入 fields 时只返回这些字段
+fn tag_stocks(
+    stock_data: &[StockCompanyInfo],
+    tag: &IndexedTag,
+    fields: Option<&[StockField]>,
+) -> Vec<ProjectedStock> {
+    tag.stock_ids
+        .iter()
+        .filter_map(|&id| stock_data.get(id))
+        .map(|stock| project_stock(stock, fields))
+        .collect()
+}
+
+/// 按字段投影股票，未指定字段时返回全部字段
+fn project_stock(stock: &StockCompanyInfo, fields: Option<&[StockField]>) -> ProjectedStock {
+    match fields {
+        Some(fields) => stock.project(fields),
+        None => ProjectedStock::from(stock),
+    }
+}
+
+/// 获取股票列表：从 offset 开始取一页，超出末页时返回空页
+/// 传入 fields 时只返回这些字段
+pub fn get_stock_list(
+    stocks: &[&StockCompanyInfo],
+    offset: usize,
+    per_page: u32,
+    fields: Option<&[StockField]>,
+) -> StockListResult {
+    let total_stocks = stocks.len() as u32;
+    let total_pages = total_stocks.div_ceil(per_page);
 
     // 股票分页
-    let start_index = ((params.stocks_page - 1) * params.stocks_per_page) as usize;
-    let end_index = (start_index + params.stocks_per_page as usize).min(selected_tag.stocks.len());
-    let paged_stocks = selected_tag.stocks[start_index..end_index].to_vec();
+    let paged_stocks = stocks
+        .iter()
+        .skip(offset)
+        .take(per_page as usize)
+        .map(|&stock| project_stock(stock, fields))
+        .collect();
 
     StockListResult {
         stocks: paged_stocks,
         total_stocks,
         total_pages,
-        current_page: params.stocks_page,
+        current_page: page_number(offset, per_page),
+        next_cursor: None,
+        dataset_version: 0,
+    }
+}
+
+/// 解析查询中的标签引用，返回 (分类, 标签名称, 补充说明)
+fn resolve_tag_reference(
+    query: &TagStocksQuery,
+) -> CommandResult<(String, String, Option<String>)> {
+    if let Some(tag_id) = &query.tag_id {
+        return parse_tag_id(tag_id)
+            .map(|(category_name, item)| (category_name, item.name, item.detail))
+            .ok_or_else(|| CommandError::InvalidInput(format!("Invalid tag id: {}", tag_id)));
+    }
+    match (&query.category_name, &query.tag_name) {
+        (Some(category_name), Some(tag_name)) => Ok((
+            category_name.clone(),
+            tag_name.clone(),
+            query.tag_detail.clone(),
+        )),
+        _ => Err(CommandError::InvalidInput(
+            "Tag reference must have a tag id or a category and a name".to_string(),
+        )),
     }
 }
 
+/// 标签下的股票下标；include_children 为 true 时合并标签层级中所有子标签的股票
+/// 标签和子标签都不存在时返回 None
+pub fn tag_stock_ids(
+    index: &TagIndex,
+    taxonomy: &TagTaxonomy,
+    category_name: &str,
+    tag_name: &str,
+    tag_detail: Option<&str>,
+    include_children: bool,
+) -> Option<Vec<usize>> {
+    let tag = index.tag(category_name, tag_name, tag_detail);
+    let stock_ids = if include_children {
+        rollup_stock_ids(index, taxonomy, category_name, tag_name, tag_detail)
+    } else {
+        tag.map(|tag| tag.stock_ids.clone()).unwrap_or_default()
+    };
+    if tag.is_none() && stock_ids.is_empty() {
+        return None;
+    }
+    Some(stock_ids)
+}
+
+/// 按标签引用获取股票列表（在服务端排序和分页）
+pub fn get_tag_stock_list(
+    stock_data: &[StockCompanyInfo],
+    index: &TagIndex,
+    taxonomy: &TagTaxonomy,
+    query: &TagStocksQuery,
+) -> CommandResult<StockListResult> {
+    let (category_name, tag_name, tag_detail) = resolve_tag_reference(query)?;
+    let include_children = query.include_children;
+    let fingerprint = query_fingerprint(&(
+        &category_name,
+        &tag_name,
+        &tag_detail,
+        include_children,
+        query.sort_by,
+        query.descending,
+        query.collation,
+    ));
+    let offset = page_offset(
+        query.cursor.as_deref(),
+        index.version,
+        fingerprint,
+        query.page,
+        query.per_page,
+    )?;
+    let stock_ids = tag_stock_ids(
+        index,
+        taxonomy,
+        &category_name,
+        &tag_name,
+        tag_detail.as_deref(),
+        include_children,
+    )
+    .ok_or_else(|| {
+        CommandError::NotFound(format!(
+            "Tag {}",
+            tag_id(&category_name, &tag_name, tag_detail.as_deref())
+        ))
+    })?;
+
+    // 按更新时间排序时预先解析时间，无法解析的时间视为最早
+    let mut keyed: Vec<(&StockCompanyInfo, Option<i64>)> = stock_ids
+        .iter()
+        .filter_map(|&id| stock_data.get(id))
+        .map(|stock| {
+            let updated_at = (query.sort_by == StockSortKey::UpdatedAt)
+                .then(|| parse_timestamp(&stock.updated_at))
+                .flatten();
+            (stock, updated_at)
+        })
+        .collect();
+    keyed.sort_by(|(a, a_time), (b, b_time)| {
+        let ordering = match query.sort_by {
+            StockSortKey::Code => std::cmp::Ordering::Equal,
+            StockSortKey::Name => collate(&a.stock_name, &b.stock_name, query.collation),
+            StockSortKey::Exchange => a.exchange.cmp(&b.exchange),
+            StockSortKey::UpdatedAt => a_time.cmp(b_time),
+        }
+        .then_with(|| a.stock_code.cmp(&b.stock_code));
+        if query.descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    });
+    let stocks: Vec<&StockCompanyInfo> = keyed.into_iter().map(|(stock, _)| stock).collect();
+
+    let mut result = get_stock_list(
+        &stocks,
+        offset,
+        query.per_page,
+        query.stock_fields.as_deref(),
+    );
+    result.next_cursor = next_cursor(
+        index.version,
+        fingerprint,
+        offset,
+        query.per_page,
+        stocks.len(),
+    );
+    result.dataset_version = index.version;
+    Ok(result)
+}
+
 /// 计算标签统计信息
 pub fn calculate_tag_statistics(
     _stock_data: &[StockCompanyInfo],
@@ -471,3 +1497,232 @@ pub fn calculate_tag_statistics(
         valid_tags_count: valid_count,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(name: &str, detail: Option<&str>) -> TagItem {
+        TagItem {
+            name: name.to_string(),
+            detail: detail.map(str::to_string),
+        }
+    }
+
+    /// parse → serialize → parse 得到相同结果，且规范字符串再序列化不变
+    fn assert_round_trip(tags: &str) {
+        let parsed = parse_custom_tags_ordered(tags);
+        let serialized = serialize_custom_tags(&parsed);
+        let reparsed = parse_custom_tags_ordered(&serialized);
+        assert_eq!(
+            parsed, reparsed,
+            "round trip of {:?} via {:?}",
+            tags, serialized
+        );
+        assert_eq!(serialize_custom_tags(&reparsed), serialized);
+    }
+
+    #[test]
+    fn parse_preserves_order() {
+        let parsed = parse_custom_tags_ordered("行业:半导体{晶圆};概念:AI;行业:芯片;地区:上海");
+        let names: Vec<&str> = parsed
+            .categories
+            .iter()
+            .map(|category| category.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["行业", "概念", "地区"]);
+        assert_eq!(
+            parsed.categories[0].items,
+            vec![item("半导体", Some("晶圆")), item("芯片", None)]
+        );
+        assert_eq!(
+            serialize_custom_tags(&parsed),
+            "行业:半导体{晶圆};行业:芯片;概念:AI;地区:上海"
+        );
+    }
+
+    #[test]
+    fn parse_matches_unordered_parser() {
+        let tags = "概念: AI ;; 行业:半导体{ 晶圆 };无分隔符;:空分类;概念:;概念:a{b}c}";
+        let parsed = parse_custom_tags(tags);
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(
+            parsed["概念"],
+            vec![item("AI", None), item("a", Some("b}c"))]
+        );
+        assert_eq!(parsed["行业"], vec![item("半导体", Some("晶圆"))]);
+    }
+
+    #[test]
+    fn serialize_deduplicates_and_drops_empty() {
+        let parsed = ParsedTags {
+            categories: vec![
+                ParsedTagCategory {
+                    name: "概念".to_string(),
+                    items: vec![item("AI", None), item(" AI ", Some(" ")), item("", None)],
+                },
+                ParsedTagCategory {
+                    name: " ".to_string(),
+                    items: vec![item("x", None)],
+                },
+                ParsedTagCategory {
+                    name: "概念".to_string(),
+                    items: vec![item("AI", None), item("芯片", None)],
+                },
+            ],
+        };
+        assert_eq!(serialize_custom_tags(&parsed), "概念:AI;概念:芯片");
+    }
+
+    #[test]
+    fn serialize_escapes_special_chars() {
+        let mut parsed = ParsedTags::default();
+        parsed.push("a:b", item("x;y", Some("{1}")));
+        parsed.push("c", item("{z}", None));
+        parsed.push("c", item("w\\", Some("d")));
+        parsed.push("c", item("t:u", None));
+        let serialized = serialize_custom_tags(&parsed);
+        assert_eq!(serialized, r"a\:b:x\;y{\{1\}};c:\{z\};c:w\\{d};c:t:u");
+        assert_eq!(parse_custom_tags_ordered(&serialized), parsed);
+    }
+
+    #[test]
+    fn backslash_before_ordinary_char_is_literal() {
+        let parsed = parse_custom_tags_ordered("路径:C\\Data;概念:A\\");
+        assert_eq!(parsed.categories[0].items, vec![item("C\\Data", None)]);
+        assert_eq!(parsed.categories[1].items, vec![item("A\\", None)]);
+        assert_round_trip("路径:C\\Data;概念:A\\");
+    }
+
+    #[test]
+    fn round_trip_examples() {
+        for tags in [
+            "",
+            ";;",
+            "概念:AI",
+            "概念:AI;概念:AI;概念:AI{1}",
+            "行业:半导体{晶圆};概念:AI;行业:芯片",
+            "概念:a{}",
+            "概念:{b}",
+            "概念:{x{b}",
+            "概念:a{}{b}",
+            "概念:a{ }",
+            "概念:a{b}c}",
+            r"概念:a\{b\}",
+            r"概念:a\;b;c\:d:e",
+            "概念:\\",
+            r"概念:a\\\;b:c",
+            "概念: : ",
+            "a:b:c{d:e}",
+            "概念:x}{y",
+            "概念:多行\n文本{补充\n说明}",
+        ] {
+            assert_round_trip(tags);
+        }
+    }
+
+    #[test]
+    fn round_trip_generated() {
+        // 用特殊字符组合生成大量输入，覆盖转义、空白和大括号的各种排列
+        let alphabet = ['a', '中', ' ', ';', ':', '{', '}', '\\'];
+        let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+        for _ in 0..20_000 {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            let len = (seed % 12) as usize;
+            let tags: String = (0..len)
+                .map(|i| alphabet[((seed >> (i * 3 + 4)) % alphabet.len() as u64) as usize])
+                .collect();
+            assert_round_trip(&tags);
+        }
+    }
+
+    fn parsed_query(query: &str) -> String {
+        SearchQuery::parse(query).unwrap().unwrap().expr.to_string()
+    }
+
+    #[test]
+    fn query_precedence() {
+        assert_eq!(parsed_query("a b OR c"), r#"(("a" AND "b") OR "c")"#);
+        assert_eq!(parsed_query("a OR b c"), r#"("a" OR ("b" AND "c"))"#);
+        assert_eq!(parsed_query("NOT a b"), r#"(NOT "a" AND "b")"#);
+        assert_eq!(
+            parsed_query("-a || b && !c"),
+            r#"(NOT "a" OR ("b" AND NOT "c"))"#
+        );
+        assert_eq!(parsed_query("(a OR b) c"), r#"(("a" OR "b") AND "c")"#);
+        assert_eq!(
+            parsed_query(r#"TAG:"半 导体" detail:/芯.+/"#),
+            r#"(tag:"半 导体" AND detail:/芯.+/)"#
+        );
+        assert!(SearchQuery::parse("   ").unwrap().is_none());
+    }
+
+    #[test]
+    fn query_errors() {
+        let cases = [
+            ("AI:x", QueryErrorKind::UnknownField, 0),
+            ("(a", QueryErrorKind::UnmatchedParen, 0),
+            ("a)", QueryErrorKind::UnmatchedParen, 1),
+            ("a AND", QueryErrorKind::UnexpectedEnd, 5),
+            ("OR a", QueryErrorKind::UnexpectedToken, 0),
+            ("a \"bc", QueryErrorKind::UnclosedQuote, 2),
+            ("/abc", QueryErrorKind::UnclosedRegex, 0),
+            ("/(/", QueryErrorKind::InvalidRegex, 0),
+            ("tag:", QueryErrorKind::EmptyValue, 4),
+            ("tag: x", QueryErrorKind::EmptyValue, 4),
+            ("\"\"", QueryErrorKind::EmptyValue, 0),
+        ];
+        for (query, kind, position) in cases {
+            let error = SearchQuery::parse(query).unwrap_err();
+            assert_eq!(
+                (error.kind, error.position),
+                (kind, position),
+                "{:?}",
+                query
+            );
+        }
+    }
+
+    #[test]
+    fn updated_at_orders_by_parsed_time() {
+        let stock = |code: &str, tags: &str, updated_at: &str| StockCompanyInfo {
+            updated_at: updated_at.to_string(),
+            ..test_stock(code, tags)
+        };
+        // 按字符串比较 +08:00 的时间更晚，实际更早
+        let stock_data = vec![
+            stock("600001", "概念:AI", "2024-05-01T09:00:00+08:00"),
+            stock("600002", "概念:芯片", "2024-05-01T02:00:00Z"),
+            stock("600003", "概念:机器人", "未知"),
+        ];
+        let index = TagIndex::build_with_synonyms(&stock_data, Default::default());
+        let key = |name: &str| {
+            TagOrderKey::of(
+                &stock_data,
+                index.tag("概念", name, None).unwrap(),
+                CollationOrder::default(),
+            )
+            .updated_at
+        };
+        assert!(key("芯片") > key("AI"));
+        assert_eq!(key("机器人"), None);
+    }
+
+    #[test]
+    fn pinyin_fallback_only_in_fuzzy_mode() {
+        let forms = PinyinForms::of("新能源");
+        let pattern = QueryPattern::text("XNY");
+        assert!(pattern
+            .find("新能源", Some(&forms), MatchMode::Fuzzy)
+            .is_some());
+        assert!(pattern
+            .find("新能源", Some(&forms), MatchMode::Exact)
+            .is_none());
+        assert_eq!(
+            SearchQuery::parse("xny").unwrap().unwrap().match_mode,
+            MatchMode::Exact
+        );
+    }
+}
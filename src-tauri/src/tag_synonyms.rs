@@ -0,0 +1,382 @@
+use crate::fuzzy_match::fuzzy_match;
+use crate::pinyin_index::{is_pinyin_query, PinyinForms};
+use crate::stock_store::write_file_atomic;
+use crate::tag_index::TagIndex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// 内置的同义词词典，与仓库根目录的 tag-synonyms.json 保持一致
+const BUILTIN_SYNONYMS: &str = include_str!("../../tag-synonyms.json");
+
+/// 数据目录中保存同义词词典的文件名
+pub const SYNONYMS_FILE_NAME: &str = "tag-synonyms.json";
+
+/// 词典文件格式
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct SynonymFile {
+    #[serde(default)]
+    description: Option<String>,
+    synonyms: Vec<SynonymEntry>,
+}
+
+/// 一组同义词：规范名称及其别名
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SynonymEntry {
+    pub canonical: String,
+    pub aliases: Vec<String>,
+    /// 只在该分类下生效（为空时对所有分类生效）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+}
+
+/// 词典概要信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SynonymInfo {
+    /// 词典来源文件，内置词典为 None
+    pub source: Option<String>,
+    pub description: Option<String>,
+    pub entries: Vec<SynonymEntry>,
+    pub alias_count: u32,
+}
+
+/// 别名建议
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AliasSuggestion {
+    pub category_name: String,
+    /// 建议的规范名称（股票数较多的一方）
+    pub canonical: String,
+    pub canonical_count: u32,
+    pub alias: String,
+    pub alias_count: u32,
+    /// 相似度，取值 0 ~ 1
+    pub score: f64,
+    pub reason: String,
+}
+
+/// 标签同义词词典 - 别名统一转为小写后查找
+#[derive(Debug, Clone, Default)]
+pub struct TagSynonyms {
+    source: Option<String>,
+    description: Option<String>,
+    entries: Vec<SynonymEntry>,
+    /// 对所有分类生效的 别名 → 规范名称
+    global: HashMap<String, String>,
+    /// 分类 → 别名 → 规范名称
+    by_category: HashMap<String, HashMap<String, String>>,
+}
+
+fn alias_key(name: &str) -> String {
+    name.trim().to_lowercase()
+}
+
+impl TagSynonyms {
+    /// 加载内置词典
+    pub fn builtin() -> Self {
+        Self::from_json(BUILTIN_SYNONYMS, None).expect("built-in tag synonyms must be valid")
+    }
+
+    /// 从词典 JSON 构建
+    pub fn from_json(json: &str, source: Option<String>) -> Result<Self, String> {
+        let file: SynonymFile = serde_json::from_str(json)
+            .map_err(|e| format!("Failed to parse tag synonyms: {}", e))?;
+        let mut synonyms = Self {
+            source,
+            description: file.description,
+            ..Self::default()
+        };
+        for entry in file.synonyms {
+            synonyms.add_entry(entry)?;
+        }
+        Ok(synonyms)
+    }
+
+    /// 从文件加载词典
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read tag synonyms {}: {}", path, e))?;
+        Self::from_json(&json, Some(path.to_string()))
+    }
+
+    /// 文件存在时从文件加载，否则使用内置词典并以该文件作为保存位置
+    pub fn load_or_builtin(path: &Path) -> Result<Self, String> {
+        let path_str = path.to_string_lossy().to_string();
+        if path.exists() {
+            Self::from_file(&path_str)
+        } else {
+            Ok(Self {
+                source: Some(path_str),
+                ..Self::builtin()
+            })
+        }
+    }
+
+    /// 按原来源重新加载
+    pub fn reload(&self) -> Result<Self, String> {
+        match &self.source {
+            Some(path) => Self::load_or_builtin(Path::new(path)),
+            None => Ok(Self::builtin()),
+        }
+    }
+
+    /// 将词典写回来源文件（先写临时文件再替换），内置词典没有来源文件时跳过
+    pub fn save(&self) -> Result<(), String> {
+        let Some(path) = &self.source else {
+            return Ok(());
+        };
+        let file = SynonymFile {
+            description: self.description.clone(),
+            synonyms: self.entries.clone(),
+        };
+        let json = serde_json::to_string_pretty(&file)
+            .map_err(|e| format!("Failed to serialize tag synonyms: {}", e))?;
+        write_file_atomic(Path::new(path), json)
+            .map_err(|e| format!("Failed to write tag synonyms {}: {}", path, e))
+    }
+
+    /// 添加一组同义词；规范名称已存在（同一作用范围）时合并别名
+    pub fn add_entry(&mut self, entry: SynonymEntry) -> Result<(), String> {
+        let canonical = entry.canonical.trim().to_string();
+        if canonical.is_empty() {
+            return Err("Invalid tag synonyms: canonical name is empty".to_string());
+        }
+
+        let lookup = match &entry.category {
+            Some(category) => self.by_category.entry(category.clone()).or_default(),
+            None => &mut self.global,
+        };
+        if let Some(existing) = lookup.get(&alias_key(&canonical)) {
+            return Err(format!(
+                "Invalid tag synonyms: {} is already an alias of {}",
+                canonical, existing
+            ));
+        }
+        let mut aliases = Vec::new();
+        for alias in entry.aliases {
+            let alias = alias.trim().to_string();
+            let key = alias_key(&alias);
+            if alias.is_empty() || key == alias_key(&canonical) {
+                continue;
+            }
+            // 同一别名不能指向两个规范名称，规范名称本身也不能再作为别名
+            match lookup.get(&key) {
+                Some(existing) if *existing != canonical => {
+                    return Err(format!(
+                        "Invalid tag synonyms: alias {} already maps to {}",
+                        alias, existing
+                    ));
+                }
+                Some(_) => continue,
+                None => {}
+            }
+            if lookup.values().any(|existing| alias_key(existing) == key) {
+                return Err(format!(
+                    "Invalid tag synonyms: {} is already a canonical name",
+                    alias
+                ));
+            }
+            lookup.insert(key, canonical.clone());
+            aliases.push(alias);
+        }
+
+        match self
+            .entries
+            .iter_mut()
+            .find(|existing| existing.canonical == canonical && existing.category == entry.category)
+        {
+            Some(existing) => existing.aliases.extend(aliases),
+            None => self.entries.push(SynonymEntry {
+                canonical,
+                aliases,
+                category: entry.category,
+            }),
+        }
+        Ok(())
+    }
+
+    /// 返回标签的规范名称（分类词典优先于全局词典），不是别名时返回 None
+    pub fn canonical(&self, category_name: &str, tag_name: &str) -> Option<&str> {
+        let key = alias_key(tag_name);
+        self.by_category
+            .get(category_name)
+            .and_then(|lookup| lookup.get(&key))
+            .or_else(|| self.global.get(&key))
+            .map(String::as_str)
+    }
+
+    /// 判断两个名称是否已在同一组同义词中
+    fn is_same(&self, category_name: &str, a: &str, b: &str) -> bool {
+        let a = self.canonical(category_name, a).unwrap_or(a);
+        let b = self.canonical(category_name, b).unwrap_or(b);
+        alias_key(a) == alias_key(b)
+    }
+
+    /// 词典概要信息
+    pub fn info(&self) -> SynonymInfo {
+        SynonymInfo {
+            source: self.source.clone(),
+            description: self.description.clone(),
+            entries: self.entries.clone(),
+            alias_count: self
+                .entries
+                .iter()
+                .map(|entry| entry.aliases.len() as u32)
+                .sum(),
+        }
+    }
+}
+
+/// 根据名称相似度和拼音简写，建议可以合并的标签（同一分类内，已是同义词的除外）
+pub fn suggest_aliases(
+    index: &TagIndex,
+    category_name: Option<&str>,
+    min_score: f64,
+    limit: u32,
+) -> Vec<AliasSuggestion> {
+    let mut suggestions = Vec::new();
+    let mut categories: Vec<&String> = index
+        .categories
+        .keys()
+        .filter(|category| category_name.is_none_or(|name| *category == name))
+        .collect();
+    categories.sort();
+
+    for category in categories {
+        // 标签名称 → (股票数, 拼音)
+        let mut names: HashMap<&str, (u32, &PinyinForms)> = HashMap::new();
+        for tag in index.categories[category].values() {
+            names
+                .entry(tag.name.as_str())
+                .or_insert((0, &tag.name_pinyin))
+                .0 += tag.stock_ids.len() as u32;
+        }
+        let mut names: Vec<(&str, u32, &PinyinForms)> = names
+            .into_iter()
+            .map(|(name, (count, pinyin))| (name, count, pinyin))
+            .collect();
+        names.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+
+        for (i, &(canonical, canonical_count, canonical_pinyin)) in names.iter().enumerate() {
+            for &(alias, alias_count, _) in &names[i + 1..] {
+                if index.synonyms.is_same(category, canonical, alias) {
+                    continue;
+                }
+                let Some((score, reason)) = alias_similarity(canonical, canonical_pinyin, alias)
+                else {
+                    continue;
+                };
+                if score < min_score {
+                    continue;
+                }
+                suggestions.push(AliasSuggestion {
+                    category_name: category.clone(),
+                    canonical: canonical.to_string(),
+                    canonical_count,
+                    alias: alias.to_string(),
+                    alias_count,
+                    score,
+                    reason,
+                });
+            }
+        }
+    }
+
+    suggestions.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| {
+                (b.canonical_count + b.alias_count).cmp(&(a.canonical_count + a.alias_count))
+            })
+            .then_with(|| a.alias.cmp(&b.alias))
+    });
+    suggestions.truncate(limit as usize);
+    suggestions
+}
+
+/// 两个标签名称的相似度：拼音简写完全一致，或一方可由另一方少量增删改得到
+fn alias_similarity(
+    canonical: &str,
+    canonical_pinyin: &PinyinForms,
+    alias: &str,
+) -> Option<(f64, String)> {
+    if is_pinyin_query(alias) && canonical_pinyin.initials == alias.to_lowercase() {
+        return Some((0.9, "拼音简写相同".to_string()));
+    }
+
+    // 较短的名称作为查询词，在较长的名称中做容错匹配
+    let (short, long) = if canonical.chars().count() <= alias.chars().count() {
+        (canonical, alias)
+    } else {
+        (alias, canonical)
+    };
+    if short.chars().count() < 2 {
+        return None;
+    }
+    let found = fuzzy_match(&short.to_lowercase(), long)?;
+    let coverage = short.chars().count() as f64 / long.chars().count() as f64;
+    Some((found.score * coverage, "名称相近".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(canonical: &str, aliases: &[&str], category: Option<&str>) -> SynonymEntry {
+        SynonymEntry {
+            canonical: canonical.to_string(),
+            aliases: aliases.iter().map(|alias| alias.to_string()).collect(),
+            category: category.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn rejects_conflicting_aliases() {
+        let mut synonyms = TagSynonyms::default();
+        synonyms
+            .add_entry(entry("新能源汽车", &["新能源车", "NEV"], None))
+            .unwrap();
+
+        // 同一别名指向另一个规范名称
+        assert!(synonyms
+            .add_entry(entry("电动车", &["nev"], None))
+            .unwrap_err()
+            .contains("already maps to"));
+        // 别名不能再作为规范名称
+        assert!(synonyms
+            .add_entry(entry("新能源车", &["电车"], None))
+            .unwrap_err()
+            .contains("already an alias"));
+        // 规范名称不能再作为别名
+        assert!(synonyms
+            .add_entry(entry("电动汽车", &["新能源汽车"], None))
+            .unwrap_err()
+            .contains("already a canonical name"));
+        assert!(synonyms.add_entry(entry(" ", &["x"], None)).is_err());
+    }
+
+    #[test]
+    fn merges_entries_and_scopes_by_category() {
+        let mut synonyms = TagSynonyms::default();
+        synonyms
+            .add_entry(entry("人工智能", &["AI", "人工智能"], None))
+            .unwrap();
+        // 同一规范名称再次添加时合并别名，已有的别名跳过
+        synonyms
+            .add_entry(entry("人工智能", &["ai", "AIGC"], None))
+            .unwrap();
+        assert_eq!(synonyms.info().entries.len(), 1);
+        assert_eq!(synonyms.info().alias_count, 2);
+        assert_eq!(synonyms.canonical("概念", " Aigc "), Some("人工智能"));
+        assert_eq!(synonyms.canonical("概念", "人工智能"), None);
+
+        // 分类词典与全局词典互不冲突，查找时分类词典优先
+        synonyms
+            .add_entry(entry("安徽", &["AI"], Some("地区")))
+            .unwrap();
+        assert_eq!(synonyms.canonical("地区", "ai"), Some("安徽"));
+        assert_eq!(synonyms.canonical("地区", "AIGC"), Some("人工智能"));
+        assert_eq!(synonyms.canonical("概念", "AI"), Some("人工智能"));
+        assert!(synonyms.is_same("概念", "AI", "aigc"));
+        assert!(!synonyms.is_same("地区", "AI", "AIGC"));
+    }
+}
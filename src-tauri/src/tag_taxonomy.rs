@@ -0,0 +1,336 @@
+use crate::stock_data::*;
+use crate::tag_index::TagIndex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+
+/// 内置的标签层级，与仓库根目录的 tag-taxonomy.json 保持一致
+const BUILTIN_TAXONOMY: &str = include_str!("../../tag-taxonomy.json");
+
+/// 层级文件格式
+#[derive(Debug, Clone, Deserialize)]
+struct TaxonomyFile {
+    #[serde(default)]
+    description: Option<String>,
+    categories: Vec<TaxonomyCategoryDef>,
+}
+
+/// 单个分类下的层级定义
+#[derive(Debug, Clone, Deserialize)]
+struct TaxonomyCategoryDef {
+    category: String,
+    nodes: Vec<TaxonomyNodeDef>,
+}
+
+/// 层级节点定义（按标签名称匹配，忽略补充说明）
+#[derive(Debug, Clone, Deserialize)]
+struct TaxonomyNodeDef {
+    tag: String,
+    #[serde(default)]
+    children: Vec<TaxonomyNodeDef>,
+}
+
+/// 标签层级概要信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaxonomyInfo {
+    /// 层级来源文件，内置层级为 None
+    pub source: Option<String>,
+    pub description: Option<String>,
+    pub categories: Vec<String>,
+    /// 声明了父标签的标签数量
+    pub link_count: u32,
+}
+
+/// 标签层级 - 每个分类下维护 子标签 → 父标签 和 父标签 → 子标签 两个映射
+#[derive(Debug, Clone, Default)]
+pub struct TagTaxonomy {
+    source: Option<String>,
+    description: Option<String>,
+    /// 分类 → 子标签 → 父标签
+    parents: HashMap<String, HashMap<String, String>>,
+    /// 分类 → 父标签 → 子标签（按文件中的顺序）
+    children: HashMap<String, HashMap<String, Vec<String>>>,
+}
+
+impl TagTaxonomy {
+    /// 加载内置层级
+    pub fn builtin() -> Self {
+        Self::from_json(BUILTIN_TAXONOMY, None).expect("built-in tag taxonomy must be valid")
+    }
+
+    /// 从层级 JSON 构建
+    pub fn from_json(json: &str, source: Option<String>) -> Result<Self, String> {
+        let file: TaxonomyFile = serde_json::from_str(json)
+            .map_err(|e| format!("Failed to parse tag taxonomy: {}", e))?;
+
+        let mut taxonomy = Self {
+            source,
+            description: file.description,
+            ..Self::default()
+        };
+        for category in file.categories {
+            let mut seen: BTreeSet<String> = BTreeSet::new();
+            let mut pending: Vec<(Option<String>, TaxonomyNodeDef)> = category
+                .nodes
+                .into_iter()
+                .map(|node| (None, node))
+                .collect();
+            while let Some((parent, node)) = pending.pop() {
+                let tag = node.tag.trim().to_string();
+                if tag.is_empty() {
+                    return Err(format!(
+                        "Invalid tag taxonomy: empty tag in category {}",
+                        category.category
+                    ));
+                }
+                // 每个标签只能出现一次，保证层级是一棵树（不会成环，也不会有多个父标签）
+                if !seen.insert(tag.clone()) {
+                    return Err(format!(
+                        "Invalid tag taxonomy: tag {} appears more than once in category {}",
+                        tag, category.category
+                    ));
+                }
+                if let Some(parent) = parent {
+                    taxonomy
+                        .parents
+                        .entry(category.category.clone())
+                        .or_default()
+                        .insert(tag.clone(), parent.clone());
+                    taxonomy
+                        .children
+                        .entry(category.category.clone())
+                        .or_default()
+                        .entry(parent)
+                        .or_default()
+                        .push(tag.clone());
+                }
+                pending.extend(
+                    node.children
+                        .into_iter()
+                        .rev()
+                        .map(|child| (Some(tag.clone()), child)),
+                );
+            }
+        }
+        Ok(taxonomy)
+    }
+
+    /// 从文件加载层级
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read tag taxonomy {}: {}", path, e))?;
+        Self::from_json(&json, Some(path.to_string()))
+    }
+
+    /// 按原来源重新加载（文件层级重新读取，内置层级保持不变）
+    pub fn reload(&self) -> Result<Self, String> {
+        match &self.source {
+            Some(path) => Self::from_file(path),
+            None => Ok(Self::builtin()),
+        }
+    }
+
+    /// 层级概要信息
+    pub fn info(&self) -> TaxonomyInfo {
+        let mut categories: Vec<String> = self.parents.keys().cloned().collect();
+        categories.sort();
+        TaxonomyInfo {
+            source: self.source.clone(),
+            description: self.description.clone(),
+            categories,
+            link_count: self.parents.values().map(|links| links.len() as u32).sum(),
+        }
+    }
+
+    /// 获取标签的所有后代标签（深度优先，不含自身）
+    pub fn descendants(&self, category_name: &str, tag_name: &str) -> Vec<String> {
+        let Some(children) = self.children.get(category_name) else {
+            return Vec::new();
+        };
+        let mut descendants = Vec::new();
+        let mut pending: Vec<&String> = children
+            .get(tag_name)
+            .map(|tags| tags.iter().rev().collect())
+            .unwrap_or_default();
+        while let Some(tag) = pending.pop() {
+            descendants.push(tag.clone());
+            if let Some(grandchildren) = children.get(tag) {
+                pending.extend(grandchildren.iter().rev());
+            }
+        }
+        descendants
+    }
+
+    /// 构建分类的标签树：未在层级中声明父标签的标签作为根节点
+    /// 每个节点的股票数为其子树中股票的并集，同一只股票只计一次
+    pub fn build_tree(&self, index: &TagIndex, category_name: &str) -> Vec<TagTreeNode> {
+        // 标签名称 → 直接带有该标签（任意补充说明）的股票
+        let mut direct: HashMap<&str, BTreeSet<usize>> = HashMap::new();
+        if let Some(tags) = index.category(category_name) {
+            for tag in tags.values() {
+                direct
+                    .entry(tag.name.as_str())
+                    .or_default()
+                    .extend(tag.stock_ids.iter().copied());
+            }
+        }
+
+        let parents = self.parents.get(category_name);
+        let children = self.children.get(category_name);
+        let mut roots: BTreeSet<&str> = direct
+            .keys()
+            .copied()
+            .filter(|name| parents.is_none_or(|parents| !parents.contains_key(*name)))
+            .collect();
+        if let Some(children) = children {
+            roots.extend(
+                children
+                    .keys()
+                    .map(String::as_str)
+                    .filter(|name| parents.is_none_or(|parents| !parents.contains_key(*name))),
+            );
+        }
+
+        let mut tree: Vec<TagTreeNode> = roots
+            .into_iter()
+            .filter_map(|name| {
+                self.build_node(name, &direct, children)
+                    .map(|(node, _)| node)
+            })
+            .collect();
+        sort_tree_nodes(&mut tree);
+        tree
+    }
+
+    fn build_node(
+        &self,
+        name: &str,
+        direct: &HashMap<&str, BTreeSet<usize>>,
+        children: Option<&HashMap<String, Vec<String>>>,
+    ) -> Option<(TagTreeNode, BTreeSet<usize>)> {
+        let own = direct.get(name).cloned().unwrap_or_default();
+        let direct_count = own.len() as u32;
+        let mut stock_ids = own;
+
+        let mut child_nodes = Vec::new();
+        for child in children
+            .and_then(|children| children.get(name))
+            .into_iter()
+            .flatten()
+        {
+            if let Some((node, child_ids)) = self.build_node(child, direct, children) {
+                stock_ids.extend(child_ids);
+                child_nodes.push(node);
+            }
+        }
+        if stock_ids.is_empty() {
+            // 整棵子树都没有股票时不展示
+            return None;
+        }
+        sort_tree_nodes(&mut child_nodes);
+
+        Some((
+            TagTreeNode {
+                name: name.to_string(),
+                count: stock_ids.len() as u32,
+                direct_count,
+                children: child_nodes,
+            },
+            stock_ids,
+        ))
+    }
+}
+
+/// 兄弟节点按股票数降序，数量相同时按名称排序
+fn sort_tree_nodes(nodes: &mut [TagTreeNode]) {
+    nodes.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+}
+
+/// 标签及其所有后代标签的股票下标（升序，已去重）
+pub fn rollup_stock_ids(
+    index: &TagIndex,
+    taxonomy: &TagTaxonomy,
+    category_name: &str,
+    tag_name: &str,
+    tag_detail: Option<&str>,
+) -> Vec<usize> {
+    let mut stock_ids: BTreeSet<usize> = index
+        .tag(category_name, tag_name, tag_detail)
+        .map(|tag| tag.stock_ids.iter().copied().collect())
+        .unwrap_or_default();
+
+    let descendants = taxonomy.descendants(category_name, tag_name);
+    if let Some(tags) = index.category(category_name) {
+        for tag in tags.values() {
+            if descendants.contains(&tag.name) {
+                stock_ids.extend(tag.stock_ids.iter().copied());
+            }
+        }
+    }
+    stock_ids.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TAXONOMY: &str = r#"{
+        "categories": [{
+            "category": "行业",
+            "nodes": [{
+                "tag": "半导体",
+                "children": [{ "tag": "芯片设计", "children": [{ "tag": "GPU" }] }]
+            }]
+        }]
+    }"#;
+
+    #[test]
+    fn parses_parent_and_child_links() {
+        let taxonomy = TagTaxonomy::from_json(TAXONOMY, None).unwrap();
+        assert_eq!(
+            taxonomy.descendants("行业", "半导体"),
+            vec!["芯片设计", "GPU"]
+        );
+        assert!(taxonomy.descendants("行业", "GPU").is_empty());
+        assert_eq!(taxonomy.info().link_count, 2);
+
+        let repeated = r#"{"categories": [{"category": "行业", "nodes": [
+            {"tag": "半导体", "children": [{"tag": "半导体"}]}
+        ]}]}"#;
+        assert!(TagTaxonomy::from_json(repeated, None).is_err());
+    }
+
+    #[test]
+    fn rollup_counts_each_stock_once() {
+        let taxonomy = TagTaxonomy::from_json(TAXONOMY, None).unwrap();
+        let stock_data = vec![
+            // 同时带有父标签和子标签
+            test_stock("600001", "行业:半导体;行业:芯片设计"),
+            // 只带有子标签
+            test_stock("600002", "行业:GPU"),
+            test_stock("600003", "行业:芯片设计{EDA};行业:GPU"),
+            test_stock("600004", "行业:银行"),
+        ];
+        let index = TagIndex::build_with_synonyms(&stock_data, Default::default());
+
+        assert_eq!(
+            rollup_stock_ids(&index, &taxonomy, "行业", "半导体", None),
+            vec![0, 1, 2]
+        );
+        assert_eq!(
+            rollup_stock_ids(&index, &taxonomy, "行业", "芯片设计", None),
+            vec![0, 1, 2]
+        );
+
+        let tree = taxonomy.build_tree(&index, "行业");
+        let semiconductor = &tree[0];
+        assert_eq!(semiconductor.name, "半导体");
+        assert_eq!((semiconductor.count, semiconductor.direct_count), (3, 1));
+        let design = &semiconductor.children[0];
+        assert_eq!((design.count, design.direct_count), (3, 2));
+        assert_eq!(
+            (design.children[0].count, design.children[0].direct_count),
+            (2, 2)
+        );
+        assert_eq!(tree[1].name, "银行");
+    }
+}
@@ -0,0 +1,314 @@
+use crate::stock_store::write_file_atomic;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// 内置的验证规则，与仓库根目录的 tag-validation-rules.json 保持一致
+const BUILTIN_RULES: &str = include_str!("../../tag-validation-rules.json");
+
+/// 数据目录中保存自定义验证规则的文件名
+pub const VALIDATION_RULES_FILE_NAME: &str = "tag-validation-rules.json";
+
+/// 标签验证状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ValidationStatus {
+    Valid,
+    Warning,
+    Error,
+    Special,
+}
+
+impl ValidationStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ValidationStatus::Valid => "valid",
+            ValidationStatus::Warning => "warning",
+            ValidationStatus::Error => "error",
+            ValidationStatus::Special => "special",
+        }
+    }
+
+    pub fn weight(&self) -> u8 {
+        match self {
+            ValidationStatus::Error => 4,
+            ValidationStatus::Warning => 3,
+            ValidationStatus::Special => 2,
+            ValidationStatus::Valid => 1,
+        }
+    }
+}
+
+/// 验证结果的原因代码
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ValidationReason {
+    /// 格式正确
+    Ok,
+    /// 标签名称为空
+    EmptyName,
+    /// 标签名称包含 : { }
+    IllegalCharacter,
+    /// 标签名称包含换行符或制表符
+    ControlCharacter,
+    /// 标签名称前后有空格
+    SurroundingWhitespace,
+    /// 标签名称以 - _ 等符号开头
+    LeadingSymbol,
+    /// 无意义的单字标签
+    MeaninglessSingleChar,
+    /// 补充说明包含 : { }
+    DetailIllegalCharacter,
+    /// 补充说明前后有空格
+    DetailSurroundingWhitespace,
+    /// 补充说明超过最大长度
+    DetailExceedsMaxLength,
+    /// 白名单中的单字标签
+    SpecialSingleChar,
+    /// 标签名称过长
+    NameTooLong,
+    /// 补充说明过长
+    DetailTooLong,
+    /// 标签名称过短
+    NameTooShort,
+}
+
+impl ValidationReason {
+    /// 提示信息，前端直接显示
+    pub fn message(&self) -> &'static str {
+        match self {
+            ValidationReason::Ok => "标签格式正确",
+            ValidationReason::EmptyName => "标签名称不能为空",
+            ValidationReason::IllegalCharacter => "标签名称包含非法字符",
+            ValidationReason::ControlCharacter => "标签名称不能包含换行符或制表符",
+            ValidationReason::SurroundingWhitespace => "标签名称不能以空格开头或结尾",
+            ValidationReason::LeadingSymbol => "标签名称不能以特殊字符开头",
+            ValidationReason::MeaninglessSingleChar => "无意义的单字标签",
+            ValidationReason::DetailIllegalCharacter => "补充说明包含非法字符",
+            ValidationReason::DetailSurroundingWhitespace => "补充说明前后有多余空格",
+            ValidationReason::DetailExceedsMaxLength => "补充说明超过最大长度",
+            ValidationReason::SpecialSingleChar => "重要的行业标签",
+            ValidationReason::NameTooLong => "标签名称过长，建议缩短",
+            ValidationReason::DetailTooLong => "标签详情过长，建议精简",
+            ValidationReason::NameTooShort => "标签名称过短，可能无意义",
+        }
+    }
+}
+
+/// 单个标签的验证结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagValidationResult {
+    pub status: ValidationStatus,
+    pub reason: ValidationReason,
+    pub message: String,
+}
+
+impl TagValidationResult {
+    fn new(status: ValidationStatus, reason: ValidationReason) -> Self {
+        Self {
+            status,
+            reason,
+            message: reason.message().to_string(),
+        }
+    }
+}
+
+/// 标签验证规则 - 白名单和长度阈值均为可配置数据
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidationRules {
+    #[serde(default)]
+    pub description: Option<String>,
+    /// 有意义的单字标签白名单（按用途分组）
+    pub special_single_chars: BTreeMap<String, Vec<String>>,
+    /// 无意义的单字标签（按小写比较）
+    pub meaningless_single_chars: Vec<String>,
+    /// 标签名称不允许的开头字符
+    pub forbidden_leading_chars: Vec<String>,
+    /// 标签名称超过该字符数时警告
+    pub name_warning_length: usize,
+    /// 补充说明超过该字符数时警告
+    pub detail_warning_length: usize,
+    /// 补充说明超过该字符数时报错
+    pub detail_max_length: usize,
+}
+
+impl ValidationRules {
+    /// 加载内置规则
+    pub fn builtin() -> Self {
+        Self::from_json(BUILTIN_RULES).expect("built-in validation rules must be valid")
+    }
+
+    /// 从 JSON 解析规则
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|e| format!("Failed to parse validation rules: {}", e))
+    }
+
+    /// 文件存在时从文件加载规则，否则使用内置规则
+    pub fn load_or_builtin(path: &Path) -> Result<Self, String> {
+        if !path.exists() {
+            return Ok(Self::builtin());
+        }
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read validation rules {}: {}", path.display(), e))?;
+        Self::from_json(&json)
+    }
+
+    /// 将规则写入文件（先写临时文件再替换）
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize validation rules: {}", e))?;
+        write_file_atomic(path, json)
+            .map_err(|e| format!("Failed to write validation rules {}: {}", path.display(), e))
+    }
+
+    fn is_special_single_char(&self, name: &str) -> bool {
+        self.special_single_chars
+            .values()
+            .any(|chars| chars.iter().any(|c| c == name))
+    }
+
+    fn is_meaningless_single_char(&self, name: &str) -> bool {
+        let lower = name.to_lowercase();
+        self.meaningless_single_chars.contains(&lower)
+    }
+
+    fn has_forbidden_leading_char(&self, name: &str) -> bool {
+        self.forbidden_leading_chars
+            .iter()
+            .any(|prefix| !prefix.is_empty() && name.starts_with(prefix.as_str()))
+    }
+}
+
+impl Default for ValidationRules {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+fn has_structural_chars(text: &str) -> bool {
+    text.contains(':') || text.contains('{') || text.contains('}')
+}
+
+/// 按规则验证标签，错误优先于特殊标记，特殊标记优先于警告
+pub fn evaluate_tag(
+    rules: &ValidationRules,
+    tag_name: &str,
+    tag_detail: Option<&str>,
+) -> TagValidationResult {
+    let trimmed_name = tag_name.trim();
+    let name_length = trimmed_name.chars().count();
+
+    // 1. 标签名称错误
+    let name_error = if trimmed_name.is_empty() {
+        Some(ValidationReason::EmptyName)
+    } else if has_structural_chars(tag_name) {
+        Some(ValidationReason::IllegalCharacter)
+    } else if tag_name.contains(['\n', '\t', '\r']) {
+        Some(ValidationReason::ControlCharacter)
+    } else if tag_name != trimmed_name {
+        Some(ValidationReason::SurroundingWhitespace)
+    } else if rules.has_forbidden_leading_char(trimmed_name) {
+        Some(ValidationReason::LeadingSymbol)
+    } else if name_length == 1 && rules.is_meaningless_single_char(trimmed_name) {
+        Some(ValidationReason::MeaninglessSingleChar)
+    } else {
+        None
+    };
+    if let Some(reason) = name_error {
+        return TagValidationResult::new(ValidationStatus::Error, reason);
+    }
+
+    // 2. 补充说明错误
+    if let Some(detail) = tag_detail {
+        let detail_error = if has_structural_chars(detail) {
+            Some(ValidationReason::DetailIllegalCharacter)
+        } else if detail != detail.trim() {
+            Some(ValidationReason::DetailSurroundingWhitespace)
+        } else if detail.chars().count() > rules.detail_max_length {
+            Some(ValidationReason::DetailExceedsMaxLength)
+        } else {
+            None
+        };
+        if let Some(reason) = detail_error {
+            return TagValidationResult::new(ValidationStatus::Error, reason);
+        }
+    }
+
+    // 3. 有意义的单字标签
+    if name_length == 1 && rules.is_special_single_char(trimmed_name) {
+        return TagValidationResult::new(
+            ValidationStatus::Special,
+            ValidationReason::SpecialSingleChar,
+        );
+    }
+
+    // 4. 警告
+    if name_length > rules.name_warning_length {
+        return TagValidationResult::new(ValidationStatus::Warning, ValidationReason::NameTooLong);
+    }
+    if tag_detail.is_some_and(|detail| detail.chars().count() > rules.detail_warning_length) {
+        return TagValidationResult::new(
+            ValidationStatus::Warning,
+            ValidationReason::DetailTooLong,
+        );
+    }
+    if name_length < 2 {
+        return TagValidationResult::new(ValidationStatus::Warning, ValidationReason::NameTooShort);
+    }
+
+    TagValidationResult::new(ValidationStatus::Valid, ValidationReason::Ok)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evaluates_builtin_rules() {
+        let rules = ValidationRules::builtin();
+        let reason = |name: &str, detail: Option<&str>| evaluate_tag(&rules, name, detail).reason;
+        assert_eq!(reason("芯片", None), ValidationReason::Ok);
+        assert_eq!(reason("铜", None), ValidationReason::SpecialSingleChar);
+        assert_eq!(reason("a", None), ValidationReason::MeaninglessSingleChar);
+        assert_eq!(reason("-芯片", None), ValidationReason::LeadingSymbol);
+        assert_eq!(
+            reason("芯片", Some(" 设计")),
+            ValidationReason::DetailSurroundingWhitespace
+        );
+        assert_eq!(
+            reason(&"长".repeat(21), None),
+            ValidationReason::NameTooLong
+        );
+        assert_eq!(
+            reason("芯片", Some(&"长".repeat(51))),
+            ValidationReason::DetailTooLong
+        );
+        assert_eq!(
+            evaluate_tag(&rules, "甲", None).status,
+            ValidationStatus::Warning
+        );
+    }
+
+    #[test]
+    fn saves_and_loads_custom_rules() {
+        let dir = std::env::temp_dir().join(format!("tag-validation-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(VALIDATION_RULES_FILE_NAME);
+        assert_eq!(
+            ValidationRules::load_or_builtin(&path)
+                .unwrap()
+                .name_warning_length,
+            20
+        );
+
+        let mut rules = ValidationRules::builtin();
+        rules.name_warning_length = 4;
+        rules.save(&path).unwrap();
+        let loaded = ValidationRules::load_or_builtin(&path).unwrap();
+        assert_eq!(
+            evaluate_tag(&loaded, "人工智能芯片", None).reason,
+            ValidationReason::NameTooLong
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,18 +1,477 @@
+use crate::collation::*;
+use crate::command_error::*;
+use crate::company_search::*;
 use crate::stock_data::*;
+use crate::stock_diff::*;
+use crate::stock_merge::*;
+use crate::stock_similarity::*;
+use crate::stock_store::*;
+use crate::tag_blacklist::*;
+use crate::tag_cooccurrence::*;
+use crate::tag_diagnostics::*;
+use crate::tag_duplicates::*;
+use crate::tag_editor::*;
+use crate::tag_index::*;
+use crate::tag_journal::*;
 use crate::tag_processor::*;
-use std::sync::RwLock;
+use crate::tag_synonyms::*;
+use crate::tag_taxonomy::*;
+use crate::tag_validation::*;
+use crate::text_normalize::*;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{RwLock, RwLockReadGuard};
 use tauri::State;
+use tauri_plugin_dialog::DialogExt;
+
+/// 股票数据与标签索引的读锁
+pub type IndexedData<'a> = (
+    RwLockReadGuard<'a, Vec<StockCompanyInfo>>,
+    RwLockReadGuard<'a, TagIndex>,
+);
 
 /// 应用状态，用于缓存股票数据
 pub struct AppState {
     pub stock_data: RwLock<Vec<StockCompanyInfo>>,
+    /// 标签倒排索引，随 stock_data 一起更新
+    pub tag_index: RwLock<TagIndex>,
+    /// 公司经营范围和简介的全文索引，首次检索时构建，数据变化后失效
+    pub company_index: RwLock<Option<CompanyTextIndex>>,
+    /// 标签黑名单规则
+    pub tag_blacklist: RwLock<TagBlacklist>,
+    /// 标签层级（父子关系）
+    pub tag_taxonomy: RwLock<TagTaxonomy>,
+    /// 标签修改的撤销/重做日志
+    pub tag_journal: RwLock<TagJournal>,
+    /// 本地持久化存储，init_storage 之前为 None
+    pub stock_store: RwLock<Option<StockStore>>,
+    /// 最近一次数据集比较的完整结果，翻页时复用
+    pub diff_cache: RwLock<Option<DiffCache>>,
+    /// 应用数据目录，init_storage 之前为 None
+    pub data_dir: RwLock<Option<PathBuf>>,
+    /// 通过文件对话框选择的文件（规范化路径），前端传入的路径只能指向这些文件或应用数据目录
+    pub chosen_files: RwLock<HashSet<PathBuf>>,
 }
 
 impl AppState {
     pub fn new() -> Self {
         Self {
             stock_data: RwLock::new(Vec::new()),
+            tag_index: RwLock::new(TagIndex::build_with_synonyms(&[], TagSynonyms::builtin())),
+            company_index: RwLock::new(None),
+            tag_blacklist: RwLock::new(TagBlacklist::builtin()),
+            tag_taxonomy: RwLock::new(TagTaxonomy::builtin()),
+            tag_journal: RwLock::new(TagJournal::default()),
+            stock_store: RwLock::new(None),
+            diff_cache: RwLock::new(None),
+            data_dir: RwLock::new(None),
+            chosen_files: RwLock::new(HashSet::new()),
+        }
+    }
+
+    /// 初始化本地存储：加载应用数据目录中持久化的数据集和日志
+    pub fn init_storage(&self, data_dir: PathBuf) -> CommandResult<()> {
+        std::fs::create_dir_all(&data_dir).map_err(|e| {
+            CommandError::Storage(format!("Failed to create data directory: {}", e))
+        })?;
+        *self
+            .data_dir
+            .write()
+            .map_err(CommandError::lock_poisoned("data directory"))? = data_dir.canonicalize().ok();
+
+        let store =
+            StockStore::open(&data_dir, DEFAULT_MAX_SNAPSHOTS).map_err(CommandError::Storage)?;
+        let saved_data = store.load_current().map_err(CommandError::Storage)?;
+        *self
+            .stock_store
+            .write()
+            .map_err(CommandError::lock_poisoned("stock store"))? = Some(store);
+
+        // 同义词词典保存在数据目录中，不存在时使用内置词典
+        let synonyms = TagSynonyms::load_or_builtin(&data_dir.join(SYNONYMS_FILE_NAME))
+            .map_err(CommandError::Storage)?;
+        // 规范化配置同样保存在数据目录中，随当前数据集生效
+        let normalizer = TagNormalizer::load_or_default(&data_dir.join(NORMALIZATION_FILE_NAME))
+            .map_err(CommandError::Storage)?;
+        {
+            let mut data = self
+                .stock_data
+                .write()
+                .map_err(CommandError::lock_poisoned("stock data"))?;
+            let mut index = self
+                .tag_index
+                .write()
+                .map_err(CommandError::lock_poisoned("tag index"))?;
+            if let Some(saved_data) = saved_data {
+                *data = saved_data;
+            }
+            index.synonyms = synonyms;
+            index.normalizer = normalizer;
+            index.rebuild(&data);
+            self.invalidate_company_index()?;
+        }
+
+        // 自定义验证规则保存在数据目录中，不存在时使用内置规则
+        let rules = ValidationRules::load_or_builtin(&data_dir.join(VALIDATION_RULES_FILE_NAME))
+            .map_err(CommandError::Storage)?;
+        self.replace_validation_rules(rules)?;
+
+        let journal = TagJournal::load(&data_dir).map_err(CommandError::Storage)?;
+        *self
+            .tag_journal
+            .write()
+            .map_err(CommandError::lock_poisoned("tag journal"))? = journal;
+        Ok(())
+    }
+
+    /// 检查前端传入的文件路径，只允许读取应用数据目录中的文件或通过文件对话框选择的文件
+    /// 返回规范化后的路径
+    pub fn authorize_file(&self, path: &str) -> CommandResult<PathBuf> {
+        let canonical = Path::new(path)
+            .canonicalize()
+            .map_err(|e| CommandError::InvalidInput(format!("Failed to open {}: {}", path, e)))?;
+        let in_data_dir = self
+            .data_dir
+            .read()
+            .map_err(CommandError::lock_poisoned("data directory"))?
+            .as_ref()
+            .is_some_and(|data_dir| canonical.starts_with(data_dir));
+        if in_data_dir
+            || self
+                .chosen_files
+                .read()
+                .map_err(CommandError::lock_poisoned("chosen files"))?
+                .contains(&canonical)
+        {
+            Ok(canonical)
+        } else {
+            Err(CommandError::PermissionDenied(path.to_string()))
+        }
+    }
+
+    /// 检查路径并转换为字符串形式，用于按路径加载的规则和词典
+    fn authorize_file_str(&self, path: &str) -> CommandResult<String> {
+        Ok(self.authorize_file(path)?.to_string_lossy().into_owned())
+    }
+
+    /// 使用本地存储执行操作，未初始化时返回错误
+    pub fn with_store<R>(
+        &self,
+        action: impl FnOnce(&StockStore) -> CommandResult<R>,
+    ) -> CommandResult<R> {
+        let store = self
+            .stock_store
+            .read()
+            .map_err(CommandError::lock_poisoned("stock store"))?;
+        match store.as_ref() {
+            Some(store) => action(store),
+            None => Err(storage_not_initialized()),
+        }
+    }
+
+    /// 为当前数据集创建快照，未初始化存储时返回 None
+    pub fn snapshot_current(&self) -> CommandResult<Option<SnapshotInfo>> {
+        let data = self
+            .stock_data
+            .read()
+            .map_err(CommandError::lock_poisoned("stock data"))?;
+        self.snapshot_data(&data)
+    }
+
+    /// 为给定数据集创建快照（内容与最近一次快照相同时复用该快照），未初始化存储时返回 None
+    fn snapshot_data(
+        &self,
+        stock_data: &[StockCompanyInfo],
+    ) -> CommandResult<Option<SnapshotInfo>> {
+        let store = self
+            .stock_store
+            .read()
+            .map_err(CommandError::lock_poisoned("stock store"))?;
+        store
+            .as_ref()
+            .map(|store| store.create_snapshot(stock_data))
+            .transpose()
+            .map_err(CommandError::Storage)
+    }
+
+    /// 将当前数据集写入本地存储，未初始化存储时跳过
+    fn persist_stock_data(&self, stock_data: &[StockCompanyInfo]) -> CommandResult<()> {
+        let store = self
+            .stock_store
+            .read()
+            .map_err(CommandError::lock_poisoned("stock store"))?;
+        match store.as_ref() {
+            Some(store) => store
+                .save_current(stock_data)
+                .map_err(CommandError::Storage),
+            None => Ok(()),
+        }
+    }
+
+    /// 数据变化后丢弃全文索引，下次检索时重新构建
+    fn invalidate_company_index(&self) -> CommandResult<()> {
+        *self
+            .company_index
+            .write()
+            .map_err(CommandError::lock_poisoned("company index"))? = None;
+        Ok(())
+    }
+
+    /// 替换股票数据、重建标签索引并写入本地存储，数据相同时不做任何修改
+    /// 替换之前先为原数据创建快照
+    /// 锁顺序固定为 stock_data → tag_index → company_index → tag_journal → stock_store → diff_cache
+    pub fn replace_stock_data(
+        &self,
+        stock_data: Vec<StockCompanyInfo>,
+        operation: JournalOperation,
+    ) -> CommandResult<()> {
+        let mut data = self
+            .stock_data
+            .write()
+            .map_err(CommandError::lock_poisoned("stock data"))?;
+        let mut index = self
+            .tag_index
+            .write()
+            .map_err(CommandError::lock_poisoned("tag index"))?;
+
+        if *data == stock_data {
+            return Ok(());
+        }
+        if !data.is_empty() {
+            self.snapshot_data(&data)?;
         }
+        // 新增和删除的股票同样记入日志，撤销时可以恢复被替换掉的股票
+        let diff = diff_journal_changes(&data, &stock_data);
+        self.record_journal_stocks(operation, diff.changes, diff.inserted, diff.removed)?;
+        index.rebuild(&stock_data);
+        *data = stock_data;
+        self.invalidate_company_index()?;
+        self.persist_stock_data(&data)
+    }
+
+    /// 在写锁内修改股票数据，数据发生变化时重建标签索引并丢弃全文索引
+    pub fn modify_stock_data<R>(
+        &self,
+        modify: impl FnOnce(&mut Vec<StockCompanyInfo>) -> CommandResult<(R, bool)>,
+    ) -> CommandResult<R> {
+        self.modify_indexed(|stock_data, index, company_index| {
+            let (result, changed) = modify(stock_data)?;
+            if changed {
+                index.rebuild(stock_data);
+                *company_index = None;
+            }
+            Ok((result, changed))
+        })
+    }
+
+    /// 在写锁内同时修改股票数据、标签索引和全文索引（由调用方负责增量维护索引，
+    /// 全文索引尚未构建时为 None，修改了检索字段时需要同步更新或置为 None）
+    /// modify 返回结果以及数据是否发生变化，返回错误时不得修改数据；
+    /// 只有数据发生变化时才递增数据集版本并写入本地存储
+    pub fn modify_indexed<R>(
+        &self,
+        modify: impl FnOnce(
+            &mut Vec<StockCompanyInfo>,
+            &mut TagIndex,
+            &mut Option<CompanyTextIndex>,
+        ) -> CommandResult<(R, bool)>,
+    ) -> CommandResult<R> {
+        let mut data = self
+            .stock_data
+            .write()
+            .map_err(CommandError::lock_poisoned("stock data"))?;
+        let mut index = self
+            .tag_index
+            .write()
+            .map_err(CommandError::lock_poisoned("tag index"))?;
+        let mut company_index = self
+            .company_index
+            .write()
+            .map_err(CommandError::lock_poisoned("company index"))?;
+
+        let (result, changed) = modify(&mut data, &mut index, &mut company_index)?;
+        if changed {
+            index.touch();
+            self.persist_stock_data(&data)?;
+        }
+        Ok(result)
+    }
+
+    /// 记录一次对 stock_data 的修改
+    pub fn record_journal(
+        &self,
+        operation: JournalOperation,
+        changes: Vec<StockTagChange>,
+    ) -> CommandResult<()> {
+        self.tag_journal
+            .write()
+            .map_err(CommandError::lock_poisoned("tag journal"))?
+            .record(operation, changes)
+            .map_err(CommandError::Storage)
+    }
+
+    /// 记录一次新增或删除股票的修改
+    pub fn record_journal_stocks(
+        &self,
+        operation: JournalOperation,
+        changes: Vec<StockTagChange>,
+        inserted: Vec<StockCompanyInfo>,
+        removed: Vec<StockCompanyInfo>,
+    ) -> CommandResult<()> {
+        self.tag_journal
+            .write()
+            .map_err(CommandError::lock_poisoned("tag journal"))?
+            .record_stocks(operation, changes, inserted, removed)
+            .map_err(CommandError::Storage)
+    }
+
+    /// 替换同义词词典并重新聚合标签索引
+    pub fn replace_synonyms(&self, synonyms: TagSynonyms) -> CommandResult<SynonymInfo> {
+        let data = self
+            .stock_data
+            .read()
+            .map_err(CommandError::lock_poisoned("stock data"))?;
+        let mut index = self
+            .tag_index
+            .write()
+            .map_err(CommandError::lock_poisoned("tag index"))?;
+        index.synonyms = synonyms;
+        index.rebuild(&data);
+        Ok(index.synonyms.info())
+    }
+
+    /// 替换规范化配置并重新解析所有股票的标签
+    pub fn replace_normalizer(
+        &self,
+        normalizer: TagNormalizer,
+    ) -> CommandResult<NormalizationOptions> {
+        let data = self
+            .stock_data
+            .read()
+            .map_err(CommandError::lock_poisoned("stock data"))?;
+        let mut index = self
+            .tag_index
+            .write()
+            .map_err(CommandError::lock_poisoned("tag index"))?;
+        index.normalizer = normalizer;
+        index.rebuild(&data);
+        Ok(index.normalizer.options)
+    }
+
+    /// 使用全文索引（首次使用或数据变化后先构建），调用方需已持有 stock_data 读锁
+    pub fn with_company_index<R>(
+        &self,
+        stock_data: &[StockCompanyInfo],
+        f: impl FnOnce(&CompanyTextIndex) -> R,
+    ) -> CommandResult<R> {
+        {
+            let company_index = self
+                .company_index
+                .read()
+                .map_err(CommandError::lock_poisoned("company index"))?;
+            if let Some(company_index) = company_index.as_ref() {
+                return Ok(f(company_index));
+            }
+        }
+        // 索引尚未构建时才获取写锁，其他线程可能已先一步构建
+        let mut company_index = self
+            .company_index
+            .write()
+            .map_err(CommandError::lock_poisoned("company index"))?;
+        Ok(f(company_index.get_or_insert_with(|| {
+            CompanyTextIndex::build(stock_data)
+        })))
+    }
+
+    /// 同时读取股票数据和标签索引
+    pub fn read_indexed(&self) -> CommandResult<IndexedData<'_>> {
+        let data = self
+            .stock_data
+            .read()
+            .map_err(CommandError::lock_poisoned("stock data"))?;
+        let index = self
+            .tag_index
+            .read()
+            .map_err(CommandError::lock_poisoned("tag index"))?;
+        Ok((data, index))
+    }
+
+    /// 读取标签黑名单
+    pub fn read_blacklist(&self) -> CommandResult<RwLockReadGuard<'_, TagBlacklist>> {
+        self.tag_blacklist
+            .read()
+            .map_err(CommandError::lock_poisoned("tag blacklist"))
+    }
+
+    /// 替换标签黑名单，并递增数据集版本（隐藏模式下标签列表随之变化，旧游标失效）
+    /// 与读取命令一致，先获取 tag_index 再获取 tag_blacklist
+    pub fn replace_blacklist(&self, blacklist: TagBlacklist) -> CommandResult<BlacklistInfo> {
+        let mut index = self
+            .tag_index
+            .write()
+            .map_err(CommandError::lock_poisoned("tag index"))?;
+        let mut current = self
+            .tag_blacklist
+            .write()
+            .map_err(CommandError::lock_poisoned("tag blacklist"))?;
+        *current = blacklist;
+        index.touch();
+        Ok(current.info())
+    }
+
+    /// 替换标签验证规则，并递增数据集版本（按验证状态排序的标签列表随之变化，旧游标失效）
+    pub fn replace_validation_rules(&self, rules: ValidationRules) -> CommandResult<()> {
+        let mut index = self
+            .tag_index
+            .write()
+            .map_err(CommandError::lock_poisoned("tag index"))?;
+        set_validation_rules(rules);
+        index.touch();
+        Ok(())
+    }
+
+    /// 保存自定义验证规则到数据目录，传入 None 时删除已保存的规则；未初始化存储时跳过
+    fn save_validation_rules(&self, rules: Option<&ValidationRules>) -> CommandResult<()> {
+        let data_dir = self
+            .data_dir
+            .read()
+            .map_err(CommandError::lock_poisoned("data directory"))?;
+        let Some(path) = data_dir
+            .as_ref()
+            .map(|data_dir| data_dir.join(VALIDATION_RULES_FILE_NAME))
+        else {
+            return Ok(());
+        };
+        match rules {
+            Some(rules) => rules.save(&path).map_err(CommandError::Storage),
+            None if path.exists() => std::fs::remove_file(&path).map_err(|e| {
+                CommandError::Storage(format!("Failed to remove validation rules: {}", e))
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// 读取标签层级
+    pub fn read_taxonomy(&self) -> CommandResult<RwLockReadGuard<'_, TagTaxonomy>> {
+        self.tag_taxonomy
+            .read()
+            .map_err(CommandError::lock_poisoned("tag taxonomy"))
+    }
+
+    /// 替换标签层级，并递增数据集版本（包含子标签的股票列表随之变化，旧游标失效）
+    /// 与读取命令一致，先获取 tag_index 再获取 tag_taxonomy
+    pub fn replace_taxonomy(&self, taxonomy: TagTaxonomy) -> CommandResult<TaxonomyInfo> {
+        let mut index = self
+            .tag_index
+            .write()
+            .map_err(CommandError::lock_poisoned("tag index"))?;
+        let mut current = self
+            .tag_taxonomy
+            .write()
+            .map_err(CommandError::lock_poisoned("tag taxonomy"))?;
+        *current = taxonomy;
+        index.touch();
+        Ok(current.info())
     }
 }
 
@@ -21,29 +480,164 @@ impl AppState {
 pub async fn set_stock_data(
     state: State<'_, AppState>,
     stock_data: Vec<StockCompanyInfo>,
-) -> Result<(), String> {
-    match state.stock_data.write() {
-        Ok(mut data) => {
-            *data = stock_data;
-            Ok(())
+) -> CommandResult<()> {
+    state.replace_stock_data(stock_data, JournalOperation::SetStockData)
+}
+
+/// 按 stock_code 增量合并股票数据，updated_at 较新的记录生效
+#[tauri::command]
+pub async fn upsert_stocks(
+    state: State<'_, AppState>,
+    stocks: Vec<StockCompanyInfo>,
+) -> CommandResult<StockMergeSummary> {
+    state.modify_indexed(|stock_data, index, company_index| {
+        // 先写入撤销日志，写入失败时不修改数据
+        let upsert = plan_stock_upsert(stock_data, index, stocks)?;
+        state.record_journal_stocks(
+            JournalOperation::UpsertStocks,
+            upsert.changes.clone(),
+            upsert.inserted.clone(),
+            Vec::new(),
+        )?;
+        let changed = !upsert.is_empty();
+        Ok((
+            upsert.apply(stock_data, index, company_index.as_mut()),
+            changed,
+        ))
+    })
+}
+
+/// 按 stock_code 删除股票数据，可通过 undo 恢复
+#[tauri::command]
+pub async fn remove_stocks(
+    state: State<'_, AppState>,
+    stock_codes: Vec<String>,
+) -> CommandResult<StockMergeSummary> {
+    state.modify_indexed(|stock_data, index, company_index| {
+        let removal = plan_stock_removal(stock_data, index, &stock_codes);
+        state.record_journal_stocks(
+            JournalOperation::RemoveStocks,
+            Vec::new(),
+            Vec::new(),
+            removal.removed.clone(),
+        )?;
+        let changed = !removal.is_empty();
+        Ok((
+            removal.apply(stock_data, index, company_index.as_mut()),
+            changed,
+        ))
+    })
+}
+
+/// 获取本地保存的数据快照（最新的在前）
+#[tauri::command]
+pub async fn list_snapshots(state: State<'_, AppState>) -> CommandResult<Vec<SnapshotInfo>> {
+    state.with_store(|store| store.list_snapshots().map_err(CommandError::Storage))
+}
+
+/// 为当前数据集创建快照
+#[tauri::command]
+pub async fn create_snapshot(state: State<'_, AppState>) -> CommandResult<SnapshotInfo> {
+    state
+        .snapshot_current()?
+        .ok_or_else(storage_not_initialized)
+}
+
+/// 从快照恢复数据集
+#[tauri::command]
+pub async fn restore_snapshot(state: State<'_, AppState>, id: u64) -> CommandResult<u32> {
+    let stock_data = state.with_store(|store| store.load_snapshot(id))?;
+    let stock_count = stock_data.len() as u32;
+    state.replace_stock_data(stock_data, JournalOperation::RestoreSnapshot)?;
+    Ok(stock_count)
+}
+
+/// 删除快照
+#[tauri::command]
+pub async fn delete_snapshot(state: State<'_, AppState>, id: u64) -> CommandResult<()> {
+    state.with_store(|store| store.delete_snapshot(id))
+}
+
+/// 比较两个数据集（基准 → 目标）的差异（带分页）
+#[tauri::command]
+pub async fn diff_stock_data(
+    state: State<'_, AppState>,
+    base: DiffSource,
+    target: DiffSource,
+    page: u32,
+    per_page: u32,
+    kinds: Option<Vec<StockDiffKind>>,
+) -> CommandResult<DatasetDiffReport> {
+    validate_page(page, per_page)?;
+    for source in [&base, &target] {
+        if let DiffSource::File { path } = source {
+            state.authorize_file(path)?;
         }
-        Err(e) => Err(format!("Failed to update stock data: {}", e)),
     }
+
+    let (current, index) = state.read_indexed()?;
+    let key = DiffCacheKey::new(&base, &target, index.version);
+    drop(index);
+    // 同一组数据来源翻页时直接使用缓存的比较结果
+    if let Some(cache) = state
+        .diff_cache
+        .read()
+        .map_err(CommandError::lock_poisoned("diff cache"))?
+        .as_ref()
+        .filter(|cache| cache.key == key)
+    {
+        return Ok(cache.diff.report(kinds.as_deref(), page, per_page));
+    }
+
+    // Current 返回 None，直接使用内存中的数据，避免复制
+    let load = |source: &DiffSource| -> CommandResult<Option<Vec<StockCompanyInfo>>> {
+        match source {
+            DiffSource::Current => Ok(None),
+            DiffSource::Snapshot { id } => {
+                state.with_store(|store| store.load_snapshot(*id)).map(Some)
+            }
+            DiffSource::File { path } => load_dataset_file(Path::new(path))
+                .map(Some)
+                .map_err(CommandError::InvalidInput),
+        }
+    };
+    let base_data = load(&base)?;
+    let target_data = load(&target)?;
+
+    let diff = DatasetDiff::compute(
+        base_data.as_deref().unwrap_or(&current),
+        target_data.as_deref().unwrap_or(&current),
+    );
+    let report = diff.report(kinds.as_deref(), page, per_page);
+    *state
+        .diff_cache
+        .write()
+        .map_err(CommandError::lock_poisoned("diff cache"))? = Some(DiffCache { key, diff });
+    Ok(report)
+}
+
+/// 检查搜索查询语法，返回规范化后的查询表达式（空白查询返回 None）
+#[tauri::command]
+pub async fn check_search_query(query: String) -> CommandResult<Option<String>> {
+    Ok(SearchQuery::parse(&query)?.map(|query| query.expr.to_string()))
 }
 
-/// 获取分类列表和统计信息
+/// 获取分类列表和统计信息，分类默认按拼音排序
 #[tauri::command]
 pub async fn get_categories(
     state: State<'_, AppState>,
     search_query: Option<String>,
-) -> Result<CategoryListResult, String> {
-    match state.stock_data.read() {
-        Ok(stock_data) => {
-            let query = search_query.as_deref().filter(|s| !s.is_empty());
-            Ok(get_category_list(&stock_data, query))
-        }
-        Err(e) => Err(format!("Failed to read stock data: {}", e)),
-    }
+    match_mode: Option<MatchMode>,
+    collation: Option<CollationOrder>,
+) -> CommandResult<CategoryListResult> {
+    let query = parse_search_query(search_query.as_deref(), match_mode.unwrap_or_default())?;
+    let (stock_data, index) = state.read_indexed()?;
+    Ok(get_category_list(
+        &stock_data,
+        &index,
+        query.as_ref(),
+        collation.unwrap_or_default(),
+    ))
 }
 
 /// 获取指定分类下的标签列表（带分页）
@@ -51,20 +645,22 @@ pub async fn get_categories(
 pub async fn get_tags_by_category(
     state: State<'_, AppState>,
     params: SearchParams,
-) -> Result<TagListResult, String> {
-    match state.stock_data.read() {
-        Ok(stock_data) => Ok(get_tag_list(&stock_data, &params)),
-        Err(e) => Err(format!("Failed to read stock data: {}", e)),
-    }
+) -> CommandResult<TagListResult> {
+    let query = parse_search_query(params.search_query.as_deref(), params.match_mode)?;
+    let (stock_data, index) = state.read_indexed()?;
+    let blacklist = state.read_blacklist()?;
+    get_tag_list(&stock_data, &index, &blacklist, &params, query.as_ref())
 }
 
-/// 获取指定标签下的股票列表（带分页）
+/// 获取指定标签下的股票列表（按引用查询，在后端排序和分页）
 #[tauri::command]
 pub async fn get_stocks_by_tag(
-    selected_tag: SelectedTag,
-    params: SearchParams,
-) -> Result<StockListResult, String> {
-    Ok(get_stock_list(&selected_tag, &params))
+    state: State<'_, AppState>,
+    query: TagStocksQuery,
+) -> CommandResult<StockListResult> {
+    let (stock_data, index) = state.read_indexed()?;
+    let taxonomy = state.read_taxonomy()?;
+    get_tag_stock_list(&stock_data, &index, &taxonomy, &query)
 }
 
 /// 计算标签统计信息
@@ -75,7 +671,7 @@ pub async fn calculate_statistics(
     filtered_categories_count: u32,
     total_tags_count: u32,
     selected_category_tags_count: u32,
-) -> Result<TagStatistics, String> {
+) -> CommandResult<TagStatistics> {
     Ok(calculate_tag_statistics(
         &[], // 不需要原始数据，直接从tags计算
         &tags,
@@ -89,45 +685,309 @@ pub async fn calculate_statistics(
 #[tauri::command]
 pub async fn parse_tags(
     tags: String,
-) -> Result<std::collections::HashMap<String, Vec<TagItem>>, String> {
+) -> CommandResult<std::collections::HashMap<String, Vec<TagItem>>> {
     Ok(parse_custom_tags(&tags))
 }
 
+/// 解析自定义标签并保留分类和标签的原有顺序 - 用于前端调用
+#[tauri::command]
+pub async fn parse_tags_ordered(tags: String) -> CommandResult<ParsedTags> {
+    Ok(parse_custom_tags_ordered(&tags))
+}
+
+/// 将解析后的标签序列化为规范的 custom_tags 字符串 - 用于前端调用
+#[tauri::command]
+pub async fn serialize_tags(parsed: ParsedTags) -> CommandResult<String> {
+    Ok(serialize_custom_tags(&parsed))
+}
+
+/// 预览标签编辑的影响（不修改数据）
+#[tauri::command]
+pub async fn preview_tag_edit(
+    state: State<'_, AppState>,
+    edit: TagEdit,
+) -> CommandResult<TagEditPreview> {
+    edit.validate()?;
+    let (stock_data, index) = state.read_indexed()?;
+    Ok(compute_tag_edit(&stock_data, &index.normalizer, &edit))
+}
+
+/// 执行标签编辑，将规范化后的 custom_tags 写回数据集
+#[tauri::command]
+pub async fn apply_tag_edit(
+    state: State<'_, AppState>,
+    edit: TagEdit,
+) -> CommandResult<TagEditPreview> {
+    edit.validate()?;
+    // 只修改 custom_tags，全文索引保持不变
+    state.modify_indexed(|stock_data, index, _| {
+        let preview = compute_tag_edit(stock_data, &index.normalizer, &edit);
+        state.record_journal(JournalOperation::from(&edit), preview.changes.clone())?;
+        let changed = !preview.changes.is_empty();
+        if changed {
+            apply_tag_changes(stock_data, &preview.changes);
+            index.rebuild(stock_data);
+        }
+        Ok((preview, changed))
+    })
+}
+
+/// 撤销最近一次对股票数据的修改
+#[tauri::command]
+pub async fn undo(state: State<'_, AppState>) -> CommandResult<JournalApplyResult> {
+    state.modify_stock_data(|stock_data| {
+        let mut journal = state
+            .tag_journal
+            .write()
+            .map_err(CommandError::lock_poisoned("tag journal"))?;
+        let result = journal.undo(stock_data)?;
+        let changed = result.applied_stocks > 0;
+        Ok((result, changed))
+    })
+}
+
+/// 重做最近一次撤销的修改
+#[tauri::command]
+pub async fn redo(state: State<'_, AppState>) -> CommandResult<JournalApplyResult> {
+    state.modify_stock_data(|stock_data| {
+        let mut journal = state
+            .tag_journal
+            .write()
+            .map_err(CommandError::lock_poisoned("tag journal"))?;
+        let result = journal.redo(stock_data)?;
+        let changed = result.applied_stocks > 0;
+        Ok((result, changed))
+    })
+}
+
+/// 获取修改历史（最新的在前）
+#[tauri::command]
+pub async fn list_history(state: State<'_, AppState>) -> CommandResult<Vec<HistoryItem>> {
+    let journal = state
+        .tag_journal
+        .read()
+        .map_err(CommandError::lock_poisoned("tag journal"))?;
+    Ok(journal.history())
+}
+
+/// 诊断单个 custom_tags 字符串中的格式问题
+#[tauri::command]
+pub async fn diagnose_tags(
+    stock_code: Option<String>,
+    tags: String,
+) -> CommandResult<Vec<TagProblem>> {
+    Ok(diagnose_custom_tags(
+        stock_code.as_deref().unwrap_or(""),
+        &tags,
+    ))
+}
+
+/// 获取全量数据的标签问题报告（带分页）
+#[tauri::command]
+pub async fn get_tag_diagnostics(
+    state: State<'_, AppState>,
+    page: u32,
+    per_page: u32,
+    kinds: Option<Vec<TagProblemKind>>,
+) -> CommandResult<TagDiagnosticsReport> {
+    validate_page(page, per_page)?;
+    let stock_data = state
+        .stock_data
+        .read()
+        .map_err(CommandError::lock_poisoned("stock data"))?;
+    Ok(build_diagnostics_report(
+        &stock_data,
+        kinds.as_deref(),
+        page,
+        per_page,
+    ))
+}
+
+/// 获取当前数据集的规范化配置
+#[tauri::command]
+pub async fn get_tag_normalization(
+    state: State<'_, AppState>,
+) -> CommandResult<NormalizationOptions> {
+    let (_stock_data, index) = state.read_indexed()?;
+    Ok(index.normalizer.options)
+}
+
+/// 修改规范化配置（保存到数据目录），并按新配置重新解析标签
+#[tauri::command]
+pub async fn set_tag_normalization(
+    state: State<'_, AppState>,
+    options: NormalizationOptions,
+) -> CommandResult<NormalizationOptions> {
+    let normalizer = {
+        let (_stock_data, index) = state.read_indexed()?;
+        index.normalizer.with_options(options)
+    };
+    normalizer.save().map_err(CommandError::Storage)?;
+    state.replace_normalizer(normalizer)
+}
+
+/// 预览规范化结果：列出每只股票会被改动的 custom_tags 及应用的规范化步骤（带分页）
+#[tauri::command]
+pub async fn preview_tag_normalization(
+    state: State<'_, AppState>,
+    page: u32,
+    per_page: u32,
+) -> CommandResult<TagNormalizationReport> {
+    validate_page(page, per_page)?;
+    let (stock_data, index) = state.read_indexed()?;
+    Ok(build_normalization_report(
+        &stock_data,
+        &index.normalizer,
+        page,
+        per_page,
+    ))
+}
+
+/// 将规范化后的 custom_tags 写回数据集，可通过 undo 撤销
+#[tauri::command]
+pub async fn apply_tag_normalization(state: State<'_, AppState>) -> CommandResult<TagEditPreview> {
+    // 只修改 custom_tags，全文索引保持不变
+    state.modify_indexed(|stock_data, index, _| {
+        let changes: Vec<StockTagChange> = collect_normalizations(stock_data, &index.normalizer)
+            .into_iter()
+            .map(StockTagChange::from)
+            .collect();
+        state.record_journal(JournalOperation::Normalize, changes.clone())?;
+        let changed = !changes.is_empty();
+        if changed {
+            apply_tag_changes(stock_data, &changes);
+            index.rebuild(stock_data);
+        }
+        Ok((
+            TagEditPreview {
+                affected_stocks: changes.len() as u32,
+                changes,
+            },
+            changed,
+        ))
+    })
+}
+
+/// 检测同一分类内疑似重复的标签（全半角、空白、繁简、后缀和编辑距离），附带合并用的编辑操作
+#[tauri::command]
+pub async fn get_duplicate_tags(
+    state: State<'_, AppState>,
+    params: DuplicateTagParams,
+) -> CommandResult<DuplicateTagReport> {
+    validate_page(params.page, params.per_page)?;
+    let (stock_data, index) = state.read_indexed()?;
+    Ok(build_duplicate_report(&stock_data, &index, &params))
+}
+
 /// 验证标签格式
 #[tauri::command]
-pub async fn validate_tag(tag_name: String, tag_detail: Option<String>) -> Result<String, String> {
+pub async fn validate_tag(tag_name: String, tag_detail: Option<String>) -> CommandResult<String> {
     let status = validate_tag_format(&tag_name, tag_detail.as_deref());
     Ok(status.as_str().to_string())
 }
 
-/// 获取标签详情（包含股票信息）
+/// 验证标签格式，返回状态、原因代码和提示信息
+#[tauri::command]
+pub async fn validate_tag_detail(
+    tag_name: String,
+    tag_detail: Option<String>,
+) -> CommandResult<TagValidationResult> {
+    Ok(validate_tag_detailed(&tag_name, tag_detail.as_deref()))
+}
+
+/// 获取当前的标签验证规则
+#[tauri::command]
+pub async fn get_tag_validation_rules() -> CommandResult<ValidationRules> {
+    Ok(get_validation_rules())
+}
+
+/// 设置标签验证规则（保存到数据目录），传入 None 时恢复内置规则
+#[tauri::command]
+pub async fn set_tag_validation_rules(
+    state: State<'_, AppState>,
+    rules: Option<ValidationRules>,
+) -> CommandResult<ValidationRules> {
+    state.save_validation_rules(rules.as_ref())?;
+    state.replace_validation_rules(rules.unwrap_or_else(ValidationRules::builtin))?;
+    Ok(get_validation_rules())
+}
+
+/// 获取标签详情（标签引用和股票数量，股票通过 get_stocks_by_tag 分页获取）
+/// 默认合并标签层级中所有子标签的股票（同一只股票只计一次），include_children 为 false 时只统计直接带有该标签的股票
 #[tauri::command]
 pub async fn get_tag_details(
     state: State<'_, AppState>,
     category_name: String,
     tag_name: String,
     tag_detail: Option<String>,
-) -> Result<SelectedTag, String> {
-    match state.stock_data.read() {
-        Ok(stock_data) => {
-            let category_data = get_category_data(&stock_data, &category_name);
-
-            // 查找匹配的标签
-            for tag in category_data.tags {
-                if tag.name == tag_name && tag.detail == tag_detail {
-                    return Ok(SelectedTag {
-                        category_name,
-                        tag_name,
-                        tag_detail,
-                        stocks: tag.stocks,
-                    });
-                }
-            }
+    include_children: Option<bool>,
+) -> CommandResult<SelectedTag> {
+    let (_stock_data, index) = state.read_indexed()?;
+    let taxonomy = state.read_taxonomy()?;
 
-            Err("Tag not found".to_string())
-        }
-        Err(e) => Err(format!("Failed to read stock data: {}", e)),
-    }
+    // 直接从索引查找匹配的标签，再合并子标签的股票
+    let stock_ids = tag_stock_ids(
+        &index,
+        &taxonomy,
+        &category_name,
+        &tag_name,
+        tag_detail.as_deref(),
+        include_children.unwrap_or(true),
+    )
+    .ok_or_else(|| {
+        CommandError::NotFound(format!(
+            "Tag {}",
+            tag_id(&category_name, &tag_name, tag_detail.as_deref())
+        ))
+    })?;
+
+    Ok(SelectedTag {
+        tag_id: tag_id(&category_name, &tag_name, tag_detail.as_deref()),
+        count: stock_ids.len() as u32,
+        category_name,
+        tag_name,
+        tag_detail,
+    })
+}
+
+/// 获取指定分类的全部标签，并按标签层级构建标签树（父节点计入子标签的股票）
+#[tauri::command]
+pub async fn get_category_tree(
+    state: State<'_, AppState>,
+    category_name: String,
+    collation: Option<CollationOrder>,
+) -> CommandResult<TagCategory> {
+    let (stock_data, index) = state.read_indexed()?;
+    let blacklist = state.read_blacklist()?;
+    let taxonomy = state.read_taxonomy()?;
+    Ok(get_category_data(
+        &stock_data,
+        &index,
+        Some(&blacklist),
+        Some(&taxonomy),
+        &category_name,
+        collation.unwrap_or_default(),
+    ))
+}
+
+/// 查询与所选标签经常同时出现的标签（共现数、提升度、置信度和 Jaccard）
+#[tauri::command]
+pub async fn get_tag_associations(
+    state: State<'_, AppState>,
+    params: TagAssociationParams,
+) -> CommandResult<TagAssociationResult> {
+    let (stock_data, index) = state.read_indexed()?;
+    find_tag_associations(&stock_data, &index, &params)
+}
+
+/// 计算标签两两之间的共现矩阵
+#[tauri::command]
+pub async fn get_tag_cooccurrence(
+    state: State<'_, AppState>,
+    params: CooccurrenceMatrixParams,
+) -> CommandResult<CooccurrenceMatrix> {
+    let (_stock_data, index) = state.read_indexed()?;
+    Ok(build_cooccurrence_matrix(&index, &params))
 }
 
 /// 执行全面的搜索和过滤
@@ -135,43 +995,231 @@ pub async fn get_tag_details(
 pub async fn search_and_filter(
     state: State<'_, AppState>,
     params: SearchParams,
-) -> Result<(CategoryListResult, TagListResult), String> {
-    match state.stock_data.read() {
-        Ok(stock_data) => {
-            let category_result = get_category_list(&stock_data, params.search_query.as_deref());
-            let tag_result = get_tag_list(&stock_data, &params);
+) -> CommandResult<(CategoryListResult, TagListResult)> {
+    let query = parse_search_query(params.search_query.as_deref(), params.match_mode)?;
+    let (stock_data, index) = state.read_indexed()?;
+    let blacklist = state.read_blacklist()?;
+    let category_result = get_category_list(&stock_data, &index, query.as_ref(), params.collation);
+    let tag_result = get_tag_list(&stock_data, &index, &blacklist, &params, query.as_ref())?;
 
-            Ok((category_result, tag_result))
-        }
-        Err(e) => Err(format!("Failed to read stock data: {}", e)),
+    Ok((category_result, tag_result))
+}
+
+/// 全文检索公司经营范围和简介（BM25 排序，可按分类和标签过滤）
+#[tauri::command]
+pub async fn search_companies(
+    state: State<'_, AppState>,
+    params: CompanySearchParams,
+) -> CommandResult<CompanySearchResult> {
+    validate_page(params.page, params.per_page)?;
+    let (stock_data, index) = state.read_indexed()?;
+    state.with_company_index(&stock_data, |company_index| {
+        search_company_text(&stock_data, &index, company_index, &params)
+    })
+}
+
+/// 查找与指定股票最相似的 k 只股票（标签、板块概念和可选的文本相似度），附带得分明细
+#[tauri::command]
+pub async fn find_similar_stocks(
+    state: State<'_, AppState>,
+    stock_code: String,
+    k: u32,
+    options: Option<SimilarityOptions>,
+) -> CommandResult<Vec<SimilarStock>> {
+    let options = options.unwrap_or_default();
+    let (stock_data, index) = state.read_indexed()?;
+    if options.use_text {
+        state.with_company_index(&stock_data, |company_index| {
+            find_similar(
+                &stock_data,
+                &index,
+                Some(company_index),
+                &stock_code,
+                k,
+                &options,
+            )
+        })?
+    } else {
+        find_similar(&stock_data, &index, None, &stock_code, k, &options)
     }
 }
 
 /// 获取股票数据的基本统计信息
 #[tauri::command]
-pub async fn get_data_statistics(state: State<'_, AppState>) -> Result<(u32, u32, u32), String> {
-    match state.stock_data.read() {
-        Ok(stock_data) => {
-            let total_stocks = stock_data.len() as u32;
-            let stocks_with_tags = stock_data
-                .iter()
-                .filter(|stock| !stock.custom_tags.is_empty())
-                .count() as u32;
-
-            // 计算总分类数
-            let mut all_categories = std::collections::HashSet::new();
-            for stock in stock_data.iter() {
-                if !stock.custom_tags.is_empty() {
-                    let parsed = parse_custom_tags(&stock.custom_tags);
-                    for category in parsed.keys() {
-                        all_categories.insert(category.clone());
-                    }
-                }
-            }
-            let total_categories = all_categories.len() as u32;
+pub async fn get_data_statistics(state: State<'_, AppState>) -> CommandResult<(u32, u32, u32)> {
+    let (stock_data, index) = state.read_indexed()?;
+    let total_stocks = stock_data.len() as u32;
+    let total_categories = index.categories.len() as u32;
 
-            Ok((total_stocks, stocks_with_tags, total_categories))
-        }
-        Err(e) => Err(format!("Failed to read stock data: {}", e)),
-    }
+    Ok((total_stocks, index.stocks_with_tags, total_categories))
+}
+
+/// 获取笔画排序实际使用的规则，不是 stroke 时笔画排序结果可能不准确
+#[tauri::command]
+pub async fn get_stroke_collation_support() -> CommandResult<StrokeCollationSupport> {
+    Ok(stroke_collation_support())
+}
+
+/// 打开文件对话框选择规则、词典或数据文件，返回选择的路径（取消时返回 None）
+/// 选择的文件此后可以传给 load_tag_blacklist 等按路径读取的命令
+#[tauri::command]
+pub async fn choose_data_file(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> CommandResult<Option<String>> {
+    let Some(file_path) = app.dialog().file().blocking_pick_file() else {
+        return Ok(None);
+    };
+    let open_error = |e: &dyn std::fmt::Display| {
+        CommandError::InvalidInput(format!("Failed to open chosen file: {}", e))
+    };
+    let path = file_path
+        .into_path()
+        .map_err(|e| open_error(&e))?
+        .canonicalize()
+        .map_err(|e| open_error(&e))?;
+    let path_str = path.to_string_lossy().into_owned();
+    state
+        .chosen_files
+        .write()
+        .map_err(CommandError::lock_poisoned("chosen files"))?
+        .insert(path);
+    Ok(Some(path_str))
+}
+
+/// 加载标签黑名单规则文件，未指定路径时恢复内置规则
+#[tauri::command]
+pub async fn load_tag_blacklist(
+    state: State<'_, AppState>,
+    path: Option<String>,
+) -> CommandResult<BlacklistInfo> {
+    let blacklist = match path {
+        Some(path) => TagBlacklist::from_file(&state.authorize_file_str(&path)?)
+            .map_err(CommandError::InvalidInput)?,
+        None => TagBlacklist::builtin(),
+    };
+    state.replace_blacklist(blacklist)
+}
+
+/// 从当前规则来源重新加载标签黑名单
+#[tauri::command]
+pub async fn reload_tag_blacklist(state: State<'_, AppState>) -> CommandResult<BlacklistInfo> {
+    let blacklist = state
+        .read_blacklist()?
+        .reload()
+        .map_err(CommandError::InvalidInput)?;
+    state.replace_blacklist(blacklist)
+}
+
+/// 加载标签层级文件，未指定路径时恢复内置层级
+#[tauri::command]
+pub async fn load_tag_taxonomy(
+    state: State<'_, AppState>,
+    path: Option<String>,
+) -> CommandResult<TaxonomyInfo> {
+    let taxonomy = match path {
+        Some(path) => TagTaxonomy::from_file(&state.authorize_file_str(&path)?)
+            .map_err(CommandError::InvalidInput)?,
+        None => TagTaxonomy::builtin(),
+    };
+    state.replace_taxonomy(taxonomy)
+}
+
+/// 从当前来源重新加载标签层级
+#[tauri::command]
+pub async fn reload_tag_taxonomy(state: State<'_, AppState>) -> CommandResult<TaxonomyInfo> {
+    let taxonomy = state
+        .read_taxonomy()?
+        .reload()
+        .map_err(CommandError::InvalidInput)?;
+    state.replace_taxonomy(taxonomy)
+}
+
+/// 获取当前的同义词词典
+#[tauri::command]
+pub async fn get_tag_synonyms(state: State<'_, AppState>) -> CommandResult<SynonymInfo> {
+    let (_stock_data, index) = state.read_indexed()?;
+    Ok(index.synonyms.info())
+}
+
+/// 加载同义词词典文件，未指定路径时恢复内置词典
+#[tauri::command]
+pub async fn load_tag_synonyms(
+    state: State<'_, AppState>,
+    path: Option<String>,
+) -> CommandResult<SynonymInfo> {
+    let synonyms = match path {
+        Some(path) => TagSynonyms::from_file(&state.authorize_file_str(&path)?)
+            .map_err(CommandError::InvalidInput)?,
+        None => TagSynonyms::builtin(),
+    };
+    state.replace_synonyms(synonyms)
+}
+
+/// 从当前来源重新加载同义词词典
+#[tauri::command]
+pub async fn reload_tag_synonyms(state: State<'_, AppState>) -> CommandResult<SynonymInfo> {
+    let synonyms = {
+        let (_stock_data, index) = state.read_indexed()?;
+        index
+            .synonyms
+            .reload()
+            .map_err(CommandError::InvalidInput)?
+    };
+    state.replace_synonyms(synonyms)
+}
+
+/// 为规范名称添加别名（category_name 为空时对所有分类生效），并写回词典文件
+#[tauri::command]
+pub async fn add_tag_alias(
+    state: State<'_, AppState>,
+    canonical: String,
+    aliases: Vec<String>,
+    category_name: Option<String>,
+) -> CommandResult<SynonymInfo> {
+    let mut synonyms = {
+        let (_stock_data, index) = state.read_indexed()?;
+        index.synonyms.clone()
+    };
+    synonyms
+        .add_entry(SynonymEntry {
+            canonical,
+            aliases,
+            category: category_name,
+        })
+        .map_err(CommandError::InvalidInput)?;
+    synonyms.save().map_err(CommandError::Storage)?;
+    state.replace_synonyms(synonyms)
+}
+
+/// 根据名称相似度建议可以合并为同义词的标签
+#[tauri::command]
+pub async fn suggest_tag_aliases(
+    state: State<'_, AppState>,
+    category_name: Option<String>,
+    min_score: Option<f64>,
+    limit: u32,
+) -> CommandResult<Vec<AliasSuggestion>> {
+    let (_stock_data, index) = state.read_indexed()?;
+    Ok(suggest_aliases(
+        &index,
+        category_name.as_deref(),
+        min_score.unwrap_or(0.5),
+        limit,
+    ))
+}
+
+/// 检测标签是否命中黑名单
+#[tauri::command]
+pub async fn test_tag_blacklist(
+    state: State<'_, AppState>,
+    tags: Vec<String>,
+) -> CommandResult<Vec<BlacklistMatch>> {
+    let blacklist = state.read_blacklist()?;
+    Ok(tags.iter().map(|tag| blacklist.test(tag)).collect())
+}
+
+/// 本地存储尚未初始化
+fn storage_not_initialized() -> CommandError {
+    CommandError::Storage("Local storage is not initialized".to_string())
 }
@@ -0,0 +1,373 @@
+use crate::command_error::page_start;
+use crate::stock_data::*;
+use crate::stock_store::write_file_atomic;
+use crate::tag_editor::StockTagChange;
+use once_cell::sync::Lazy;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use unicode_normalization::UnicodeNormalization;
+
+/// 数据目录中保存规范化配置的文件名
+pub const NORMALIZATION_FILE_NAME: &str = "tag-normalization.json";
+
+/// 常用繁体字 → 简体字（每两个字符为一组），覆盖行业、概念标签中的常见用字
+const TRADITIONAL_SIMPLIFIED_PAIRS: &str = concat!(
+    "電电車车發发機机導导體体軟软網网醫医藥药銀银證证險险業业產产製制區区塊块鏈链雲云",
+    "計计數数據据環环節节輸输運运貨货幣币賣卖買买價价實实際际線线條条報报團团統统聯联",
+    "開开關关門门間间問问題题圖图書书術术學学習习藝艺廣广東东無无錫锡濱滨蘇苏滬沪廈厦",
+    "華华億亿萬万與与專专務务設设備备儀仪鋼钢鐵铁礦矿氣气氫氢鋰锂儲储鈉钠鎳镍鈷钴紡纺",
+    "織织農农飲饮養养豬猪漁渔遊游戲戏傳传視视頻频聲声樂乐衛卫檢检測测試试驗验動动風风",
+    "點点燈灯顯显觸触圓圆積积裝装碼码軍军艦舰彈弹飛飞鐘钟錶表紙纸療疗護护貿贸進进過过",
+    "認认權权債债貸贷稅税資资擔担託托營营銷销廠厂煉炼熱热變变壓压鋁铝銅铜鋅锌鈦钛鎂镁",
+    "鉬钼鎢钨貴贵黃黄糧粮種种飼饲漿浆鹽盐紗纱絲丝綫线針针傢家廚厨標标準准級级層层個个",
+    "們们來来對对會会從从當当後后時时長长現现經经濟济場场強强國国內内陸陆臺台灣湾歐欧",
+    "亞亚韓韩麗丽島岛嶼屿鄉乡鎮镇縣县蘭兰劃划規规畫画創创適适應应邊边緣缘雙双綠绿廢废",
+    "棄弃處处汙污淨净潔洁擁拥軌轨橋桥築筑預预載载輛辆輪轮駛驶艙舱號号訊讯話话語语腦脑",
+    "絡络頁页劇剧聞闻紀纪錄录訂订閱阅讀读寫写課课員员費费劑剂診诊斷断腫肿癥症細细獸兽",
+    "寵宠貓猫鳥鸟魚鱼蝦虾雞鸡鴨鸭麥麦穀谷麵面飯饭釀酿葉叶菸烟煙烟鑽钻銲焊鏡镜頭头攝摄",
+    "響响碩硕憑凭額额總总勢势屬属礎础組组構构優优質质負负責责賬账帳帐單单齊齐鉅巨鋒锋",
+);
+
+static TRADITIONAL_TO_SIMPLIFIED: Lazy<HashMap<char, char>> = Lazy::new(|| {
+    let chars: Vec<char> = TRADITIONAL_SIMPLIFIED_PAIRS.chars().collect();
+    chars.chunks(2).map(|pair| (pair[0], pair[1])).collect()
+});
+
+/// 全角字符转半角（全角空格转为普通空格），其余字符原样返回
+pub fn to_half_width(ch: char) -> char {
+    match ch {
+        '\u{3000}' => ' ',
+        '\u{ff01}'..='\u{ff5e}' => char::from_u32(ch as u32 - 0xfee0).unwrap_or(ch),
+        _ => ch,
+    }
+}
+
+/// 繁体字转简体字，不在对照表中的字符原样返回
+pub fn to_simplified(ch: char) -> char {
+    TRADITIONAL_TO_SIMPLIFIED.get(&ch).copied().unwrap_or(ch)
+}
+
+/// custom_tags 的规范化步骤（按执行顺序排列）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NormalizationKind {
+    /// 全角字符转半角（包括 "：" "；" "｛｝" 等分隔符）
+    FullWidth,
+    /// Unicode NFKC 兼容规范化
+    Nfkc,
+    /// 繁体字转简体字
+    TraditionalChinese,
+}
+
+/// 数据集的规范化配置，在解析 custom_tags 之前执行
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NormalizationOptions {
+    pub full_width: bool,
+    pub nfkc: bool,
+    pub traditional_to_simplified: bool,
+}
+
+impl Default for NormalizationOptions {
+    fn default() -> Self {
+        Self {
+            full_width: true,
+            nfkc: true,
+            traditional_to_simplified: true,
+        }
+    }
+}
+
+/// custom_tags 规范化器 - 配置保存在数据目录中，随数据集一起生效
+#[derive(Debug, Clone, Default)]
+pub struct TagNormalizer {
+    source: Option<String>,
+    pub options: NormalizationOptions,
+}
+
+impl TagNormalizer {
+    /// 文件存在时从文件加载配置，否则使用默认配置并以该文件作为保存位置
+    pub fn load_or_default(path: &Path) -> Result<Self, String> {
+        let path_str = path.to_string_lossy().to_string();
+        let options = if path.exists() {
+            let json = std::fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read tag normalization {}: {}", path_str, e))?;
+            serde_json::from_str(&json)
+                .map_err(|e| format!("Failed to parse tag normalization: {}", e))?
+        } else {
+            NormalizationOptions::default()
+        };
+        Ok(Self {
+            source: Some(path_str),
+            options,
+        })
+    }
+
+    /// 替换配置，保留原来的保存位置
+    pub fn with_options(&self, options: NormalizationOptions) -> Self {
+        Self {
+            source: self.source.clone(),
+            options,
+        }
+    }
+
+    /// 将配置写回来源文件（先写临时文件再替换），未初始化存储时跳过
+    pub fn save(&self) -> Result<(), String> {
+        let Some(path) = &self.source else {
+            return Ok(());
+        };
+        let json = serde_json::to_string_pretty(&self.options)
+            .map_err(|e| format!("Failed to serialize tag normalization: {}", e))?;
+        write_file_atomic(Path::new(path), json)
+            .map_err(|e| format!("Failed to write tag normalization {}: {}", path, e))
+    }
+
+    /// 规范化 custom_tags，返回规范化后的文本和实际产生改动的步骤
+    pub fn normalize(&self, tags: &str) -> (String, Vec<NormalizationKind>) {
+        let mut applied = Vec::new();
+        let mut text = tags.to_string();
+        if tags.is_ascii() {
+            return (text, applied);
+        }
+
+        let mut apply = |enabled: bool, kind: NormalizationKind, step: &dyn Fn(&str) -> String| {
+            if !enabled {
+                return;
+            }
+            let next = step(&text);
+            if next != text {
+                applied.push(kind);
+                text = next;
+            }
+        };
+        apply(
+            self.options.full_width,
+            NormalizationKind::FullWidth,
+            &|text| text.chars().map(to_half_width).collect(),
+        );
+        apply(self.options.nfkc, NormalizationKind::Nfkc, &|text| {
+            text.nfkc().collect()
+        });
+        apply(
+            self.options.traditional_to_simplified,
+            NormalizationKind::TraditionalChinese,
+            &|text| text.chars().map(to_simplified).collect(),
+        );
+        (text, applied)
+    }
+}
+
+/// 单只股票的规范化结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StockNormalization {
+    pub stock_code: String,
+    pub stock_name: String,
+    pub before: String,
+    pub after: String,
+    pub applied: Vec<NormalizationKind>,
+}
+
+impl From<StockNormalization> for StockTagChange {
+    fn from(normalization: StockNormalization) -> Self {
+        StockTagChange {
+            stock_code: normalization.stock_code,
+            stock_name: normalization.stock_name,
+            before: normalization.before,
+            after: normalization.after,
+        }
+    }
+}
+
+/// 全量数据的规范化报告（带分页）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagNormalizationReport {
+    pub options: NormalizationOptions,
+    pub stocks: Vec<StockNormalization>,
+    pub total_stocks: u32,
+    pub total_pages: u32,
+    pub current_page: u32,
+    /// 各规范化步骤影响的股票数量
+    pub counts_by_kind: BTreeMap<NormalizationKind, u32>,
+}
+
+/// 找出规范化后 custom_tags 会发生变化的股票
+pub fn collect_normalizations(
+    stock_data: &[StockCompanyInfo],
+    normalizer: &TagNormalizer,
+) -> Vec<StockNormalization> {
+    stock_data
+        .par_iter()
+        .filter(|stock| !stock.custom_tags.is_empty())
+        .filter_map(|stock| {
+            let (after, applied) = normalizer.normalize(&stock.custom_tags);
+            if applied.is_empty() {
+                return None;
+            }
+            Some(StockNormalization {
+                stock_code: stock.stock_code.clone(),
+                stock_name: stock.stock_name.clone(),
+                before: stock.custom_tags.clone(),
+                after,
+                applied,
+            })
+        })
+        .collect()
+}
+
+/// 生成规范化报告，列出每只股票应用了哪些规范化步骤
+pub fn build_normalization_report(
+    stock_data: &[StockCompanyInfo],
+    normalizer: &TagNormalizer,
+    page: u32,
+    per_page: u32,
+) -> TagNormalizationReport {
+    let stocks = collect_normalizations(stock_data, normalizer);
+
+    let mut counts_by_kind: BTreeMap<NormalizationKind, u32> = BTreeMap::new();
+    for stock in &stocks {
+        for kind in &stock.applied {
+            *counts_by_kind.entry(*kind).or_default() += 1;
+        }
+    }
+
+    let total_stocks = stocks.len() as u32;
+    let total_pages = total_stocks.div_ceil(per_page);
+    let start_index = page_start(page, per_page);
+
+    TagNormalizationReport {
+        options: normalizer.options,
+        stocks: stocks
+            .into_iter()
+            .skip(start_index)
+            .take(per_page as usize)
+            .collect(),
+        total_stocks,
+        total_pages,
+        current_page: page,
+        counts_by_kind,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalizer(options: NormalizationOptions) -> TagNormalizer {
+        TagNormalizer {
+            source: None,
+            options,
+        }
+    }
+
+    #[test]
+    fn converts_full_width_characters() {
+        assert_eq!(to_half_width('：'), ':');
+        assert_eq!(to_half_width('；'), ';');
+        assert_eq!(to_half_width('｛'), '{');
+        assert_eq!(to_half_width('Ａ'), 'A');
+        assert_eq!(to_half_width('\u{3000}'), ' ');
+        assert_eq!(to_half_width('中'), '中');
+    }
+
+    #[test]
+    fn converts_traditional_characters() {
+        let text: String = "電動車產業鏈帳目賬單".chars().map(to_simplified).collect();
+        assert_eq!(text, "电动车产业链帐目账单");
+        assert_eq!(to_simplified('银'), '银');
+    }
+
+    #[test]
+    fn traditional_table_has_no_duplicate_or_chained_pairs() {
+        let chars: Vec<char> = TRADITIONAL_SIMPLIFIED_PAIRS.chars().collect();
+        assert_eq!(chars.len() % 2, 0);
+        assert_eq!(TRADITIONAL_TO_SIMPLIFIED.len(), chars.len() / 2);
+        for (traditional, simplified) in TRADITIONAL_TO_SIMPLIFIED.iter() {
+            assert_ne!(traditional, simplified);
+            assert!(
+                !TRADITIONAL_TO_SIMPLIFIED.contains_key(simplified),
+                "{} maps to another traditional character {}",
+                traditional,
+                simplified
+            );
+        }
+    }
+
+    #[test]
+    fn reports_applied_steps() {
+        let all = normalizer(NormalizationOptions::default());
+        let (text, applied) = all.normalize("概念：電動車；行業：半導體｛晶片｝");
+        assert_eq!(text, "概念:电动车;行业:半导体{晶片}");
+        assert_eq!(
+            applied,
+            vec![
+                NormalizationKind::FullWidth,
+                NormalizationKind::TraditionalChinese
+            ]
+        );
+
+        // NFKC 处理全角转换之外的兼容字符，例如带圈数字和合字
+        let (text, applied) = all.normalize("概念:①号ﬁ");
+        assert_eq!(text, "概念:1号fi");
+        assert_eq!(applied, vec![NormalizationKind::Nfkc]);
+
+        let (text, applied) = all.normalize("概念:AI");
+        assert_eq!(text, "概念:AI");
+        assert!(applied.is_empty());
+    }
+
+    #[test]
+    fn disabled_steps_are_skipped() {
+        let full_width_only = normalizer(NormalizationOptions {
+            full_width: true,
+            nfkc: false,
+            traditional_to_simplified: false,
+        });
+        let (text, applied) = full_width_only.normalize("行業：半導體");
+        assert_eq!(text, "行業:半導體");
+        assert_eq!(applied, vec![NormalizationKind::FullWidth]);
+    }
+
+    #[test]
+    fn collects_changed_stocks() {
+        let stock_data = vec![
+            test_stock("600001", "概念：AI"),
+            test_stock("600002", "概念:AI"),
+        ];
+        let report = build_normalization_report(
+            &stock_data,
+            &normalizer(NormalizationOptions::default()),
+            1,
+            10,
+        );
+        assert_eq!(report.total_stocks, 1);
+        assert_eq!(report.stocks[0].stock_code, "600001");
+        assert_eq!(report.stocks[0].after, "概念:AI");
+        assert_eq!(report.counts_by_kind[&NormalizationKind::FullWidth], 1);
+    }
+
+    #[test]
+    fn saves_and_loads_options() {
+        let dir =
+            std::env::temp_dir().join(format!("tag-normalization-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(NORMALIZATION_FILE_NAME);
+
+        let options = NormalizationOptions {
+            nfkc: false,
+            ..Default::default()
+        };
+        TagNormalizer::load_or_default(&path)
+            .unwrap()
+            .with_options(options)
+            .save()
+            .unwrap();
+        assert_eq!(
+            TagNormalizer::load_or_default(&path).unwrap().options,
+            options
+        );
+        assert!(!path.with_extension("tmp").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -6,25 +6,112 @@ export interface TagItem {
   detail?: string
 }
 
+export interface StockSample {
+  stock_code: string
+  stock_name: string
+}
+
+// 标签验证结果，由 Rust 按 tag-validation-rules.json（或数据目录中的自定义规则）计算
+export type TagValidationStatus = 'valid' | 'warning' | 'error' | 'special'
+
+export type TagValidationReason =
+  | 'ok'
+  | 'empty_name'
+  | 'illegal_character'
+  | 'control_character'
+  | 'surrounding_whitespace'
+  | 'leading_symbol'
+  | 'meaningless_single_char'
+  | 'detail_illegal_character'
+  | 'detail_surrounding_whitespace'
+  | 'detail_exceeds_max_length'
+  | 'special_single_char'
+  | 'name_too_long'
+  | 'detail_too_long'
+  | 'name_too_short'
+
+export interface TagValidationResult {
+  status: TagValidationStatus
+  reason: TagValidationReason
+  message: string
+}
+
+// 按 stock_fields 投影后的股票：stock_code 始终存在，未请求的字段不会出现（而不是空字符串）
+export type ProjectedStock = Pick<StockCompanyInfo, 'stock_code'> & Partial<StockCompanyInfo>
+
 export interface TagDetails {
+  tag_id: string
   name: string
   detail?: string
   count: number
-  stocks: StockCompanyInfo[]
+  // 摘要模式下为空，改用 sample_stocks；传入 stock_fields 时只包含请求的字段
+  stocks: ProjectedStock[]
+  sample_stocks?: StockSample[]
+  // 标签列表中的标签始终带有验证结果
+  validation?: TagValidationResult
 }
 
+export type TagProjection = 'full' | 'summary'
+
+export type StockField =
+  | 'stock_code'
+  | 'stock_name'
+  | 'company_name'
+  | 'exchange'
+  | 'business_scope'
+  | 'custom_tags'
+  | 'official_website'
+  | 'company_description'
+  | 'underwriting_method'
+  | 'created_at'
+  | 'updated_at'
+  | 'sectors_concepts'
+
 export interface TagCategory {
   name: string
   tags: TagDetails[]
 }
 
+// 选中的标签：只包含标签引用和股票数量，股票通过 getStocksByTag 分页获取
 export interface SelectedTag {
   category_name: string
   tag_name: string
   tag_detail?: string
-  stocks: StockCompanyInfo[]
+  tag_id: string
+  count: number
 }
 
+export type StockSortKey = 'code' | 'name' | 'exchange' | 'updated_at'
+
+// 名称排序方式：拼音（默认，读音相同时按笔画）、笔画、Unicode 码位
+export type CollationOrder = 'pinyin' | 'stroke' | 'unicode'
+
+// 笔画排序实际使用的规则：stroke 正常；root 缺少笔画数据，退回通用规则；code_point 按码位比较
+export type StrokeCollationSupport = 'stroke' | 'root' | 'code_point'
+
+// 按标签引用查询股票列表：传 tag_id，或传 category_name + tag_name (+ tag_detail)
+export interface TagStocksQuery {
+  category_name?: string
+  tag_name?: string
+  tag_detail?: string
+  tag_id?: string
+  // 合并标签层级中子标签的股票（同一只股票只返回一次），默认 true；false 时只返回直接带有该标签的股票
+  include_children?: boolean
+  page: number
+  per_page: number
+  sort_by?: StockSortKey
+  descending?: boolean
+  collation?: CollationOrder
+  stock_fields?: StockField[]
+  // 上一页返回的 next_cursor，传入时忽略 page
+  cursor?: string
+}
+
+export type TagSortKey = 'validation' | 'count' | 'name' | 'updated_at'
+
+// 搜索词的匹配方式：exact（默认）为精确包含匹配；fuzzy 允许错字并匹配拼音，结果按相关度排序
+export type MatchMode = 'exact' | 'fuzzy'
+
 export interface TagStatistics {
   total_tags: number
   total_categories: number
@@ -37,11 +124,20 @@ export interface TagStatistics {
 
 export interface SearchParams {
   search_query?: string
+  match_mode?: MatchMode
   category_name?: string
   tags_page: number
   stocks_page: number
   tags_per_page: number
   stocks_per_page: number
+  projection?: TagProjection
+  sample_size?: number
+  stock_fields?: StockField[]
+  tags_sort_by?: TagSortKey
+  tags_reverse?: boolean
+  collation?: CollationOrder
+  // 上一页返回的 next_cursor，传入时忽略 tags_page
+  cursor?: string
 }
 
 export interface CategoryListResult {
@@ -58,13 +154,66 @@ export interface TagListResult {
   error_tags_count: number
   warning_tags_count: number
   valid_tags_count: number
+  // 下一页的游标，已到末页时为 null；数据集变化后游标失效（stale_cursor）
+  next_cursor: string | null
+  dataset_version: number
 }
 
-export interface StockListResult {
-  stocks: StockCompanyInfo[]
+// 未传 stock_fields 时股票包含全部字段；传入时可用 StockListResult<ProjectedStock>
+export interface StockListResult<S extends ProjectedStock = StockCompanyInfo> {
+  stocks: S[]
   total_stocks: number
   total_pages: number
   current_page: number
+  next_cursor: string | null
+  dataset_version: number
+}
+
+export type CommandErrorCode =
+  | 'not_found'
+  | 'invalid_page'
+  | 'lock_poisoned'
+  | 'invalid_query'
+  | 'stale_cursor'
+  | 'invalid_input'
+  | 'permission_denied'
+  | 'storage'
+  | 'internal'
+
+// 后端命令返回的错误，code 为稳定的错误代码
+export interface CommandError {
+  code: CommandErrorCode
+  message: string
+  // invalid_query 时为查询解析错误的位置和类型，stale_cursor 时为游标和当前的数据集版本，其余为 null
+  details: unknown
+}
+
+// 判断 invoke 抛出的是否为后端的 CommandError
+export function isCommandError(error: unknown): error is CommandError {
+  return typeof error === 'object' && error !== null && 'code' in error && 'message' in error
+}
+
+// API 调用失败时抛出的错误：message 为面向用户的提示，同时保留后端的 code 和 details
+// UI 可据此分别处理，例如 stale_cursor 时从第一页重新加载、invalid_query 时标出查询中的错误位置
+export class RustCommandError extends Error {
+  readonly code: CommandErrorCode | 'unknown'
+  readonly details: unknown
+  // 后端返回的原始错误信息
+  readonly backendMessage: string
+
+  constructor(message: string, error: unknown) {
+    super(message)
+    this.name = 'RustCommandError'
+    if (isCommandError(error)) {
+      this.code = error.code
+      this.details = error.details
+      this.backendMessage = error.message
+    } else {
+      this.code = 'unknown'
+      this.details = null
+      this.backendMessage = String(error)
+    }
+  }
 }
 
 // Rust 后端 API 调用函数
@@ -77,19 +226,27 @@ export class RustTagAPI {
       await invoke('set_stock_data', { stockData: data })
     } catch (error) {
       console.error('Failed to set stock data:', error)
-      throw new Error('无法设置股票数据到后端')
+      throw new RustCommandError('无法设置股票数据到后端', error)
     }
   } 
 
   /**
    * 获取分类列表和统计信息
    */
-  static async getCategories(searchQuery?: string): Promise<CategoryListResult> {
+  static async getCategories(
+    searchQuery?: string,
+    collation?: CollationOrder,
+    matchMode?: MatchMode
+  ): Promise<CategoryListResult> {
     try {
-      return await invoke('get_categories', { searchQuery: searchQuery || null })
+      return await invoke('get_categories', {
+        searchQuery: searchQuery || null,
+        collation: collation || null,
+        matchMode: matchMode || null,
+      })
     } catch (error) {
       console.error('Failed to get categories:', error)
-      throw new Error('无法获取分类列表')
+      throw new RustCommandError('无法获取分类列表', error)
     }
   }
 
@@ -101,22 +258,19 @@ export class RustTagAPI {
       return await invoke('get_tags_by_category', { params })
     } catch (error) {
       console.error('Failed to get tags by category:', error)
-      throw new Error('无法获取标签列表')
+      throw new RustCommandError('无法获取标签列表', error)
     }
   }
 
   /**
    * 获取指定标签下的股票列表（带分页）
    */
-  static async getStocksByTag(
-    selectedTag: SelectedTag,
-    params: SearchParams
-  ): Promise<StockListResult> {
+  static async getStocksByTag(query: TagStocksQuery): Promise<StockListResult> {
     try {
-      return await invoke('get_stocks_by_tag', { selectedTag: selectedTag, params })
+      return await invoke('get_stocks_by_tag', { query })
     } catch (error) {
       console.error('Failed to get stocks by tag:', error)
-      throw new Error('无法获取股票列表')
+      throw new RustCommandError('无法获取股票列表', error)
     }
   }
 
@@ -138,7 +292,7 @@ export class RustTagAPI {
       })
     } catch (error) {
       console.error('Failed to calculate statistics:', error)
-      throw new Error('无法计算统计信息')
+      throw new RustCommandError('无法计算统计信息', error)
     }
   }
 
@@ -150,7 +304,7 @@ export class RustTagAPI {
       return await invoke('parse_tags', { tags })
     } catch (error) {
       console.error('Failed to parse tags:', error)
-      throw new Error('无法解析标签')
+      throw new RustCommandError('无法解析标签', error)
     }
   }
 
@@ -162,12 +316,24 @@ export class RustTagAPI {
       return await invoke('validate_tag', { tag_name: tagName, tag_detail: tagDetail })
     } catch (error) {
       console.error('Failed to validate tag:', error)
-      throw new Error('无法验证标签格式')
+      throw new RustCommandError('无法验证标签格式', error)
     }
   }
 
   /**
-   * 获取标签详情（包含股票信息）
+   * 验证标签格式，返回状态、原因代码和提示信息
+   */
+  static async validateTagDetail(tagName: string, tagDetail?: string): Promise<TagValidationResult> {
+    try {
+      return await invoke('validate_tag_detail', { tagName, tagDetail })
+    } catch (error) {
+      console.error('Failed to validate tag:', error)
+      throw new RustCommandError('无法验证标签格式', error)
+    }
+  }
+
+  /**
+   * 获取标签详情（标签引用和股票数量，不包含股票数据）
    */
   static async getTagDetails(
     categoryName: string,
@@ -182,7 +348,7 @@ export class RustTagAPI {
       })
     } catch (error) {
       console.error('Failed to get tag details:', error)
-      throw new Error('无法获取标签详情')
+      throw new RustCommandError('无法获取标签详情', error)
     }
   }
 
@@ -196,7 +362,7 @@ export class RustTagAPI {
       return await invoke('search_and_filter', { params })
     } catch (error) {
       console.error('Failed to search and filter:', error)
-      throw new Error('搜索过滤失败')
+      throw new RustCommandError('搜索过滤失败', error)
     }
   }
 
@@ -209,7 +375,19 @@ export class RustTagAPI {
       return await invoke('get_data_statistics')
     } catch (error) {
       console.error('Failed to get data statistics:', error)
-      throw new Error('无法获取数据统计')
+      throw new RustCommandError('无法获取数据统计', error)
+    }
+  }
+
+  /**
+   * 获取笔画排序实际使用的规则，不是 stroke 时可提示笔画排序结果可能不准确
+   */
+  static async getStrokeCollationSupport(): Promise<StrokeCollationSupport> {
+    try {
+      return await invoke('get_stroke_collation_support')
+    } catch (error) {
+      console.error('Failed to get stroke collation support:', error)
+      throw new RustCommandError('无法获取笔画排序状态', error)
     }
   }
 }
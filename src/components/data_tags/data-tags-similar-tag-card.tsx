@@ -2,7 +2,7 @@ import { ChevronRight, AlertTriangle, Star, TrendingUp } from 'lucide-react'
 import { Badge } from '@/components/ui/badge'
 import { StockCompanyInfo } from '@/types/stock_details'
 import { cn } from '@/lib/utils'
-import { validateTagFormat, getTagValidationResult } from '@/lib/tag-validation'
+import type { TagValidationResult } from '@/lib/tag-validation'
 
 interface SimilarTagCardProps {
   categoryName: string
@@ -11,6 +11,8 @@ interface SimilarTagCardProps {
   similarity: number
   count: number
   stocks: StockCompanyInfo[]
+  // 后端返回的验证结果，缺省时按格式正确显示
+  validation?: TagValidationResult
   onClick: (categoryName: string, tagName: string, tagDetail: string | undefined, stocks: StockCompanyInfo[]) => void
 }
 
@@ -21,10 +23,10 @@ export function SimilarTagCard({
   similarity, 
   count, 
   stocks, 
+  validation,
   onClick 
 }: SimilarTagCardProps) {
-  const validationStatus = validateTagFormat(tagName, tagDetail)
-  const validationResult = getTagValidationResult(tagName, tagDetail)
+  const validationStatus = validation?.status ?? 'valid'
   
   // 获取状态图标
   const getStatusIcon = () => {
@@ -120,9 +122,9 @@ export function SimilarTagCard({
         )}
         
         {/* 验证消息 */}
-        {validationResult.message && validationStatus !== 'valid' && (
+        {validation?.message && validationStatus !== 'valid' && (
           <div className="text-xs mt-0.5 opacity-75">
-            {validationResult.message}
+            {validation.message}
           </div>
         )}
       </div>
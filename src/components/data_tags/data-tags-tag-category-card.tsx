@@ -2,7 +2,7 @@ import { ChevronRight, AlertTriangle, CheckCircle2, Star } from 'lucide-react'
 import { Badge } from '@/components/ui/badge'
 import { StockCompanyInfo } from '@/types/stock_details'
 import { cn } from '@/lib/utils'
-import { validateTagFormat, getTagValidationResult } from '@/lib/tag-validation'
+import type { TagValidationResult } from '@/lib/tag-validation'
 import { memo, useMemo, useCallback } from 'react'
 
 interface TagInfo {
@@ -10,6 +10,8 @@ interface TagInfo {
   detail?: string
   count: number
   stocks: StockCompanyInfo[]
+  // 后端返回的验证结果，缺省时按格式正确显示
+  validation?: TagValidationResult
 }
 
 interface TagCategoryCardProps {
@@ -24,12 +26,11 @@ const TagItem = memo(({ tag, categoryName, onTagClick }: {
   categoryName: string
   onTagClick: (categoryName: string, tagName: string, tagDetail: string | undefined, stocks: StockCompanyInfo[]) => void
 }) => {
-  // 缓存验证结果
-  const validationData = useMemo(() => {
-    const status = validateTagFormat(tag.name, tag.detail)
-    const result = getTagValidationResult(tag.name, tag.detail)
-    return { status, result }
-  }, [tag.name, tag.detail])
+  // 验证结果由后端计算
+  const validationData = useMemo(() => ({
+    status: tag.validation?.status ?? 'valid',
+    message: tag.validation?.message,
+  }), [tag.validation])
 
   // 获取状态图标
   const statusIcon = useMemo(() => {
@@ -105,9 +106,9 @@ const TagItem = memo(({ tag, categoryName, onTagClick }: {
         )}
         
         {/* 验证消息 */}
-        {validationData.result.message && validationData.status !== 'valid' && (
+        {validationData.message && validationData.status !== 'valid' && (
           <div className="text-xs mt-0.5 opacity-75">
-            {validationData.result.message}
+            {validationData.message}
           </div>
         )}
       </div>
@@ -1,4 +1,4 @@
-import RustTagAPI, { SelectedTag, CategoryListResult, TagListResult, StockListResult } from '@/api/rust-tag-api'
+import RustTagAPI, { SelectedTag, CategoryListResult, TagDetails, TagListResult, StockListResult } from '@/api/rust-tag-api'
 import { fetchStockDetails } from '@/api/stock-details-api'
 import { EmptyState } from '@/components/common/empty-state'
 import { SkeletonCategory, SkeletonTag, SkeletonCard } from '@/components/common/skeleton-card'
@@ -7,12 +7,11 @@ import { Alert, AlertDescription } from '@/components/ui/alert'
 import { Badge } from '@/components/ui/badge'
 import { Pagination, PaginationContent, PaginationItem, PaginationPrevious, PaginationLink, PaginationNext } from '@/components/ui/pagination'
 import { Tooltip } from '@/components/ui/tooltip'
-import { TagFormatValidationResult, validateTagStructureFormat } from '@/lib/tag-validation'
 import { cn } from '@/lib/utils'
 import { TooltipProvider, TooltipTrigger, TooltipContent } from '@radix-ui/react-tooltip'
 import { createLazyFileRoute } from '@tanstack/react-router'
 import { Sparkles, AlertCircle, Tag, CheckCircle, AlertTriangle, TrendingUp } from 'lucide-react'
-import { useState, useEffect, useCallback, useMemo } from 'react'
+import { useState, useEffect, useCallback } from 'react'
 
 export const Route = createLazyFileRoute('/data/tags')({
   component: TagsPage,
@@ -30,7 +29,6 @@ function TagsPage() {
   // 选中的分类和标签
   const [selectedCategory, setSelectedCategory] = useState<string | null>(null)
   const [selectedTag, setSelectedTag] = useState<SelectedTag | null>(null)
-  const [tagSwitchLoading, setTagSwitchLoading] = useState(false)
   
   // 分页状态
   const [tagsPage, setTagsPage] = useState(1)
@@ -149,6 +147,8 @@ function TagsPage() {
         stocks_page: 1, // 固定为1，因为此时不需要股票数据
         tags_per_page: TAGS_PER_PAGE,
         stocks_per_page: STOCKS_PER_PAGE,
+        // 标签列表只展示数量，不需要携带股票数据
+        projection: 'summary' as const,
       }
       const result = await RustTagAPI.getTagsByCategory(params)
       setTagListResult(result)
@@ -166,13 +166,11 @@ function TagsPage() {
     
     setStockLoading(true)
     try {
-      const params = {
-        tags_page: tagsPage,
-        stocks_page: stocksPage,
-        tags_per_page: TAGS_PER_PAGE,
-        stocks_per_page: STOCKS_PER_PAGE,
-      }
-      const result = await RustTagAPI.getStocksByTag(selectedTag, params)
+      const result = await RustTagAPI.getStocksByTag({
+        tag_id: selectedTag.tag_id,
+        page: stocksPage,
+        per_page: STOCKS_PER_PAGE,
+      })
       setStockListResult(result)
     } catch (error) {
       console.error('Failed to load stocks:', error)
@@ -180,7 +178,7 @@ function TagsPage() {
     } finally {
       setStockLoading(false)
     }
-  }, [selectedTag, rustInitialized, tagsPage, stocksPage])
+  }, [selectedTag, rustInitialized, stocksPage])
 
   // 处理分类选择
   const handleCategorySelect = useCallback((categoryName: string | null) => {
@@ -190,20 +188,16 @@ function TagsPage() {
     setStocksPage(1)
   }, [])
 
-  // 优化的标签点击处理 - 使用 Rust 后端获取标签详情
-  const handleTagClick = useCallback(async (categoryName: string, tagName: string, tagDetail: string | undefined) => {
-    setTagSwitchLoading(true)
-    
-    try {
-      const tagDetails = await RustTagAPI.getTagDetails(categoryName, tagName, tagDetail)
-      setSelectedTag(tagDetails)
-      setStocksPage(1)
-    } catch (error) {
-      console.error('Failed to get tag details:', error)
-      setError(error instanceof Error ? error.message : '获取标签详情失败')
-    } finally {
-      setTagSwitchLoading(false)
-    }
+  // 标签点击处理 - 只记录标签引用，股票由 getStocksByTag 分页加载
+  const handleTagClick = useCallback((categoryName: string, tag: TagDetails) => {
+    setSelectedTag({
+      category_name: categoryName,
+      tag_name: tag.name,
+      tag_detail: tag.detail,
+      tag_id: tag.tag_id,
+      count: tag.count,
+    })
+    setStocksPage(1)
   }, [])
 
   // 清除搜索和重置状态
@@ -236,25 +230,6 @@ function TagsPage() {
     // 标签更新成功
   }, [])
 
-  // 验证标签格式（直接使用函数，无需 useCallback）
-  const validateTag = (categoryName: string, tagName: string, tagDetail?: string): TagFormatValidationResult => {
-    return validateTagStructureFormat(categoryName, tagName, tagDetail)
-  }
-
-  // 计算当前页面标签的格式验证状态（用于显示错误提示）
-  const tagValidationMap = useMemo(() => {
-    if (!tagListResult || !selectedCategory) return new Map<string, TagFormatValidationResult>()
-    
-    const map = new Map<string, TagFormatValidationResult>()
-    tagListResult.tags.forEach(tag => {
-      const key = `${tag.name}:${tag.detail || ''}`
-      const validation = validateTag(selectedCategory, tag.name, tag.detail)
-      map.set(key, validation)
-    })
-    
-    return map
-  }, [tagListResult, selectedCategory])
-
   // 初始化数据
   useEffect(() => {
     const initializeData = async () => {
@@ -476,15 +451,16 @@ function TagsPage() {
                   ) : tagListResult?.tags.length ? (
                     tagListResult.tags.map((tag, index) => {
                       const tagKey = `${tag.name}:${tag.detail || ''}`
-                      const validation = tagValidationMap.get(tagKey)
-                      const hasFormatError = validation && !validation.isValid
+                      // 验证结果由后端按 tag-validation-rules.json 计算
+                      const validation = tag.validation
+                      const hasFormatError = validation?.status === 'error'
                       
                       return (
                         <TooltipProvider key={tagKey}>
                           <Tooltip>
                             <TooltipTrigger asChild>
                               <button
-                                onClick={() => handleTagClick(selectedCategory, tag.name, tag.detail)}
+                                onClick={() => handleTagClick(selectedCategory, tag)}
                                 className={cn(
                                   "w-full text-left p-3 rounded-lg border transition-all duration-200",
                                   "hover:shadow-md hover:scale-[1.02] active:scale-[0.98]",
@@ -537,11 +513,7 @@ function TagsPage() {
                               <TooltipContent className="max-w-xs">
                                 <div className="space-y-1">
                                   <p className="font-semibold text-xs">格式错误：</p>
-                                  <ul className="text-xs space-y-0.5">
-                                    {validation.errors.map((error, i) => (
-                                      <li key={i} className="text-destructive">• {error}</li>
-                                    ))}
-                                  </ul>
+                                  <p className="text-xs text-destructive">• {validation.message}</p>
                                   <p className="text-xs text-muted-foreground mt-2">
                                     正确格式: 分类:内容{'{补充}'}
                                   </p>
@@ -606,7 +578,7 @@ function TagsPage() {
               )}
 
               {/* 已选择分类但未选择标签 */}
-              {selectedCategory && !selectedTag && !tagSwitchLoading && (
+              {selectedCategory && !selectedTag && (
                 <EmptyState
                   icon={Tag}
                   title="选择一个标签"
@@ -615,19 +587,8 @@ function TagsPage() {
                 />
               )}
 
-              {/* 标签切换加载状态 */}
-              {tagSwitchLoading && (
-                <div className="space-y-6 animate-in fade-in duration-300">
-                  <div className="grid grid-cols-1 2xl:grid-cols-2 gap-4">
-                    {Array.from({ length: 4 }).map((_, i) => (
-                      <SkeletonCard key={i} delay={i * 100} />
-                    ))}
-                  </div>
-                </div>
-              )}
-
               {/* 股票列表 */}
-              {selectedTag && !tagSwitchLoading && (
+              {selectedTag && (
                 <div className="space-y-6 animate-in fade-in slide-in-from-bottom-4 duration-500">
                   {/* 标签详情信息 */}
                   {selectedTag.tag_detail && (
@@ -645,7 +606,7 @@ function TagsPage() {
                         <TrendingUp className="h-5 w-5 text-primary" />
                         相关股票
                         <Badge variant="outline" className="ml-2">
-                          {stockListResult?.total_stocks || selectedTag.stocks.length}
+                          {stockListResult?.total_stocks ?? selectedTag.count}
                         </Badge>
                       </h3>
                     </div>
@@ -656,8 +617,8 @@ function TagsPage() {
                         Array.from({ length: 4 }).map((_, i) => (
                           <SkeletonCard key={i} delay={i * 100} />
                         ))
-                      ) : (stockListResult?.stocks || selectedTag.stocks).length > 0 ? (
-                        (stockListResult?.stocks || selectedTag.stocks).map((stock, index) => (
+                      ) : stockListResult?.stocks.length ? (
+                        stockListResult.stocks.map((stock, index) => (
                           <div
                             key={stock.stock_code}
                             className="animate-in fade-in slide-in-from-bottom-2"